@@ -1,90 +1,536 @@
 //! This crate contains the proc macros for the [`perstruct`](https://crates.io/crates/perstruct) crate.
 //! It is not intended to be used directly.
 
+use quote::format_ident;
 use quote::quote;
 use quote::ToTokens;
+use syn::punctuated::Punctuated;
 use syn::{parse_macro_input, ItemStruct};
 
+const UNKNOWN_FIELD_ATTRIBUTE: &str = "Unknown perstruct attribute \
+    (available: key, default_fn, try_default_fn, default, skip, with, serialize_with, \
+    deserialize_with, alias, aliases, dedup, max_len, truncate, store, key_const, into_setter, \
+    no_default, getter, setter, value_default_missing, serialize_as, one_of, on_invalid, order, validate, env, readonly, display_fromstr, lazy, secret, skip_default, dynamic_key, required, precise_updates)";
+/// Name of the store of fields without a `store` attribute, see `perstruct::DEFAULT_STORE`.
+const DEFAULT_STORE: &str = "default";
+const UNKNOWN_STRUCT_ATTRIBUTE: &str = "Unknown perstruct struct attribute \
+    (available: key_fn, crate, serde_json, value_format, single_document, into_setters, dirty, \
+    case_insensitive, prefix, observe, rename_all, getter_prefix, setter_prefix, track_loaded, \
+    keep_visibility, preserve_unknown, version, migrate_fn, bound, serde, post_set, debug, codec, \
+    view, on_load_raw, on_save_raw, validate)";
+/// Name of the field holding the keys of the dirty fields, injected by the attribute macro and
+/// declared by the user when deriving.
+const DIRTY_FIELDS: &str = "_perstruct_dirty_fields";
+
 #[proc_macro_attribute]
 pub fn perstruct(
-    _args: proc_macro::TokenStream,
+    args: proc_macro::TokenStream,
     input: proc_macro::TokenStream,
 ) -> proc_macro::TokenStream {
-    let input: ItemStruct = parse_macro_input!(input as ItemStruct);
-    process_struct(input)
+    let args =
+        parse_macro_input!(args with Punctuated::<syn::Meta, syn::Token![,]>::parse_terminated);
+    parse_struct(input)
+        .and_then(|input| Ok((input, parse_struct_options(args)?)))
+        .and_then(|(input, options)| process_struct(input, options, false))
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Alternative to the attribute macro that doesn't rewrite the struct. The struct options are
+/// given in `#[perstruct(...)]` attributes on the struct, and the dirty fields are kept in a
+/// `_perstruct_dirty_fields: perstruct::DirtyFields` field declared by the user.
+#[proc_macro_derive(Perstruct, attributes(perstruct))]
+pub fn derive_perstruct(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    parse_struct(input)
+        .and_then(|input| {
+            let args = input
+                .attrs
+                .iter()
+                .filter(|attr| attr.path().is_ident("perstruct"))
+                .map(|attr| {
+                    attr.parse_args_with(Punctuated::<syn::Meta, syn::Token![,]>::parse_terminated)
+                })
+                .collect::<syn::Result<Vec<_>>>()?;
+            let options = parse_struct_options(args.into_iter().flatten().collect())?;
+            process_struct(input, options, true)
+        })
         .unwrap_or_else(syn::Error::into_compile_error)
         .into()
 }
 
-fn process_struct(mut input: ItemStruct) -> syn::Result<proc_macro2::TokenStream> {
-    if input.generics.params.len() != 0 {
-        panic!("Struct cannot be generic");
+/// Parses the item the macro is applied to, rejecting everything but structs with named fields,
+/// since the keys are derived from the field names.
+fn parse_struct(input: proc_macro::TokenStream) -> syn::Result<ItemStruct> {
+    let input = match syn::parse::<syn::Item>(input)? {
+        syn::Item::Struct(input) => input,
+        syn::Item::Enum(input) => {
+            return Err(syn::Error::new_spanned(
+                input.enum_token,
+                "Perstruct: enums are not supported, only structs with named fields",
+            ))
+        }
+        syn::Item::Union(input) => {
+            return Err(syn::Error::new_spanned(
+                input.union_token,
+                "Perstruct: unions are not supported, only structs with named fields",
+            ))
+        }
+        input => {
+            return Err(syn::Error::new_spanned(
+                input,
+                "Perstruct: only structs with named fields are supported",
+            ))
+        }
+    };
+    match &input.fields {
+        syn::Fields::Named(_) => Ok(input),
+        // Suggest the named equivalent of a newtype, keyed by the struct name
+        syn::Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+            let ident = &input.ident;
+            let ty = fields.unnamed[0].ty.to_token_stream();
+            let key = to_snake_case(&ident.to_string());
+            Err(syn::Error::new_spanned(
+                fields,
+                format!(
+                    "Perstruct: tuple structs are not supported, the fields need names to derive \
+                    their keys from. Name the field and give it a key instead: \
+                    `struct {ident} {{ #[perstruct(key = \"{key}\")] value: {ty} }}`"
+                ),
+            ))
+        }
+        syn::Fields::Unnamed(fields) => Err(syn::Error::new_spanned(
+            fields,
+            "Perstruct: tuple structs are not supported, the fields need names to derive their \
+            keys from. Name the fields instead, their names are the keys unless a `key` is given",
+        )),
+        syn::Fields::Unit => Err(syn::Error::new_spanned(
+            &input.ident,
+            "Perstruct: unit structs are not supported, there are no fields to persist. Add named \
+            fields to persist, e.g. `struct Settings { volume: u8 }`",
+        )),
+    }
+}
+
+fn parse_struct_options(
+    args: Punctuated<syn::Meta, syn::Token![,]>,
+) -> syn::Result<PerstructOptions> {
+    let mut options = PerstructOptions::default();
+    for meta in args {
+        match meta {
+            syn::Meta::NameValue(syn::MetaNameValue {
+                path,
+                value: syn::Expr::Lit(lit),
+                ..
+            }) => match path {
+                p if p.is_ident("key_fn") => {
+                    options.key_fn = Some(parse_lit_str(lit.lit)?.parse()?);
+                }
+                p if p.is_ident("crate") => {
+                    options.crate_path = Some(parse_lit_str(lit.lit)?.parse()?);
+                }
+                p if p.is_ident("serde_json") => {
+                    options.serde_json_path = Some(parse_lit_str(lit.lit)?.parse()?);
+                }
+                p if p.is_ident("codec") => {
+                    options.codec = Some(parse_lit_str(lit.lit)?.parse()?);
+                }
+                p if p.is_ident("dirty") => {
+                    let dirty = parse_lit_str(lit.lit)?;
+                    options.dirty = match dirty.value().as_str() {
+                        "hashset" => DirtyTracking::HashSet,
+                        "vec" => DirtyTracking::Vec,
+                        "bitset" => DirtyTracking::Bitset,
+                        _ => {
+                            return Err(syn::Error::new_spanned(
+                                dirty,
+                                "Expected \"hashset\", \"vec\" or \"bitset\"",
+                            ))
+                        }
+                    };
+                }
+                p if p.is_ident("observe") => {
+                    let observe = parse_lit_str(lit.lit)?;
+                    match observe.value().as_str() {
+                        "channel" => options.observe = true,
+                        "callbacks" => options.callbacks = true,
+                        _ => {
+                            return Err(syn::Error::new_spanned(
+                                observe,
+                                "Expected \"channel\" or \"callbacks\"",
+                            ))
+                        }
+                    }
+                }
+                p if p.is_ident("rename_all") => {
+                    let rename_all = parse_lit_str(lit.lit)?;
+                    options.rename_all = Some(match rename_all.value().as_str() {
+                        "camelCase" => RenameRule::Camel,
+                        "PascalCase" => RenameRule::Pascal,
+                        "kebab-case" => RenameRule::Kebab,
+                        "SCREAMING_SNAKE_CASE" => RenameRule::ScreamingSnake,
+                        _ => {
+                            return Err(syn::Error::new_spanned(
+                                rename_all,
+                                "Expected \"camelCase\", \"PascalCase\", \"kebab-case\" or \
+                                \"SCREAMING_SNAKE_CASE\"",
+                            ))
+                        }
+                    });
+                }
+                p if p.is_ident("getter_prefix") => {
+                    options.getter_prefix = Some(parse_method_prefix(lit.lit)?);
+                }
+                p if p.is_ident("setter_prefix") => {
+                    options.setter_prefix = Some(parse_method_prefix(lit.lit)?);
+                }
+                p if p.is_ident("version") => match lit.lit {
+                    syn::Lit::Int(version) => options.version = Some(version.base10_parse()?),
+                    lit => return Err(syn::Error::new_spanned(lit, "Expected integer literal")),
+                },
+                p if p.is_ident("bound") => {
+                    let bound = parse_lit_str(lit.lit)?;
+                    options.bound = Some(bound.parse_with(
+                        Punctuated::<syn::WherePredicate, syn::Token![,]>::parse_terminated,
+                    )?);
+                }
+                p if p.is_ident("migrate_fn") => {
+                    options.migrate_fn = Some(parse_lit_str(lit.lit)?.parse()?);
+                }
+                p if p.is_ident("post_set") => {
+                    options.post_set = Some(parse_lit_str(lit.lit)?.parse()?);
+                }
+                p if p.is_ident("on_load_raw") => {
+                    options.on_load_raw = Some(parse_lit_str(lit.lit)?.parse()?);
+                }
+                p if p.is_ident("validate") => {
+                    options.validate = Some(parse_lit_str(lit.lit)?.parse()?);
+                }
+                p if p.is_ident("on_save_raw") => {
+                    options.on_save_raw = Some(parse_lit_str(lit.lit)?.parse()?);
+                }
+                p if p.is_ident("prefix") => {
+                    options.prefix = parse_lit_str(lit.lit)?.value();
+                }
+                p if p.is_ident("single_document") => {
+                    options.single_document = Some(parse_lit_str(lit.lit)?);
+                }
+                p if p.is_ident("value_format") => {
+                    let value_format = parse_lit_str(lit.lit)?;
+                    options.plain_values = match value_format.value().as_str() {
+                        "plain" => true,
+                        "json" => false,
+                        _ => {
+                            return Err(syn::Error::new_spanned(
+                                value_format,
+                                "Expected \"json\" or \"plain\"",
+                            ))
+                        }
+                    };
+                }
+                thing => {
+                    return Err(syn::Error::new_spanned(
+                        thing.into_token_stream(),
+                        UNKNOWN_STRUCT_ATTRIBUTE,
+                    ))
+                }
+            },
+            syn::Meta::Path(path) if path.is_ident("into_setters") => {
+                options.into_setters = true;
+            }
+            syn::Meta::Path(path) if path.is_ident("keep_visibility") => {
+                options.keep_visibility = true;
+            }
+            syn::Meta::Path(path) if path.is_ident("serde") => {
+                options.serde = true;
+            }
+            syn::Meta::Path(path) if path.is_ident("debug") => {
+                options.debug = true;
+            }
+            syn::Meta::Path(path) if path.is_ident("view") => {
+                options.view = true;
+            }
+            syn::Meta::Path(path) if path.is_ident("preserve_unknown") => {
+                options.preserve_unknown = true;
+            }
+            syn::Meta::Path(path) if path.is_ident("track_loaded") => {
+                options.track_loaded = true;
+            }
+            syn::Meta::Path(path) if path.is_ident("case_insensitive") => {
+                options.case_insensitive = true;
+            }
+            thing => {
+                return Err(syn::Error::new_spanned(
+                    thing.into_token_stream(),
+                    UNKNOWN_STRUCT_ATTRIBUTE,
+                ))
+            }
+        }
     }
+    Ok(options)
+}
+
+/// Generates the perstruct impls for `input`. With `derive`, the struct is not emitted again and
+/// must declare the dirty fields field itself, otherwise it's rewritten and the field is injected.
+fn process_struct(
+    mut input: ItemStruct,
+    mut options: PerstructOptions,
+    derive: bool,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let struct_ty = StructTy::new(&input, &options)?;
 
     let mut fields = vec![];
-    let mut skipped_fields = vec![];
+    let mut skipped_fields: Vec<SkippedField> = vec![];
+    let mut has_dirty_fields = false;
     for field in input.fields.iter_mut() {
-        let ident = field.ident.clone().unwrap();
+        // `parse_struct` only accepts named fields
+        let Some(ident) = field.ident.clone() else {
+            return Err(syn::Error::new_spanned(
+                field,
+                "Perstruct: fields must be named",
+            ));
+        };
+        // The key of `r#type` is `type`
+        let name = syn::ext::IdentExt::unraw(&ident).to_string();
+        if derive && ident == DIRTY_FIELDS {
+            has_dirty_fields = true;
+            continue;
+        }
         let mut to_remove: Vec<syn::Path> = vec![];
         let mut key: Option<String> = None;
+        let mut key_const: Option<syn::Path> = None;
         let mut default_fn = None;
-        let mut default_lit = None;
+        let mut try_default_fn = None;
+        let mut getter: Option<syn::LitStr> = None;
+        let mut setter: Option<syn::LitStr> = None;
+        let mut default: Option<syn::Expr> = None;
+        let mut serialize_with: Option<syn::Path> = None;
+        let mut deserialize_with: Option<syn::Path> = None;
+        let mut serialize_as: Option<syn::Type> = None;
+        let mut aliases: Vec<syn::LitStr> = vec![];
+        let mut normalize = VecNormalization::default();
+        let mut store: Option<syn::LitStr> = None;
+        let mut into_setter = false;
+        let mut no_default = false;
+        let mut readonly = false;
+        let mut lazy = false;
+        let mut secret = false;
+        let mut skip_default = false;
+        let mut dynamic_key = false;
+        let mut required = false;
+        let mut precise_updates = false;
+        let mut value_default_missing = false;
+        let mut one_of: Option<(syn::Path, Vec<syn::LitStr>)> = None;
+        let mut on_invalid: Option<syn::LitStr> = None;
+        let mut validate: Option<syn::Path> = None;
+        let mut env: Option<syn::LitStr> = None;
+        let mut order: Option<usize> = None;
         let mut skip = false;
+        let mut used_attributes: Vec<syn::Path> = vec![];
 
         for attr in &field.attrs {
             let attr_path = attr.path().clone();
             if attr_path.is_ident("perstruct") {
                 to_remove.push(attr_path);
-                let meta = attr.parse_args()?;
-                match meta {
-                    syn::Meta::NameValue(syn::MetaNameValue {
-                        path,
-                        value: syn::Expr::Lit(lit),
-                        ..
-                    }) => match path {
-                        p if p.is_ident("key") => {
-                            if let syn::Lit::Str(s) = lit.lit {
-                                key = Some(s.value());
-                            } else {
+                let metas = attr
+                    .parse_args_with(Punctuated::<syn::Meta, syn::Token![,]>::parse_terminated)?;
+                for meta in metas {
+                    used_attributes.push(meta.path().clone());
+                    match meta {
+                        // Any expression, string literals are also converted with `From<&str>`
+                        syn::Meta::NameValue(syn::MetaNameValue { path, value, .. })
+                            if path.is_ident("default") =>
+                        {
+                            default = Some(value);
+                        }
+                        syn::Meta::NameValue(syn::MetaNameValue {
+                            path,
+                            value: syn::Expr::Lit(lit),
+                            ..
+                        }) => match path {
+                            p if p.is_ident("key") => {
+                                key = Some(parse_lit_str(lit.lit)?.value());
+                            }
+                            p if p.is_ident("key_const") => {
+                                key_const = Some(parse_lit_str(lit.lit)?.parse()?);
+                            }
+                            p if p.is_ident("default_fn") => {
+                                default_fn = Some(parse_fn_path(lit.lit)?);
+                            }
+                            p if p.is_ident("getter") => {
+                                getter = Some(parse_lit_str(lit.lit)?);
+                            }
+                            p if p.is_ident("setter") => {
+                                setter = Some(parse_lit_str(lit.lit)?);
+                            }
+                            p if p.is_ident("try_default_fn") => {
+                                try_default_fn = Some(parse_fn_path(lit.lit)?);
+                            }
+                            p if p.is_ident("with") => {
+                                let with: syn::Path = parse_lit_str(lit.lit)?.parse()?;
+                                serialize_with = Some(syn::parse_quote! { #with::serialize });
+                                deserialize_with = Some(syn::parse_quote! { #with::deserialize });
+                            }
+                            p if p.is_ident("serialize_with") => {
+                                serialize_with = Some(parse_lit_str(lit.lit)?.parse()?);
+                            }
+                            p if p.is_ident("deserialize_with") => {
+                                deserialize_with = Some(parse_lit_str(lit.lit)?.parse()?);
+                            }
+                            p if p.is_ident("serialize_as") => {
+                                serialize_as = Some(parse_lit_str(lit.lit)?.parse()?);
+                            }
+                            p if p.is_ident("alias") => {
+                                aliases.push(parse_lit_str(lit.lit)?);
+                            }
+                            p if p.is_ident("aliases") => {
+                                let list = parse_lit_str(lit.lit)?;
+                                for alias in list.value().split(',').map(str::trim) {
+                                    if alias.is_empty() {
+                                        return Err(syn::Error::new_spanned(
+                                            list,
+                                            "Expected comma-separated keys",
+                                        ));
+                                    }
+                                    aliases.push(syn::LitStr::new(alias, list.span()));
+                                }
+                            }
+                            p if p.is_ident("store") => {
+                                store = Some(parse_lit_str(lit.lit)?);
+                            }
+                            p if p.is_ident("on_invalid") => {
+                                on_invalid = Some(parse_lit_str(lit.lit)?);
+                            }
+                            p if p.is_ident("validate") => {
+                                validate = Some(parse_lit_str(lit.lit)?.parse()?);
+                            }
+                            p if p.is_ident("env") => {
+                                env = Some(parse_lit_str(lit.lit)?);
+                            }
+                            p if p.is_ident("max_len") => {
+                                normalize.max_len = Some(match lit.lit {
+                                    syn::Lit::Int(int) => int.base10_parse()?,
+                                    lit => {
+                                        return Err(syn::Error::new_spanned(
+                                            lit,
+                                            "Expected integer literal",
+                                        ))
+                                    }
+                                });
+                            }
+                            p if p.is_ident("order") => {
+                                order = Some(match lit.lit {
+                                    syn::Lit::Int(int) => int.base10_parse()?,
+                                    lit => {
+                                        return Err(syn::Error::new_spanned(
+                                            lit,
+                                            "Expected integer literal",
+                                        ))
+                                    }
+                                });
+                            }
+                            p if p.is_ident("truncate") => {
+                                let truncate = parse_lit_str(lit.lit)?;
+                                normalize.truncate_front = match truncate.value().as_str() {
+                                    "front" => true,
+                                    "back" => false,
+                                    _ => {
+                                        return Err(syn::Error::new_spanned(
+                                            truncate,
+                                            "Expected \"front\" or \"back\"",
+                                        ))
+                                    }
+                                };
+                            }
+                            thing => {
                                 return Err(syn::Error::new_spanned(
-                                    lit,
-                                    "Expected string literal",
+                                    thing.into_token_stream(),
+                                    UNKNOWN_FIELD_ATTRIBUTE,
+                                ))
+                            }
+                        },
+                        syn::Meta::NameValue(syn::MetaNameValue {
+                            path,
+                            value: syn::Expr::Array(array),
+                            ..
+                        }) if path.is_ident("alias") => {
+                            for elem in array.elems {
+                                match elem {
+                                    syn::Expr::Lit(lit) => aliases.push(parse_lit_str(lit.lit)?),
+                                    elem => {
+                                        return Err(syn::Error::new_spanned(
+                                            elem,
+                                            "Expected string literal",
+                                        ))
+                                    }
+                                }
+                            }
+                        }
+                        syn::Meta::List(list) if list.path.is_ident("one_of") => {
+                            let values = list.parse_args_with(
+                                Punctuated::<syn::LitStr, syn::Token![,]>::parse_terminated,
+                            )?;
+                            if values.is_empty() {
+                                return Err(syn::Error::new_spanned(
+                                    list,
+                                    "Perstruct: `one_of` needs at least one value",
                                 ));
                             }
+                            one_of = Some((list.path, values.into_iter().collect()));
                         }
-                        p if p.is_ident("default_fn") => {
-                            if let syn::Lit::Str(s) = lit.lit {
-                                default_fn = Some(s.value());
+                        syn::Meta::Path(path) => {
+                            if path.is_ident("skip") {
+                                skip = true;
+                            } else if path.is_ident("dedup") {
+                                normalize.dedup = true;
+                            } else if path.is_ident("into_setter") {
+                                into_setter = true;
+                            } else if path.is_ident("no_default") {
+                                no_default = true;
+                            } else if path.is_ident("readonly") {
+                                readonly = true;
+                            } else if path.is_ident("lazy") {
+                                lazy = true;
+                            } else if path.is_ident("secret") {
+                                secret = true;
+                            } else if path.is_ident("skip_default") {
+                                skip_default = true;
+                            } else if path.is_ident("dynamic_key") {
+                                dynamic_key = true;
+                            } else if path.is_ident("required") {
+                                required = true;
+                            } else if path.is_ident("precise_updates") {
+                                precise_updates = true;
+                            } else if path.is_ident("display_fromstr") {
+                                let crate_path = options.crate_path();
+                                let ty = &field.ty;
+                                serialize_with = Some(syn::parse_quote! {
+                                    #crate_path::__private::display_to_string
+                                });
+                                deserialize_with = Some(syn::parse_quote! {
+                                    #crate_path::__private::parse_from_str::<#ty>
+                                });
+                            } else if path.is_ident("value_default_missing") {
+                                value_default_missing = true;
                             } else {
                                 return Err(syn::Error::new_spanned(
-                                    lit,
-                                    "Expected string literal",
+                                    path.into_token_stream(),
+                                    UNKNOWN_FIELD_ATTRIBUTE,
                                 ));
                             }
                         }
-                        p if p.is_ident("default") => {
-                            default_lit = Some(lit.lit);
-                        }
-                        thing => return Err(syn::Error::new_spanned(
-                            thing.into_token_stream(),
-                            "Unknown perstruct attribute (available: key, default_fn, default, skip)",
-                        )),
-                    },
-                    syn::Meta::Path(path) => {
-                        if path.is_ident("skip") {
-                            skip = true;
-                        } else {
+                        syn::Meta::NameValue(syn::MetaNameValue { path, value, .. }) => {
+                            let name = path.to_token_stream().to_string();
                             return Err(syn::Error::new_spanned(
-                                    path.into_token_stream(),
-                                    "Unknown perstruct attribute (available: key, default_fn, default, skip)",
-                                ));
+                                value,
+                                format!(
+                                    "Perstruct: `{name}` takes a literal, only `default` takes \
+                                    any expression"
+                                ),
+                            ));
+                        }
+                        syn::Meta::List(list) => {
+                            return Err(syn::Error::new_spanned(list.path, UNKNOWN_FIELD_ATTRIBUTE))
                         }
-                    }
-                    thing => {
-                        return Err(syn::Error::new_spanned(
-                            attr.into_token_stream(),
-                            format!("Parse args failed: {thing:?}"),
-                        ))
                     }
                 }
             }
@@ -92,20 +538,134 @@ fn process_struct(mut input: ItemStruct) -> syn::Result<proc_macro2::TokenStream
         for attr in to_remove {
             field.attrs.retain(|a| a.path() != &attr);
         }
+        check_attribute_conflicts(&used_attributes)?;
+        let reject_invalid = check_one_of(
+            one_of.as_ref(),
+            validate.as_ref(),
+            on_invalid.as_ref(),
+            default.as_ref(),
+            default_fn.is_some() || try_default_fn.is_some(),
+            no_default,
+        )?;
+        let cfg_attrs = field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("cfg"))
+            .cloned()
+            .collect();
         if skip {
-            skipped_fields.push(ident);
+            skipped_fields.push(SkippedField { ident, cfg_attrs });
+            continue;
+        }
+        if !options.keep_visibility {
+            field.vis = syn::Visibility::Inherited;
+        }
+        if dynamic_key {
+            let Some(value_ty) = string_map_value_type(&field.ty) else {
+                return Err(syn::Error::new_spanned(
+                    &field.ty,
+                    "Perstruct: `dynamic_key` fields must be a `HashMap<String, T>`",
+                ));
+            };
+            let key = key.unwrap_or_else(|| match options.rename_all {
+                Some(rule) => rule.apply(&name),
+                None => name.clone(),
+            });
+            let key = format!("{}{key}", options.prefix);
+            options.dynamic_fields.push(DynamicField {
+                ident: ident.clone(),
+                key: syn::LitStr::new(&key, ident.span()),
+                row_prefix: syn::LitStr::new(&format!("{key}."), ident.span()),
+                value_ty: value_ty.clone(),
+                cfg_attrs: cfg_attrs.clone(),
+            });
+            // Initialized like skipped fields, and loaded and saved by rows
+            skipped_fields.push(SkippedField { ident, cfg_attrs });
             continue;
         }
-        field.vis = syn::Visibility::Inherited;
         let ty = field.ty.clone();
+        if lazy {
+            let crate_path = options.crate_path();
+            field.ty = syn::parse_quote! { #crate_path::Lazy<#ty> };
+        }
+        let persisted_key = match (key, key_const) {
+            (_, Some(key_const)) => PersistedKey::Runtime(key_const.into_token_stream()),
+            (key, None) => PersistedKey::Literal(syn::LitStr::new(
+                &format!(
+                    "{}{}",
+                    options.prefix,
+                    key.unwrap_or_else(|| match options.rename_all {
+                        Some(rule) => rule.apply(&name),
+                        None => name.clone(),
+                    })
+                ),
+                proc_macro2::Span::mixed_site(),
+            )),
+        };
+        // The key in errors of the environment variable, which can't be built from runtime keys
+        let env = env.map(|variable| {
+            let key = match &persisted_key {
+                PersistedKey::Literal(key) => key.value(),
+                PersistedKey::Runtime(_) => name.clone(),
+            };
+            let error_key = syn::LitStr::new(
+                &format!("{key} (env {})", variable.value()),
+                variable.span(),
+            );
+            (variable, error_key)
+        });
+        let aliases = aliases
+            .into_iter()
+            .map(|alias| {
+                syn::LitStr::new(
+                    &format!("{}{}", options.prefix, alias.value()),
+                    alias.span(),
+                )
+            })
+            .collect();
         fields.push(PerstructField {
             ident,
-            key,
+            persisted_key,
             default_fn,
-            default_lit,
+            try_default_fn,
+            getter: getter.map(|getter| getter.parse()).transpose()?,
+            setter: setter.map(|setter| setter.parse()).transpose()?,
+            default,
+            serialize_with,
+            deserialize_with,
+            aliases,
+            normalize,
+            store: store.map(|store| store.value()),
+            into_setter,
+            no_default,
+            readonly,
+            lazy,
+            secret,
+            skip_default,
+            // `no_default` fields are required, with or without `required`
+            required: required || no_default,
+            precise_updates,
+            value_default_missing,
+            serialize_as,
+            one_of: one_of.map(|(_, values)| values).unwrap_or_default(),
+            validate,
+            reject_invalid,
+            env,
+            order: order.unwrap_or(fields.len()),
+            has_order: order.is_some(),
+            cfg_attrs,
             ty,
         });
     }
+    check_duplicate_keys(&options, &fields)?;
+    // Everything generated per field follows the persisted order. Fields with an `order` come
+    // before fields without one at the same position.
+    fields.sort_by_key(|field| (field.order, !field.has_order));
+
+    // Before the injected fields are added
+    let debug_impl = options
+        .debug
+        .then(|| generate_debug_impl(&struct_ty, &input.fields, &fields));
 
     // Add _perstruct_dirty_fields field
     let syn::Fields::Named(syn::FieldsNamed { named, .. }) = &mut input.fields else {
@@ -114,220 +674,4346 @@ fn process_struct(mut input: ItemStruct) -> syn::Result<proc_macro2::TokenStream
             "Perstruct: struct must have named fields",
         ));
     };
-    named.push(syn::Field {
-        attrs: vec![],
-        vis: syn::Visibility::Inherited,
-        mutability: syn::FieldMutability::None,
-        ident: Some(syn::Ident::new(
-            "_perstruct_dirty_fields",
-            proc_macro2::Span::mixed_site(),
-        )),
-        colon_token: None,
-        ty: syn::Type::Verbatim(quote! { std::collections::HashSet<&'static str> }),
-    });
+    if !derive {
+        named.push(syn::Field {
+            attrs: vec![],
+            vis: syn::Visibility::Inherited,
+            mutability: syn::FieldMutability::None,
+            ident: Some(syn::Ident::new(
+                DIRTY_FIELDS,
+                proc_macro2::Span::mixed_site(),
+            )),
+            colon_token: None,
+            ty: syn::Type::Verbatim(options.dirty_ty()),
+        });
+        if options.observe {
+            let change_ident = format_ident!("{}Change", input.ident);
+            named.push(syn::Field {
+                attrs: vec![],
+                vis: syn::Visibility::Inherited,
+                mutability: syn::FieldMutability::None,
+                ident: Some(syn::Ident::new(
+                    "_perstruct_observer",
+                    proc_macro2::Span::mixed_site(),
+                )),
+                colon_token: None,
+                ty: syn::Type::Verbatim(
+                    quote! { ::std::option::Option<::std::sync::mpsc::Sender<#change_ident>> },
+                ),
+            });
+        }
+        if options.callbacks {
+            named.push(syn::Field {
+                attrs: vec![],
+                vis: syn::Visibility::Inherited,
+                mutability: syn::FieldMutability::None,
+                ident: Some(syn::Ident::new(
+                    "_perstruct_callbacks",
+                    proc_macro2::Span::mixed_site(),
+                )),
+                colon_token: None,
+                ty: syn::Type::Verbatim({
+                    let callback_ty = options.callback_ty();
+                    quote! { ::std::vec::Vec<#callback_ty> }
+                }),
+            });
+        }
+        if !options.dynamic_fields.is_empty() {
+            named.push(syn::Field {
+                attrs: vec![],
+                vis: syn::Visibility::Inherited,
+                mutability: syn::FieldMutability::None,
+                ident: Some(syn::Ident::new(
+                    "_perstruct_dynamic_saved",
+                    proc_macro2::Span::mixed_site(),
+                )),
+                colon_token: None,
+                ty: syn::Type::Verbatim(quote! { ::std::collections::HashMap<::std::string::String, ::std::string::String> }),
+            });
+        }
+        if options.track_loaded {
+            named.push(syn::Field {
+                attrs: vec![],
+                vis: syn::Visibility::Inherited,
+                mutability: syn::FieldMutability::None,
+                ident: Some(syn::Ident::new(
+                    "_perstruct_loaded",
+                    proc_macro2::Span::mixed_site(),
+                )),
+                colon_token: None,
+                ty: syn::Type::Verbatim(
+                    quote! { ::std::collections::HashMap<&'static str, ::std::string::String> },
+                ),
+            });
+        }
+        if options.preserve_unknown {
+            named.push(syn::Field {
+                attrs: vec![],
+                vis: syn::Visibility::Inherited,
+                mutability: syn::FieldMutability::None,
+                ident: Some(syn::Ident::new(
+                    "_perstruct_unknown",
+                    proc_macro2::Span::mixed_site(),
+                )),
+                colon_token: None,
+                ty: syn::Type::Verbatim(quote! { ::std::collections::HashMap<::std::string::String, ::std::string::String> }),
+            });
+        }
+    } else if !has_dirty_fields {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "Perstruct: deriving requires a `_perstruct_dirty_fields: perstruct::DirtyFields` field",
+        ));
+    } else if options.dirty != DirtyTracking::HashSet
+        || options.observe
+        || options.callbacks
+        || options.track_loaded
+        || options.preserve_unknown
+        || !options.dynamic_fields.is_empty()
+    {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "Perstruct: `dirty`, `observe`, `track_loaded`, `preserve_unknown` and `dynamic_key` \
+            are only supported by the attribute macro",
+        ));
+    }
+    if options.migrate_fn.is_some() && options.version.is_none() {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "Perstruct: `migrate_fn` requires a `version`",
+        ));
+    }
+    if let (Some(codec), Some(_)) = (&options.codec, &options.single_document) {
+        return Err(syn::Error::new_spanned(
+            codec,
+            "Perstruct: `codec` can't be combined with `single_document`, which stores json",
+        ));
+    }
+    if let (Some(_), Some(field)) = (
+        &options.codec,
+        fields.iter().find(|field| field.value_default_missing),
+    ) {
+        return Err(syn::Error::new_spanned(
+            &field.ident,
+            "Perstruct: `value_default_missing` can't be combined with `codec`, it completes \
+            json objects",
+        ));
+    }
+    if options.preserve_unknown && options.single_document.is_some() {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "Perstruct: `preserve_unknown` can't be combined with `single_document`",
+        ));
+    }
+    if let (Some(on_load_raw), Some(_)) = (&options.on_load_raw, &options.single_document) {
+        return Err(syn::Error::new_spanned(
+            on_load_raw,
+            "Perstruct: `on_load_raw` can't be combined with `single_document`",
+        ));
+    }
+    if options.observe && !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "Perstruct: `observe` is not supported on generic structs",
+        ));
+    }
+    if options.view && !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "Perstruct: `view` is not supported on generic structs",
+        ));
+    }
+    if options.dirty == DirtyTracking::Bitset && fields.len() > 128 {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "Perstruct: `dirty = \"bitset\"` supports at most 128 fields",
+        ));
+    }
+
+    let mapped_keys_impl = options
+        .maps_keys()
+        .then(|| generate_mapped_keys_impl(&options, &struct_ty, &mut fields));
+
+    check_method_names(&options, &fields)?;
+    let has_no_default = fields.iter().any(|field| field.no_default);
+    if has_no_default {
+        for attr in input
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("derive"))
+        {
+            let derives =
+                attr.parse_args_with(Punctuated::<syn::Path, syn::Token![,]>::parse_terminated)?;
+            if let Some(derive) = derives.iter().find(|path| {
+                path.segments
+                    .last()
+                    .is_some_and(|segment| segment.ident == "Default")
+            }) {
+                return Err(syn::Error::new_spanned(
+                    derive,
+                    "Perstruct: structs with `no_default` fields can't derive `Default`, \
+                    load them with `try_from_map` or implement `Default` yourself",
+                ));
+            }
+        }
+    }
 
     let ident = input.ident.clone();
-    let default_impl = generate_default_impl(&ident, &fields, &skipped_fields);
-    let methods_impl = generate_methods_impl(&ident, &fields);
-    let keys = fields.iter().map(|field| {
-        let key = field.key.clone().unwrap_or(field.ident.to_string());
-        syn::LitStr::new(&key, proc_macro2::Span::mixed_site())
+    let default_impl = (!has_no_default)
+        .then(|| generate_default_impl(&options, &struct_ty, &fields, &skipped_fields));
+    let dirty_fields_impl = generate_dirty_fields_impl(&options);
+    let methods_impl = generate_methods_impl(&options, &struct_ty, &fields);
+    let keys = cfg_keys(&fields);
+    let redacted_keys = cfg_keys(fields.iter().filter(|field| field.secret));
+    let on_change = options.callbacks.then(|| {
+        let callback_ty = options.callback_ty();
+        quote! {
+            /// Registers `f` to be called with the persisted key of every change through the
+            /// setters, `update_<field>`, `reset_<field>` and `perstruct_set_by_key`, after the
+            /// field is marked dirty. Loading doesn't call it.
+            pub fn perstruct_on_change(
+                &mut self,
+                f: #callback_ty,
+            ) {
+                self._perstruct_callbacks.push(f);
+            }
+        }
+    });
+    let crate_path = options.crate_path();
+    let validate = match &options.validate {
+        Some(validate) => quote! { #validate(self) },
+        None => quote! { ::std::vec![] },
+    };
+    let dynamic_impl = generate_dynamic_impl(&options);
+    let forget_dynamic_rows = (!options.dynamic_fields.is_empty())
+        .then(|| quote! { self._perstruct_dynamic_saved.clear(); });
+    let mark_all_dirty = fields.iter().map(|field| {
+        let cfg = field.cfg();
+        let key = &field.persisted_key;
+        quote! { #cfg { self._perstruct_dirty_fields.insert(#key); } }
     });
 
-    let from_map_impl = generate_from_map_impl(&fields);
-    let get_changes_impl = generate_get_changes_impl(&fields);
+    let mark_dirty = generate_key_dispatch(
+        quote! { key },
+        fields
+            .iter()
+            .map(|field| {
+                let key = &field.persisted_key;
+                let body = quote! {
+                    self._perstruct_dirty_fields.insert(#key);
+                    true
+                };
+                (key.clone(), field.cfg(), body)
+            })
+            .collect(),
+        quote! { false },
+    );
 
+    let from_map_impl = generate_from_map_impl(&options, &fields, &skipped_fields);
+    let get_changes_impl = generate_get_changes_impl(&options, &fields);
+    let (loader_definition, loader_impl) =
+        generate_loader_impl(&options, &input.vis, &struct_ty, &fields);
+    let (updater_definition, updater_impl) =
+        generate_updater_impl(&options, &input.vis, &struct_ty, &fields);
+    let stores_impl = generate_stores_impl(&options, &fields);
+    let diff_map_impl = generate_diff_map_impl(&options, &fields);
+    let diff_impl = generate_diff_impl(&options, &fields);
+    let effective_values_impl = generate_effective_values_impl(&options, &fields);
+    let field_handles_impl = generate_field_handles_impl(&options, &fields);
+    let key_slice_impl = generate_key_slice_impl(&options, &fields);
+    let trait_impl = generate_trait_impl(&options, &struct_ty, &fields);
+    let try_default_impl = generate_try_default_impl(&options, &fields, &skipped_fields);
+    let (change_definition, observer_impl) = options
+        .observe
+        .then(|| generate_observer_impl(&input.vis, &ident, &fields))
+        .unzip();
+    let (view_definition, view_impl) = options
+        .view
+        .then(|| generate_view_impl(&input.vis, &ident, &fields))
+        .unzip();
+    let apply_impl = generate_apply_impl(&options, &fields);
+    // Json documents are only available for structs storing json
+    let document_impl = options
+        .codec
+        .is_none()
+        .then(|| generate_document_impl(&options, &fields));
+    let export_impl = options
+        .codec
+        .is_none()
+        .then(|| generate_export_impl(&options, &struct_ty, &fields));
+    let serde_impl = options
+        .serde
+        .then(|| generate_serde_impl(&options, &struct_ty, &fields, &skipped_fields));
+    let by_key_impl = generate_by_key_impl(&options, &struct_ty, &fields);
+
+    let main_impl_header = struct_ty.impl_header(None, None);
+    let input = (!derive).then_some(&input);
+    // The impls live in an anonymous const block that imports the traits whose methods they call,
+    // so they don't depend on the prelude of the module the struct is declared in. The imports are
+    // unnamed so they can't clash with the user's items.
     let tokens = quote::quote! {
         #input
 
-        #default_impl
+        #loader_definition
 
-        #methods_impl
+        #updater_definition
 
-        impl #ident {
-            pub fn perstruct_dirty_fields(&self) -> &std::collections::HashSet<&str> {
-                &self._perstruct_dirty_fields
-            }
-            pub fn perstruct_keys() -> std::vec::Vec<&'static str> {
-                vec![#( #keys ),*]
+        #change_definition
+
+        #view_definition
+
+        const _: () = {
+            use ::std::borrow::ToOwned as _;
+            use ::std::clone::Clone as _;
+            use ::std::convert::AsRef as _;
+            use ::std::convert::From as _;
+            use ::std::convert::Into as _;
+            use ::std::default::Default as _;
+            use ::std::iter::Extend as _;
+            use ::std::iter::IntoIterator as _;
+            use ::std::iter::Iterator as _;
+            use ::std::string::ToString as _;
+
+            #default_impl
+
+            #methods_impl
+
+            #loader_impl
+
+            #updater_impl
+
+            #by_key_impl
+
+            #trait_impl
+
+            #observer_impl
+
+            #view_impl
+
+            #serde_impl
+
+            #debug_impl
+
+            #main_impl_header {
+                #field_handles_impl
+                #dirty_fields_impl
+                /// Keys of all persisted fields, in declaration order or as changed by `order`.
+                pub fn perstruct_keys() -> ::std::vec::Vec<&'static str> {
+                    ::std::vec![#( #keys ),*]
+                }
+                /// Checks the struct as a whole with the struct's `validate` function, e.g. that a
+                /// minimum is not above a maximum. Nothing is corrected. `from_map` and the other
+                /// loads of the whole struct report these issues in `validation_issues`.
+                pub fn perstruct_validate(&self) -> ::std::vec::Vec<#crate_path::ValidationIssue> {
+                    #validate
+                }
+                #on_change
+                #dynamic_impl
+                /// Keys of the fields marked `secret`, whose values should be masked when the
+                /// struct or its changes are logged.
+                pub fn perstruct_redacted_keys() -> ::std::vec::Vec<&'static str> {
+                    ::std::vec![#( #redacted_keys ),*]
+                }
+                /// Marks all fields dirty, so `perstruct_get_changes` returns every field, e.g.
+                /// to re-persist everything after the store was wiped or the stored format
+                /// changed. The bulk counterpart of `perstruct_mark_dirty`.
+                pub fn perstruct_mark_all_dirty(&mut self) {
+                    #( #mark_all_dirty )*
+                    #forget_dynamic_rows
+                }
+                /// Same as `perstruct_mark_all_dirty`, for forcing a one-time full rewrite during
+                /// a migration.
+                pub fn perstruct_touch_all(&mut self) {
+                    self.perstruct_mark_all_dirty();
+                }
+                /// Marks the field with the persisted key `key` dirty, e.g. after writing the field
+                /// directly. Returns `false` for unknown keys.
+                pub fn perstruct_mark_dirty(&mut self, key: &str) -> bool {
+                    #mark_dirty
+                }
+                #from_map_impl
+                #get_changes_impl
+                #stores_impl
+                #diff_map_impl
+                #diff_impl
+                #effective_values_impl
+                #apply_impl
+                #document_impl
+                #export_impl
+                #mapped_keys_impl
+                #key_slice_impl
+                #try_default_impl
             }
-            #from_map_impl
-            #get_changes_impl
-        }
+        };
     };
     Ok(tokens)
 }
 
-fn generate_get_changes_impl(fields: &[PerstructField]) -> proc_macro2::TokenStream {
-    let match_arms = fields
-        .iter()
-        .map(|field| {
-            let ident = &field.ident;
-            let key = field.key.clone().unwrap_or(field.ident.to_string());
-            let key_lit = syn::LitStr::new(&key, proc_macro2::Span::mixed_site());
-            quote! {
-                #key_lit => {
-                    let value = serde_json::to_string(&self.#ident).map_err(|e| e.to_string())?;
-                    changes.push((#key_lit, value));
-                }
-            }
-        })
-        .collect::<Vec<_>>();
-    quote! {
-        pub fn perstruct_get_changes(&self) -> Result<std::vec::Vec<(&'static str, String)>, String> {
-            let mut changes = vec![];
-            for key in self._perstruct_dirty_fields.iter() {
-                match *key {
-                    #(#match_arms)*,
-                    _ => {}
+/// Field attributes that contradict each other. `alias` and `aliases` can be combined and repeated,
+/// their keys are used in the order they are declared.
+const CONFLICTING_ATTRIBUTES: &[(&str, &str)] = &[
+    ("key", "key_const"),
+    ("default", "default_fn"),
+    ("default", "try_default_fn"),
+    ("default_fn", "try_default_fn"),
+    ("no_default", "default"),
+    ("no_default", "default_fn"),
+    ("no_default", "try_default_fn"),
+    ("no_default", "value_default_missing"),
+    ("deserialize_with", "value_default_missing"),
+    ("with", "serialize_with"),
+    ("with", "deserialize_with"),
+    ("with", "serialize_as"),
+    ("with", "value_default_missing"),
+    ("display_fromstr", "with"),
+    ("display_fromstr", "serialize_with"),
+    ("display_fromstr", "deserialize_with"),
+    ("display_fromstr", "serialize_as"),
+    ("display_fromstr", "value_default_missing"),
+    ("serialize_as", "serialize_with"),
+    ("serialize_as", "deserialize_with"),
+    ("serialize_as", "value_default_missing"),
+    ("one_of", "value_default_missing"),
+    ("validate", "value_default_missing"),
+    ("env", "no_default"),
+    ("readonly", "setter"),
+    ("readonly", "into_setter"),
+    ("skip_default", "no_default"),
+    ("precise_updates", "readonly"),
+    ("lazy", "no_default"),
+    ("lazy", "value_default_missing"),
+    ("lazy", "dedup"),
+    ("lazy", "max_len"),
+];
+
+/// Rejects repeated attributes, `skip` combined with any other attribute and the pairs in
+/// `CONFLICTING_ATTRIBUTES`, pointing at the attribute that was used last.
+fn check_attribute_conflicts(used: &[syn::Path]) -> syn::Result<()> {
+    let name = |path: &syn::Path| path.to_token_stream().to_string();
+    for (index, path) in used.iter().enumerate() {
+        let current = name(path);
+        for earlier in used[..index].iter().map(name) {
+            let message = if earlier == current {
+                if current == "alias" || current == "aliases" {
+                    continue;
                 }
-            }
-            Ok(changes)
-        }
-        pub fn perstruct_saved(&mut self) {
-            self._perstruct_dirty_fields.clear();
+                format!("`{current}` can't be used more than once on the same field")
+            } else if earlier == "skip" || current == "skip" {
+                format!("`{earlier}` and `{current}` can't both be used on the same field, skipped fields are not persisted")
+            } else if (earlier == "dynamic_key" && current != "key")
+                || (current == "dynamic_key" && earlier != "key")
+            {
+                format!("`{earlier}` and `{current}` can't both be used on the same field, `dynamic_key` fields only take a `key`")
+            } else if CONFLICTING_ATTRIBUTES
+                .iter()
+                .any(|&(a, b)| (a, b) == (&earlier, &current) || (b, a) == (&earlier, &current))
+            {
+                format!("`{earlier}` and `{current}` can't both be used on the same field")
+            } else {
+                continue;
+            };
+            return Err(syn::Error::new_spanned(path, message));
         }
     }
+    Ok(())
 }
 
-fn generate_from_map_impl(fields: &[PerstructField]) -> proc_macro2::TokenStream {
-    let field_match_arms = fields
-        .iter()
-        .map(|field| {
-            let key = field.key.clone().unwrap_or(field.ident.to_string());
-            let key_lit = syn::LitStr::new(&key, proc_macro2::Span::mixed_site());
-            let ty = &field.ty;
-            let ident = &field.ident;
-            quote! {
-                #key_lit => {
-                    match serde_json::from_str::<#ty>(value.as_ref()) {
-                        Ok(json_value) => {
-                            struct_value.#ident = json_value;
-                            dirty_fields.remove(#key_lit);
-                        }
-                        Err(e) => {
-                            deserialization_errors.push((#key_lit, e.to_string()));
-                        }
-                    }
-                }
-            }
-        })
-        .collect::<Vec<_>>();
-    let keys = fields.iter().map(|field| {
-        let key = field.key.clone().unwrap_or(field.ident.to_string());
-        syn::LitStr::new(&key, proc_macro2::Span::mixed_site())
-    });
-    quote! {
-        pub fn from_map<TKey, TValue>(
-            map: &std::collections::HashMap<TKey, TValue>
-        ) -> ::perstruct::PerstructLoadResult<Self>
-            where TKey: std::convert::AsRef<str>,
-                  TValue: std::convert::AsRef<str>
-        {
-            let mut dirty_fields = vec![
-                #( #keys ),*
-            ].into_iter().collect::<std::collections::HashSet<&'static str>>();
-            let mut unknown_fields = vec![];
-
-            let mut struct_value = Self::default();
-            let mut deserialization_errors = vec![];
-            for (key, value) in map.iter() {
-                let key_ref: &str = key.as_ref();
-                match key_ref {
-                    #(#field_match_arms)*,
-                    unknown_key => {
-                        unknown_fields.push(unknown_key.to_string());
-                    }
-                }
+/// Checks the `one_of`, `validate` and `on_invalid` attributes of a field, returning whether
+/// setters reject values that are not allowed instead of storing the default. The setters of
+/// `validate` fields always reject them, returning the error. The default has to be one of the
+/// allowed values, which can only be checked for `default` string literals and
+/// `Default::default()`.
+fn check_one_of(
+    one_of: Option<&(syn::Path, Vec<syn::LitStr>)>,
+    validate: Option<&syn::Path>,
+    on_invalid: Option<&syn::LitStr>,
+    default: Option<&syn::Expr>,
+    has_default_fn: bool,
+    no_default: bool,
+) -> syn::Result<bool> {
+    if one_of.is_none() && validate.is_none() {
+        return match on_invalid {
+            Some(on_invalid) => Err(syn::Error::new_spanned(
+                on_invalid,
+                "Perstruct: `on_invalid` can only be used with `one_of`",
+            )),
+            None => Ok(false),
+        };
+    }
+    if let (Some(on_invalid), Some(_)) = (on_invalid, validate) {
+        return Err(syn::Error::new_spanned(
+            on_invalid,
+            "Perstruct: `on_invalid` can't be used with `validate`, the setters of `validate` \
+            fields return the error and leave the field unchanged",
+        ));
+    }
+    let reject_invalid = match on_invalid {
+        Some(on_invalid) => match on_invalid.value().as_str() {
+            "default" => false,
+            "reject" => true,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    on_invalid,
+                    "Expected \"default\" or \"reject\"",
+                ))
             }
-            struct_value._perstruct_dirty_fields = dirty_fields;
-            ::perstruct::PerstructLoadResult {
-                value: struct_value,
-                deserialization_errors,
-                unknown_fields,
+        },
+        None => validate.is_some(),
+    };
+    let Some((path, values)) = one_of else {
+        return Ok(reject_invalid);
+    };
+    let is_allowed = |value: &str| values.iter().any(|allowed| allowed.value() == value);
+    let default_str = |default: &syn::Expr| match default {
+        syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Str(default),
+            ..
+        }) => Some(default.value()),
+        _ => None,
+    };
+    match default {
+        Some(default) if default_str(default).is_some_and(|value| !is_allowed(&value)) => {
+            return Err(syn::Error::new_spanned(
+                default,
+                "Perstruct: the default is not one of the `one_of` values",
+            ));
+        }
+        Some(default) if default_str(default).is_some() => {}
+        Some(default) => {
+            return Err(syn::Error::new_spanned(
+                default,
+                "Perstruct: the default of a `one_of` field must be a string literal",
+            ));
+        }
+        None if no_default && !reject_invalid => {
+            return Err(syn::Error::new_spanned(
+                path,
+                "Perstruct: `one_of` fields with `no_default` need `on_invalid = \"reject\"`",
+            ));
+        }
+        None if !no_default && !has_default_fn && !is_allowed("") => {
+            return Err(syn::Error::new_spanned(
+                path,
+                "Perstruct: the default `\"\"` is not one of the `one_of` values, \
+                add a `default` among them",
+            ));
+        }
+        None => {}
+    }
+    Ok(reject_invalid)
+}
 
+/// Rejects fields whose literal persisted key or alias is also a key or alias of an earlier field,
+/// or the version key, which would make `from_map` load only one of them. Fields with different
+/// `cfg` attributes may never be compiled together, so they can share keys.
+fn check_duplicate_keys(options: &PerstructOptions, fields: &[PerstructField]) -> syn::Result<()> {
+    let mut keys: Vec<(String, Option<&PerstructField>)> = vec![];
+    if let Some(PersistedKey::Literal(version_key)) = options.version_key() {
+        keys.push((version_key.value(), None));
+    }
+    for field in fields {
+        let field_keys = match &field.persisted_key {
+            PersistedKey::Literal(key) => vec![key.value()],
+            PersistedKey::Runtime(_) => vec![],
+        };
+        let field_keys = field_keys
+            .into_iter()
+            .chain(field.aliases.iter().map(syn::LitStr::value));
+        for key in field_keys {
+            let duplicate = keys.iter().find(|(other_key, other)| {
+                *other_key == key
+                    && other.is_none_or(|other| other.cfg_predicate() == field.cfg_predicate())
+            });
+            if let Some((_, other)) = duplicate {
+                let message = match other {
+                    Some(other) if other.ident == field.ident => format!(
+                        "Perstruct: field `{}` uses the key `{key}` more than once",
+                        field.ident
+                    ),
+                    Some(other) => format!(
+                        "Perstruct: the key `{key}` of field `{}` is already used by field `{}`, keys \
+                        and aliases must be unique",
+                        field.ident, other.ident
+                    ),
+                    None => format!(
+                        "Perstruct: the key `{key}` of field `{}` is used for the version, \
+                        change it with `key`",
+                        field.ident
+                    ),
+                };
+                return Err(syn::Error::new_spanned(&field.ident, message));
             }
+            keys.push((key, Some(field)));
         }
     }
+    Ok(())
 }
 
-fn generate_methods_impl(
-    ident: &syn::Ident,
-    fields: &[PerstructField],
-) -> proc_macro2::TokenStream {
-    let methods = fields.iter().map(|field| {
+/// Rejects fields whose generated methods have the same name as another generated method, which
+/// would otherwise be reported as a confusing duplicate definition.
+fn check_method_names(options: &PerstructOptions, fields: &[PerstructField]) -> syn::Result<()> {
+    let mut names = [
+        "from_map",
+        "from_map_strict",
+        "from_map_bytes",
+        "try_from_map",
+        "from_json_str",
+        "from_document",
+        "loader",
+        "try_default",
+    ]
+    .into_iter()
+    .map(|name| (name.to_string(), None))
+    .collect::<std::collections::HashMap<String, Option<&syn::Ident>>>();
+    for field in fields {
         let ident = &field.ident;
-        let ty = &field.ty;
-        let (reference_return, reference_ty) = match ty {
-            // copy types should be returned by value - all integer, float, bool, char
-            syn::Type::Path(syn::TypePath { qself: None, path }) if path.segments.len() == 1 => {
-                let segment = &path.segments[0];
-                match segment.ident.to_string().as_str() {
-                    "i8" | "i16" | "i32" | "i64" | "i128" | "isize" | "u8" | "u16" | "u32"
-                    | "u64" | "u128" | "usize" | "f32" | "f64" | "bool" | "char" => {
-                        (quote! { self.#ident }, quote! { #ty })
-                    }
-                    _ => (quote! { &self.#ident }, quote! { &#ty }),
-                }
-            }
-            _ => (quote! { &self.#ident }, quote! { &#ty }),
-        };
-        let set_ident = syn::Ident::new(&format!("set_{}", ident), ident.span());
-        let key = field.key.clone().unwrap_or(field.ident.to_string());
-        let key_lit = syn::ExprLit {
-            attrs: vec![],
-            lit: syn::Lit::Str(syn::LitStr::new(&key.to_string(), ident.span())),
-        };
-        let update_ident = syn::Ident::new(&format!("update_{}", ident), ident.span());
-        quote! {
-            pub fn #ident(&self) -> #reference_ty {
-                #reference_return
+        let mut methods = vec![
+            field.getter_ident(options).to_string(),
+            format!("is_{ident}_dirty"),
+            format!("mark_{ident}_dirty"),
+        ];
+        if !field.readonly {
+            methods.push(field.setter_ident(options).to_string());
+            methods.push(format!("update_{ident}"));
+            if !field.no_default {
+                methods.push(format!("reset_{ident}"));
             }
-            pub fn #set_ident(&mut self, value: #ty) {
-                self.#ident = value;
-                self._perstruct_dirty_fields.insert(#key_lit);
+            if field.is_checked() {
+                methods.push(field.try_setter_ident(options).to_string());
+                methods.push(format!("try_update_{ident}"));
             }
-            pub fn #update_ident(&mut self, f: impl FnOnce(&mut #ty)) {
-                f(&mut self.#ident);
-                self._perstruct_dirty_fields.insert(#key_lit);
+        }
+        for method in methods {
+            if let Some(other) = names.insert(method.clone(), Some(ident)) {
+                let message = match other {
+                    Some(other) if other == ident => format!(
+                        "Perstruct: two methods of field `{ident}` are named `{method}`, rename \
+                        one with `getter` or `setter`"
+                    ),
+                    Some(other) => format!(
+                        "Perstruct: method `{method}` of field `{ident}` collides with a method of \
+                        field `{other}`, rename one with `getter` or `setter`"
+                    ),
+                    None => format!(
+                        "Perstruct: method `{method}` of field `{ident}` collides with a generated \
+                        method, rename it with `getter` or `setter`"
+                    ),
+                };
+                return Err(syn::Error::new_spanned(ident, message));
             }
         }
-    });
-    quote::quote! {
-        impl #ident {
-            #(#methods)*
+    }
+    // The updater has a method per field besides `commit`, a field named `commit` gets its setter
+    // name there
+    let mut updater_methods = std::collections::HashMap::new();
+    for field in fields.iter().filter(|field| !field.readonly) {
+        let name = field.updater_ident(options).to_string();
+        if let Some(other) = updater_methods.insert(name.clone(), &field.ident) {
+            return Err(syn::Error::new_spanned(
+                &field.ident,
+                format!(
+                    "Perstruct: the updater method `{name}` of field `{}` is also generated for \
+                    field `{other}`, rename one of them or change the setter name with `setter`",
+                    field.ident
+                ),
+            ));
         }
     }
+    Ok(())
 }
 
-fn generate_default_impl(
-    ident: &syn::Ident,
-    fields: &[PerstructField],
-    skipped_fields: &[syn::Ident],
+/// With `key_fn` or the `intern` feature, keys are only known at runtime: map them once and make
+/// every field refer to its slot in the mapped key array.
+fn generate_mapped_keys_impl(
+    options: &PerstructOptions,
+    struct_ty: &StructTy,
+    fields: &mut [PerstructField],
 ) -> proc_macro2::TokenStream {
-    let default_fields = fields.iter().map(|field| {
-        let ident = &field.ident;
-        if let Some(default_fn) = &field.default_fn {
-            let default_fn = syn::Ident::new(default_fn, ident.span());
-            quote::quote! { #ident: #default_fn() }
-        } else if let Some(default_lit) = &field.default_lit {
-            quote::quote! { #ident: #default_lit }
-        } else {
-            quote::quote! { #ident: Default::default() }
-        }
-    });
-    let default_skipped_fields = skipped_fields.iter().map(|ident| {
-        quote::quote! { #ident: Default::default() }
+    let crate_path = options.crate_path();
+    let count = fields.len();
+    let mapped_keys = fields
+        .iter()
+        .map(|field| {
+            let key = &field.persisted_key;
+            let key = match &options.key_fn {
+                Some(key_fn) => quote! { #key_fn(#key) },
+                None => quote! { #key },
+            };
+            if cfg!(feature = "intern") {
+                quote! { #crate_path::intern(#key) }
+            } else {
+                key
+            }
+        })
+        .collect::<Vec<_>>();
+    let path = struct_ty.path();
+    for (index, field) in fields.iter_mut().enumerate() {
+        field.persisted_key =
+            PersistedKey::Runtime(quote! { #path::__perstruct_mapped_keys()[#index] });
+    }
+    quote! {
+        #[doc(hidden)]
+        fn __perstruct_mapped_keys() -> &'static [&'static str; #count] {
+            static KEYS: ::std::sync::OnceLock<[&'static str; #count]> = ::std::sync::OnceLock::new();
+            KEYS.get_or_init(|| [#( #mapped_keys ),*])
+        }
+    }
+}
+
+/// Runs the arm of the field whose persisted key equals `key` (a `&str`), or `fallback`. Arms are
+/// `(key, cfg attributes of the field, body)`. Literal keys are patterns, runtime keys can't be
+/// patterns so they are compared in a guard.
+fn generate_key_dispatch(
+    key: proc_macro2::TokenStream,
+    arms: Vec<(
+        PersistedKey,
+        proc_macro2::TokenStream,
+        proc_macro2::TokenStream,
+    )>,
+    fallback: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let arms = arms.into_iter().map(|(persisted_key, cfg, body)| {
+        let pattern = match persisted_key {
+            PersistedKey::Literal(lit) => lit.into_token_stream(),
+            PersistedKey::Runtime(expr) => quote! { candidate if candidate == #expr },
+        };
+        quote! { #cfg #pattern => { #body } }
+    });
+    quote! {
+        match #key {
+            #( #arms )*
+            _ => { #fallback }
+        }
+    }
+}
+
+/// The persisted keys of `fields` with the `cfg` attributes of their field, for array literals.
+fn cfg_keys<'a>(
+    fields: impl IntoIterator<Item = &'a PerstructField>,
+) -> Vec<proc_macro2::TokenStream> {
+    fields
+        .into_iter()
+        .map(|field| {
+            let cfg = field.cfg();
+            let key = &field.persisted_key;
+            quote! { #cfg #key }
+        })
+        .collect()
+}
+
+/// Like `cfg_keys`, but followed by the aliases of each field.
+fn cfg_keys_with_aliases(fields: &[&PerstructField]) -> Vec<proc_macro2::TokenStream> {
+    fields
+        .iter()
+        .flat_map(|field| {
+            let cfg = field.cfg();
+            std::iter::once(field.persisted_key.clone())
+                .chain(field.aliases.iter().cloned().map(PersistedKey::Literal))
+                .map(move |key| quote! { #cfg #key })
+        })
+        .collect()
+}
+
+fn generate_get_changes_impl(
+    options: &PerstructOptions,
+    fields: &[PerstructField],
+) -> proc_macro2::TokenStream {
+    let write_changes = generate_write_changes_impl(options, fields);
+    let on_save_raw = options.on_save_raw.as_ref().map(|on_save_raw| {
+        quote! {
+            #on_save_raw(&changes
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.clone()))
+                .collect::<::std::vec::Vec<_>>());
+        }
+    });
+    let get_changes = if options.single_document.is_some() {
+        quote! {
+            /// The serialized document with all fields if any field is dirty, to be persisted.
+            pub fn perstruct_get_changes(&self) -> ::std::result::Result<::std::vec::Vec<(&'static str, ::std::string::String)>, ::std::string::String> {
+                let mut changes: ::std::vec::Vec<(&'static str, ::std::string::String)> = ::std::vec![];
+                self.perstruct_write_changes(&mut changes).map_err(|e| e.to_string())?;
+                #on_save_raw
+                ::std::result::Result::Ok(changes)
+            }
+        }
+    } else {
+        let mut doc = "Serialized values of the dirty fields, to be persisted.".to_string();
+        if options.version.is_some() {
+            doc.push_str(" When any field is dirty, the version is added under its own key.");
+        }
+        let unknown = options.preserve_unknown.then(|| {
+            doc.push_str(
+                " When any field is dirty, the loaded entries with unknown keys follow, sorted by \
+                key, so these are written back with every save.",
+            );
+            quote! {
+                let mut changes: ::std::vec::Vec<(&str, ::std::string::String)> = changes;
+                if !changes.is_empty() {
+                    let mut unknown = self
+                        ._perstruct_unknown
+                        .iter()
+                        .map(|(key, value)| (key.as_str(), value.clone()))
+                        .collect::<::std::vec::Vec<_>>();
+                    unknown.sort();
+                    changes.extend(unknown);
+                }
+            }
+        });
+        let key_ty = if options.preserve_unknown {
+            quote! { &str }
+        } else {
+            quote! { &'static str }
+        };
+        quote! {
+            #[doc = #doc]
+            pub fn perstruct_get_changes(&self) -> ::std::result::Result<::std::vec::Vec<(#key_ty, ::std::string::String)>, ::std::string::String> {
+                let mut changes: ::std::vec::Vec<(&'static str, ::std::string::String)> = ::std::vec![];
+                self.perstruct_write_changes(&mut changes).map_err(|e| e.to_string())?;
+                #unknown
+                #on_save_raw
+                ::std::result::Result::Ok(changes)
+            }
+        }
+    };
+    let changes_key_ty = if options.preserve_unknown && options.single_document.is_none() {
+        quote! { &str }
+    } else {
+        quote! { &'static str }
+    };
+    let get_changes_sorted = quote! {
+        /// The changes of `perstruct_get_changes` ordered by key, for output that doesn't depend on
+        /// the iteration order of the dirty fields, like snapshot tests and append-only logs.
+        pub fn perstruct_get_changes_sorted(
+            &self,
+        ) -> ::std::result::Result<::std::collections::BTreeMap<#changes_key_ty, ::std::string::String>, ::std::string::String> {
+            ::std::result::Result::Ok(self.perstruct_get_changes()?.into_iter().collect())
+        }
+    };
+    let (saved_doc, remember_saved, remember_cleared) = if options.track_loaded {
+        let arms = fields
+            .iter()
+            .map(|field| {
+                let key = &field.persisted_key;
+                let serialize = field.serialize_expr(options, field.reference(quote! { self }));
+                let body = quote! {
+                    if let ::std::result::Result::Ok(value) = #serialize {
+                        self._perstruct_loaded.insert(#key, value);
+                    }
+                };
+                (key.clone(), field.cfg(), body)
+            })
+            .collect();
+        let dispatch = generate_key_dispatch(quote! { key }, arms, quote! {});
+        (
+            "Marks all fields as saved, clearing the dirty fields and remembering their values for \
+            `perstruct_revert_field`.",
+            Some(quote! {
+                let dirty_fields = self._perstruct_dirty_fields.iter().copied().collect::<::std::vec::Vec<_>>();
+                for key in dirty_fields {
+                    #dispatch
+                }
+            }),
+            Some(dispatch),
+        )
+    } else {
+        (
+            "Marks all fields as saved, clearing the dirty fields.",
+            None,
+            None,
+        )
+    };
+    let revert = options
+        .track_loaded
+        .then(|| generate_revert_field_impl(options, fields));
+    let for_each_change = generate_for_each_change_impl(options, fields);
+    let remember_dynamic_rows = (!options.dynamic_fields.is_empty()).then(|| {
+        quote! {
+            if let ::std::result::Result::Ok(rows) = self.__perstruct_dynamic_rows() {
+                self._perstruct_dynamic_saved = rows;
+            }
+        }
+    });
+    let to_hashmap = generate_to_hashmap_impl(options, fields);
+    let get_changes_with_deletes = generate_get_changes_with_deletes_impl(options, fields);
+    let crate_path = options.crate_path();
+    // A single document holds all fields, so it's saved as a whole
+    let complete_change = if options.single_document.is_some() {
+        quote! { self.perstruct_saved(); }
+    } else {
+        quote! { self.perstruct_clear_dirty(&change.0); }
+    };
+    let on_save_raw_bytes = options.on_save_raw.as_ref().map(|on_save_raw| {
+        quote! {
+            #on_save_raw(&changes
+                .iter()
+                .map(|(key, value)| {
+                    (key.to_string(), ::std::string::String::from_utf8_lossy(value.as_slice()).into_owned())
+                })
+                .collect::<::std::vec::Vec<_>>());
+        }
+    });
+    // Like in `perstruct_get_changes`, the unknown entries follow when any field is dirty
+    let unknown_bytes = (options.preserve_unknown && options.single_document.is_none()).then(|| {
+        quote! {
+            let mut changes: ::std::vec::Vec<(&str, ::std::vec::Vec<u8>)> = changes;
+            if !changes.is_empty() {
+                let mut unknown = self
+                    ._perstruct_unknown
+                    .iter()
+                    .map(|(key, value)| (key.as_str(), ::std::clone::Clone::clone(value).into_bytes()))
+                    .collect::<::std::vec::Vec<_>>();
+                unknown.sort();
+                changes.extend(unknown);
+            }
+        }
+    });
+    let get_changes_bytes = quote! {
+        /// The changes of `perstruct_get_changes` with byte values, for binary stores. The values
+        /// are serialized straight into the bytes, without going through a `String`.
+        pub fn perstruct_get_changes_bytes(
+            &self,
+        ) -> ::std::result::Result<::std::vec::Vec<(#changes_key_ty, ::std::vec::Vec<u8>)>, ::std::string::String> {
+            let mut changes: ::std::vec::Vec<(&'static str, ::std::vec::Vec<u8>)> = ::std::vec![];
+            self.perstruct_write_changes(&mut changes).map_err(|e| e.to_string())?;
+            #unknown_bytes
+            #on_save_raw_bytes
+            ::std::result::Result::Ok(changes)
+        }
+    };
+    quote! {
+        #get_changes
+        #get_changes_sorted
+        #get_changes_bytes
+        #get_changes_with_deletes
+        #write_changes
+        #for_each_change
+        #to_hashmap
+        #[doc = #saved_doc]
+        pub fn perstruct_saved(&mut self) {
+            #remember_saved
+            #remember_dynamic_rows
+            self._perstruct_dirty_fields.clear();
+        }
+        /// Marks the field with the persisted key `key` as saved, for backends that persist the
+        /// changes one at a time. Returns whether the field was dirty.
+        pub fn perstruct_clear_dirty(&mut self, key: &str) -> bool {
+            if !self._perstruct_dirty_fields.contains(key) {
+                return false;
+            }
+            #remember_cleared
+            self._perstruct_dirty_fields.remove(key)
+        }
+        /// Starts a save that completes later, remembering the changes it persists.
+        pub fn perstruct_begin_save(&self) -> ::std::result::Result<#crate_path::SaveSession, ::std::string::String> {
+            ::std::result::Result::Ok(#crate_path::SaveSession::new(self.perstruct_get_changes()?))
+        }
+        /// Marks the changes persisted by `session` as saved, except those of fields that changed
+        /// to another value since the session was created, which stay dirty.
+        pub fn perstruct_complete_save(
+            &mut self,
+            session: #crate_path::SaveSession,
+        ) -> ::std::result::Result<(), ::std::string::String> {
+            // Not through `perstruct_get_changes`, this is no save for `on_save_raw`
+            let mut current: ::std::vec::Vec<(&'static str, ::std::string::String)> = ::std::vec![];
+            self.perstruct_write_changes(&mut current).map_err(|e| e.to_string())?;
+            let current = current
+                .into_iter()
+                .map(|(key, value)| (::std::string::ToString::to_string(key), value))
+                .collect::<::std::vec::Vec<_>>();
+            for change in session.changes() {
+                if current.contains(change) {
+                    #complete_change
+                }
+            }
+            ::std::result::Result::Ok(())
+        }
+        #revert
+    }
+}
+
+/// `perstruct_get_changes_with_deletes`, turning the changes of `Option` fields that are `None`
+/// into deletions.
+fn generate_get_changes_with_deletes_impl(
+    options: &PerstructOptions,
+    fields: &[PerstructField],
+) -> proc_macro2::TokenStream {
+    let arms = fields
+        .iter()
+        .filter(|field| field.skip_default || is_option_type(&field.ty))
+        .map(|field| {
+            let ty = &field.ty;
+            let place = field.place(quote! { self });
+            let is_default = field.skip_default.then(|| {
+                let default = field.default_expr();
+                quote! { <#ty as ::std::cmp::PartialEq>::eq(&#place, &#default) }
+            });
+            let is_deleted = match (is_option_type(ty), is_default) {
+                (true, Some(is_default)) => quote! { #place.is_none() || #is_default },
+                (true, None) => quote! { #place.is_none() },
+                (false, Some(is_default)) => is_default,
+                (false, None) => unreachable!(),
+            };
+            (field.persisted_key.clone(), field.cfg(), is_deleted)
+        })
+        .collect();
+    let is_deleted = generate_key_dispatch(quote! { key }, arms, quote! { false });
+    // Without the bounds, the method is unavailable instead of failing to compile
+    let bounds = fields
+        .iter()
+        .filter(|field| field.skip_default)
+        .map(|field| {
+            let ty = &field.ty;
+            quote! { for<'perstruct> #ty: ::std::cmp::PartialEq, }
+        })
+        .collect::<Vec<_>>();
+    let where_clause = (!bounds.is_empty()).then(|| quote! { where #( #bounds )* });
+    let key_ty = if options.preserve_unknown {
+        quote! { &str }
+    } else {
+        quote! { &'static str }
+    };
+    quote! {
+        /// Like `perstruct_get_changes`, but the value of an `Option` field that is `None`, or of a
+        /// `skip_default` field equal to its default, is `None` instead of the serialized value,
+        /// so its key can be deleted from the store.
+        pub fn perstruct_get_changes_with_deletes(
+            &self
+        ) -> ::std::result::Result<::std::vec::Vec<(#key_ty, ::std::option::Option<::std::string::String>)>, ::std::string::String>
+            #where_clause
+        {
+            let is_deleted = |key: &str| #is_deleted;
+            ::std::result::Result::Ok(self
+                .perstruct_get_changes()?
+                .into_iter()
+                .map(|(key, value)| (key, (!is_deleted(key)).then_some(value)))
+                .collect())
+        }
+    }
+}
+
+/// `perstruct_write_changes`, serializing the changes straight into the buffers of a
+/// `ChangeSink`. `perstruct_get_changes` collects them with the sink implemented by `Vec`.
+fn generate_write_changes_impl(
+    options: &PerstructOptions,
+    fields: &[PerstructField],
+) -> proc_macro2::TokenStream {
+    let crate_path = options.crate_path();
+    let serde_json = options.serde_json_path();
+    let body = if let Some(document_key) = &options.single_document {
+        quote! {
+            if self._perstruct_dirty_fields.is_empty() {
+                return ::std::result::Result::Ok(());
+            }
+            let document = self.__perstruct_to_json_object().map_err(::std::io::Error::other)?;
+            sink.entry(#document_key, &mut |writer| {
+                #serde_json::to_writer(writer, &document).map_err(::std::io::Error::from)
+            })
+        }
+    } else {
+        let arms = fields
+            .iter()
+            .map(|field| {
+                let key = &field.persisted_key;
+                let write = field.write_expr(options, field.reference(quote! { self }));
+                let body = quote! {
+                    sink.entry(#key, &mut |writer| { #write })?;
+                };
+                (key.clone(), field.cfg(), body)
+            })
+            .collect();
+        let dispatch = generate_key_dispatch(quote! { *key }, arms, quote! {});
+        let version = options.version.map(|version| {
+            let version_key = options.version_key();
+            quote! {
+                if !self._perstruct_dirty_fields.is_empty() {
+                    sink.entry(#version_key, &mut |writer| {
+                        writer.write_all(#version.to_string().as_bytes())
+                    })?;
+                }
+            }
+        });
+        quote! {
+            for key in self._perstruct_dirty_fields.iter() {
+                #dispatch
+            }
+            #version
+            ::std::result::Result::Ok(())
+        }
+    };
+    let doc = if options.preserve_unknown {
+        "Serializes the changes `perstruct_get_changes` would return, except the entries with \
+        unknown keys, directly into the buffers of `sink`, without allocating a `String` per value."
+    } else {
+        "Serializes the changes `perstruct_get_changes` would return directly into the buffers of \
+        `sink`, without allocating a `String` per value."
+    };
+    quote! {
+        #[doc = #doc]
+        pub fn perstruct_write_changes(
+            &self,
+            sink: &mut impl #crate_path::ChangeSink
+        ) -> ::std::io::Result<()> {
+            use ::std::io::Write as _;
+            #body
+        }
+    }
+}
+
+/// `perstruct_for_each_change`, streaming the entries of `perstruct_get_changes` without collecting
+/// them.
+fn generate_for_each_change_impl(
+    options: &PerstructOptions,
+    fields: &[PerstructField],
+) -> proc_macro2::TokenStream {
+    let (key_ty, body) = if let Some(document_key) = &options.single_document {
+        let body = quote! {
+            if self._perstruct_dirty_fields.is_empty() {
+                return ::std::result::Result::Ok(());
+            }
+            let document = self.__perstruct_to_json_object()?;
+            f(#document_key, document.to_string())
+        };
+        (quote! { &'static str }, body)
+    } else {
+        let arms = fields
+            .iter()
+            .map(|field| {
+                let key = &field.persisted_key;
+                let serialize = field.serialize_expr(options, field.reference(quote! { self }));
+                (key.clone(), field.cfg(), quote! { f(#key, #serialize?)?; })
+            })
+            .collect();
+        let dispatch = generate_key_dispatch(quote! { *key }, arms, quote! {});
+        let version = options.version.map(|version| {
+            let version_key = options.version_key();
+            quote! { f(#version_key, #version.to_string())?; }
+        });
+        let unknown = options.preserve_unknown.then(|| {
+            quote! {
+                let mut unknown = self._perstruct_unknown.iter().collect::<::std::vec::Vec<_>>();
+                unknown.sort();
+                for (key, value) in unknown {
+                    f(key, value.clone())?;
+                }
+            }
+        });
+        let extend = (version.is_some() || unknown.is_some()).then(|| {
+            quote! {
+                if !self._perstruct_dirty_fields.is_empty() {
+                    #version
+                    #unknown
+                }
+            }
+        });
+        let key_ty = if options.preserve_unknown {
+            quote! { &str }
+        } else {
+            quote! { &'static str }
+        };
+        let body = quote! {
+            for key in self._perstruct_dirty_fields.iter() {
+                #dispatch
+            }
+            #extend
+            ::std::result::Result::Ok(())
+        };
+        (key_ty, body)
+    };
+    quote! {
+        /// Calls `f` with every entry `perstruct_get_changes` would return, in the same order,
+        /// without collecting them. Stops at the first error.
+        pub fn perstruct_for_each_change<F>(&self, mut f: F) -> ::std::result::Result<(), ::std::string::String>
+            where F: ::std::ops::FnMut(#key_ty, ::std::string::String) -> ::std::result::Result<(), ::std::string::String>
+        {
+            #body
+        }
+    }
+}
+
+/// `perstruct_to_hashmap` and `perstruct_changes_to_hashmap`, for store clients taking owned maps.
+fn generate_to_hashmap_impl(
+    options: &PerstructOptions,
+    fields: &[PerstructField],
+) -> proc_macro2::TokenStream {
+    let insert_all = if let Some(document_key) = &options.single_document {
+        quote! {
+            let document = self.__perstruct_to_json_object()?;
+            map.insert(::std::string::ToString::to_string(#document_key), document.to_string());
+        }
+    } else {
+        let insert_fields = fields.iter().map(|field| {
+            let cfg = field.cfg();
+            let key = &field.persisted_key;
+            let serialize = field.serialize_expr(options, field.reference(quote! { self }));
+            quote! {
+                #cfg
+                map.insert(::std::string::ToString::to_string(#key), #serialize?);
+            }
+        });
+        let version = options.version.map(|version| {
+            let version_key = options.version_key();
+            quote! { map.insert(::std::string::ToString::to_string(#version_key), #version.to_string()); }
+        });
+        let unknown = options.preserve_unknown.then(|| {
+            quote! {
+                for (key, value) in self._perstruct_unknown.iter() {
+                    map.insert(::std::clone::Clone::clone(key), ::std::clone::Clone::clone(value));
+                }
+            }
+        });
+        quote! {
+            #unknown
+            #( #insert_fields )*
+            #version
+        }
+    };
+    let dynamic_rows = (!options.dynamic_fields.is_empty())
+        .then(|| quote! { map.extend(self.__perstruct_dynamic_rows()?); });
+    quote! {
+        /// The serialized values of all fields, dirty or not, keyed by owned persisted keys, like
+        /// `perstruct_get_changes` would return them after `perstruct_mark_all_dirty`. Includes
+        /// the rows of `dynamic_key` fields.
+        pub fn perstruct_to_hashmap(
+            &self,
+        ) -> ::std::result::Result<::std::collections::HashMap<::std::string::String, ::std::string::String>, ::std::string::String> {
+            let mut map = ::std::collections::HashMap::new();
+            #insert_all
+            #dynamic_rows
+            ::std::result::Result::Ok(map)
+        }
+        /// The entries of `perstruct_get_changes`, keyed by owned persisted keys.
+        pub fn perstruct_changes_to_hashmap(
+            &self,
+        ) -> ::std::result::Result<::std::collections::HashMap<::std::string::String, ::std::string::String>, ::std::string::String> {
+            ::std::result::Result::Ok(
+                self.perstruct_get_changes()?
+                    .into_iter()
+                    .map(|(key, value)| (::std::string::ToString::to_string(key), value))
+                    .collect(),
+            )
+        }
+    }
+}
+
+/// `perstruct_revert_field`, with `track_loaded`.
+fn generate_revert_field_impl(
+    options: &PerstructOptions,
+    fields: &[PerstructField],
+) -> proc_macro2::TokenStream {
+    let arms = fields
+        .iter()
+        .map(|field| {
+            let ident = &field.ident;
+            let key = &field.persisted_key;
+            let deserialize = field.deserialize_expr(options, quote! { value.as_str() });
+            // Fields without a default keep their value when nothing was loaded
+            let fallback = (!field.no_default).then(|| {
+                let default = field.wrap_expr(options, field.default_expr());
+                quote! { self.#ident = #default; }
+            });
+            let loaded = field.wrap_expr(options, quote! { value });
+            let body = quote! {
+                match self._perstruct_loaded.get(#key).map(|value| #deserialize) {
+                    ::std::option::Option::Some(::std::result::Result::Ok(value)) => self.#ident = #loaded,
+                    _ => { #fallback }
+                }
+                self._perstruct_dirty_fields.remove(#key);
+                true
+            };
+            (key.clone(), field.cfg(), body)
+        })
+        .collect();
+    let dispatch = generate_key_dispatch(quote! { key }, arms, quote! { false });
+    quote! {
+        /// Reverts the field with the persisted key `key` to its last loaded or saved value, or to
+        /// its default if there is none, and clears its dirty flag. Returns `false` for unknown
+        /// keys.
+        pub fn perstruct_revert_field(&mut self, key: &str) -> bool {
+            #dispatch
+        }
+    }
+}
+
+/// Statements serializing the dirty fields among `fields` into `changes`, returning early on
+/// serialization errors.
+fn generate_collect_changes(
+    options: &PerstructOptions,
+    fields: &[&PerstructField],
+) -> proc_macro2::TokenStream {
+    let arms = fields
+        .iter()
+        .map(|field| {
+            let key = &field.persisted_key;
+            let serialize = field.serialize_expr(options, field.reference(quote! { self }));
+            let body = quote! {
+                let value = #serialize?;
+                changes.push((#key, value));
+            };
+            (key.clone(), field.cfg(), body)
+        })
+        .collect();
+    let dispatch = generate_key_dispatch(quote! { *key }, arms, quote! {});
+    quote! {
+        let mut changes = ::std::vec![];
+        for key in self._perstruct_dirty_fields.iter() {
+            #dispatch
+        }
+    }
+}
+
+fn generate_from_map_impl(
+    options: &PerstructOptions,
+    fields: &[PerstructField],
+    skipped_fields: &[SkippedField],
+) -> proc_macro2::TokenStream {
+    let crate_path = options.crate_path();
+    let load_entries = generate_load_entries(
+        options,
+        &fields.iter().collect::<Vec<_>>(),
+        quote! { struct_value },
+        quote! { dirty_fields },
+    );
+    let all_dirty = options.dirty_all(fields);
+    let load_into_entries = generate_load_entries(
+        options,
+        &fields.iter().collect::<Vec<_>>(),
+        quote! { self },
+        quote! { self._perstruct_dirty_fields },
+    );
+    let load_version = options.load_version();
+    let (collect_raw, call_on_load_raw) = options.on_load_raw().unzip();
+    let mark_migrated_dirty = options.mark_migrated_dirty(fields, quote! { dirty_fields });
+    let mark_migrated_into_dirty =
+        options.mark_migrated_dirty(fields, quote! { self._perstruct_dirty_fields });
+    let stored_version = options.stored_version();
+    let default_bound = default_bound(options, fields, quote! { Self });
+    // Unknown profile entries don't belong in the store
+    let clear_unknown = options
+        .preserve_unknown
+        .then(|| quote! { result.value_mut()._perstruct_unknown.clear(); });
+    let matches = options.key_matches();
+    let present = fields
+        .iter()
+        .map(|field| {
+            let key = &field.persisted_key;
+            let aliases = &field.aliases;
+            let present = quote! {
+                [#key, #( #aliases ),*]
+                    .iter()
+                    .any(|field_key| map.keys().any(|key| #matches))
+            };
+            (field.cfg(), key, present)
+        })
+        .collect::<Vec<_>>();
+    // The field an entry of `from_map_bytes` belongs to, matched like in `from_map`
+    let field_key_checks = fields.iter().map(|field| {
+        let cfg = field.cfg();
+        let key = &field.persisted_key;
+        let aliases = &field.aliases;
+        quote! {
+            #cfg
+            if [#key, #( #aliases ),*].iter().any(|field_key| #matches) {
+                return ::std::option::Option::Some(#key);
+            }
+        }
+    });
+    let dynamic_key_checks = options.dynamic_fields.iter().map(|field| {
+        let cfg = field.cfg();
+        let key = &field.key;
+        let row_prefix = &field.row_prefix;
+        quote! {
+            #cfg
+            if key
+                .as_ref()
+                .strip_prefix(#row_prefix)
+                .and_then(#crate_path::__private::unescape_key_suffix)
+                .is_some()
+            {
+                return ::std::option::Option::Some(#key);
+            }
+        }
+    });
+    let resolve_field_key = quote! {
+        (|| {
+            #( #field_key_checks )*
+            #( #dynamic_key_checks )*
+            ::std::option::Option::None
+        })()
+    };
+    let collect_missing_required =
+        generate_collect_missing_required(options, &fields.iter().collect::<Vec<_>>());
+    let try_from_map = fields
+        .iter()
+        .any(|field| field.no_default || field.try_default_fn.is_some())
+        .then(|| {
+            generate_try_from_map_impl(
+                options,
+                fields,
+                skipped_fields,
+                &load_entries,
+                &collect_missing_required,
+            )
+        });
+    let find_missing = present.iter().map(|(cfg, key, present)| {
+        quote! {
+            #cfg
+            if !#present {
+                missing.push(#key);
+            }
+        }
+    });
+    let find_present = present.iter().map(|(cfg, key, present)| {
+        quote! {
+            #cfg
+            if #present {
+                present.push(#key);
+            }
+        }
+    });
+    let known_keys = cfg_keys_with_aliases(&fields.iter().collect::<Vec<_>>());
+    let stored_value_arms = fields
+        .iter()
+        .map(|field| {
+            let key = &field.persisted_key;
+            let aliases = &field.aliases;
+            (
+                key.clone(),
+                field.cfg(),
+                quote! { &[#key, #( #aliases ),*] },
+            )
+        })
+        .collect();
+    let field_keys = generate_key_dispatch(quote! { key }, stored_value_arms, quote! { &[] });
+    quote! {
+        /// Keys of the fields that are not in the map under their persisted key or an alias, so
+        /// `from_map` would give them their default value.
+        pub fn perstruct_keys_missing_from<TKey, TValue>(
+            map: &::std::collections::HashMap<TKey, TValue>
+        ) -> ::std::vec::Vec<&'static str>
+            where TKey: ::std::convert::AsRef<str>
+        {
+            let mut missing = ::std::vec![];
+            #( #find_missing )*
+            missing
+        }
+        /// Keys of the fields that are in the map under their persisted key or an alias, without
+        /// deserializing any value.
+        pub fn perstruct_present_keys<TKey, TValue>(
+            map: &::std::collections::HashMap<TKey, TValue>
+        ) -> ::std::vec::Vec<&'static str>
+            where TKey: ::std::convert::AsRef<str>
+        {
+            let mut present = ::std::vec![];
+            #( #find_present )*
+            present
+        }
+        /// Whether any of `keys` is the persisted key or an alias of a field, e.g. the keys of a
+        /// store, to check whether it has any data for the struct.
+        pub fn perstruct_any_key_present<K: ::std::convert::AsRef<str>>(
+            keys: impl ::std::iter::IntoIterator<Item = K>
+        ) -> bool {
+            let known_keys: &[&'static str] = &[#( #known_keys ),*];
+            keys.into_iter().any(|key| known_keys.iter().any(|field_key| #matches))
+        }
+        #try_from_map
+        /// Loads the struct from a map of keys to serialized values. Fields missing from the map
+        /// get their default value and are marked dirty.
+        pub fn from_map<TKey, TValue>(
+            map: &::std::collections::HashMap<TKey, TValue>
+        ) -> #crate_path::PerstructLoadResult<Self>
+            where TKey: ::std::convert::AsRef<str>,
+                  TValue: ::std::convert::AsRef<str>,
+                  #default_bound
+        {
+            #collect_raw
+            #call_on_load_raw
+            #load_version
+            let mut dirty_fields = #all_dirty;
+            let mut struct_value = Self::default();
+            #load_entries
+            #collect_missing_required
+            #mark_migrated_dirty
+            struct_value._perstruct_dirty_fields = dirty_fields;
+            let validation_issues = struct_value.perstruct_validate();
+            #crate_path::PerstructLoadResult::new(struct_value)
+                .with_deserialization_errors(deserialization_errors)
+                .with_unknown_fields(unknown_fields, unknown_overflow)
+                .with_shadowed_aliases(shadowed_aliases)
+                .with_used_aliases(used_aliases)
+                .with_env_overrides(env_overrides)
+                .with_stored_version(#stored_version)
+                .with_validation_issues(validation_issues)
+                .with_missing_required(missing_required)
+        }
+        /// Builds the struct with the values in `defaults`, for example from a runtime profile,
+        /// used as defaults: like `from_map`, but no field is dirty. Fields missing from
+        /// `defaults` get their compile-time default. Load the store on top with
+        /// `perstruct_load_into`.
+        pub fn with_profile_defaults<TKey, TValue>(
+            defaults: &::std::collections::HashMap<TKey, TValue>
+        ) -> #crate_path::PerstructLoadResult<Self>
+            where TKey: ::std::convert::AsRef<str>,
+                  TValue: ::std::convert::AsRef<str>,
+                  #default_bound
+        {
+            let mut result = Self::from_map(defaults);
+            result.value_mut()._perstruct_dirty_fields.clear();
+            #clear_unknown
+            result
+        }
+        /// Loads the entries of `map` into the existing struct like `from_map`, keeping the values
+        /// of fields missing from the map and of skipped fields. Loaded fields are no longer dirty.
+        pub fn perstruct_load_into<TKey, TValue>(
+            &mut self,
+            map: &::std::collections::HashMap<TKey, TValue>
+        ) -> #crate_path::PerstructLoadResult<()>
+            where TKey: ::std::convert::AsRef<str>,
+                  TValue: ::std::convert::AsRef<str>
+        {
+            #collect_raw
+            #call_on_load_raw
+            #load_version
+            #load_into_entries
+            #collect_missing_required
+            #mark_migrated_into_dirty
+            #crate_path::PerstructLoadResult::new(())
+                .with_deserialization_errors(deserialization_errors)
+                .with_unknown_fields(unknown_fields, unknown_overflow)
+                .with_shadowed_aliases(shadowed_aliases)
+                .with_used_aliases(used_aliases)
+                .with_env_overrides(env_overrides)
+                .with_stored_version(#stored_version)
+                .with_validation_issues(self.perstruct_validate())
+                .with_missing_required(missing_required)
+        }
+        /// Loads the struct like `from_map`, with a report of the loaded fields, the fields that
+        /// got their default because they were missing or their stored value is corrupt, and the
+        /// unknown keys, for telling the user what was recovered from a damaged store.
+        pub fn from_map_recovering<TKey, TValue>(
+            map: &::std::collections::HashMap<TKey, TValue>
+        ) -> (Self, #crate_path::RecoveryReport)
+            where TKey: ::std::convert::AsRef<str>,
+                  TValue: ::std::convert::AsRef<str>,
+                  #default_bound
+        {
+            let result = Self::from_map(map);
+            let report = #crate_path::__private::recovery_report(
+                &result,
+                Self::perstruct_keys(),
+                Self::perstruct_keys_missing_from(map),
+                |key| {
+                    let field_keys: &[&str] = #field_keys;
+                    field_keys.iter().find_map(|field_key| {
+                        map.iter()
+                            .find(|(key, _)| #matches)
+                            .map(|(_, value)| value.as_ref().to_string())
+                    })
+                },
+            );
+            (result.into_value(), report)
+        }
+        /// Like `from_map`, but fails if the map contains unknown keys or values that can't be
+        /// deserialized.
+        pub fn from_map_strict<TKey, TValue>(
+            map: &::std::collections::HashMap<TKey, TValue>
+        ) -> ::std::result::Result<Self, #crate_path::PerstructLoadError>
+            where TKey: ::std::convert::AsRef<str>,
+                  TValue: ::std::convert::AsRef<str>,
+                  #default_bound
+        {
+            let result = Self::from_map(map);
+            if result.unknown_fields().is_empty() && result.deserialization_errors().is_empty() {
+                ::std::result::Result::Ok(result.into_value())
+            } else {
+                ::std::result::Result::Err(#crate_path::PerstructLoadError {
+                    missing_fields: ::std::vec![],
+                    unknown_fields: result.unknown_fields,
+                    unknown_overflow: result.unknown_overflow,
+                    deserialization_errors: result.deserialization_errors,
+                })
+            }
+        }
+        /// Like `from_map`, for binary stores with byte values. The values are the same text as
+        /// for `from_map`, values that aren't UTF-8 are reported as deserialization errors of the
+        /// field their key resolves to, like in `from_map`, or as unknown fields otherwise.
+        pub fn from_map_bytes<TKey, TValue>(
+            map: &::std::collections::HashMap<TKey, TValue>
+        ) -> #crate_path::PerstructLoadResult<Self>
+            where TKey: ::std::convert::AsRef<str>,
+                  TValue: ::std::convert::AsRef<[u8]>,
+                  #default_bound
+        {
+            let mut text: ::std::collections::HashMap<&str, &str> = ::std::collections::HashMap::new();
+            let mut invalid = ::std::vec![];
+            for (key, value) in map.iter() {
+                match ::std::str::from_utf8(value.as_ref()) {
+                    ::std::result::Result::Ok(value) => {
+                        text.insert(key.as_ref(), value);
+                    }
+                    ::std::result::Result::Err(error) => invalid.push((key, error)),
+                }
+            }
+            let mut result = Self::from_map(&text);
+            for (key, error) in invalid {
+                let field_key: ::std::option::Option<&'static str> = #resolve_field_key;
+                match field_key {
+                    ::std::option::Option::Some(field_key) => {
+                        // The key is present, just not loadable
+                        result.missing_required.retain(|missing| *missing != field_key);
+                        result.deserialization_errors.push(#crate_path::PerstructFieldError {
+                            key: field_key,
+                            message: error.to_string(),
+                            line: 0,
+                            column: 0,
+                        });
+                    }
+                    ::std::option::Option::None => {
+                        if result.unknown_fields.len() < #crate_path::MAX_UNKNOWN_FIELDS {
+                            result.unknown_fields.push(key.as_ref().to_string());
+                        } else {
+                            result.unknown_overflow += 1;
+                        }
+                    }
+                }
+            }
+            result
+        }
+    }
+}
+
+/// Bound for methods building the struct through `Default`, which isn't generated for structs with
+/// `no_default` fields. Higher-ranked so it's not rejected as a trivially false bound, the methods
+/// are then only available when the user implements `Default`. Through `NoDefaultFields`, so the
+/// error explains why `Default` is missing instead of suggesting to derive it.
+fn default_bound(
+    options: &PerstructOptions,
+    fields: &[PerstructField],
+    ty: proc_macro2::TokenStream,
+) -> Option<proc_macro2::TokenStream> {
+    let crate_path = options.crate_path();
+    fields
+        .iter()
+        .any(|field| field.no_default)
+        .then(|| quote! { for<'perstruct> #ty: #crate_path::__private::NoDefaultFields })
+}
+
+/// Statements collecting the keys of the `required` fields missing from `map` into
+/// `missing_required`, counting aliases like `perstruct_keys_missing_from`.
+fn generate_collect_missing_required(
+    options: &PerstructOptions,
+    fields: &[&PerstructField],
+) -> proc_macro2::TokenStream {
+    let matches = options.key_matches();
+    let find_missing = fields.iter().filter(|field| field.required).map(|field| {
+        let cfg = field.cfg();
+        let key = &field.persisted_key;
+        let aliases = &field.aliases;
+        quote! {
+            #cfg
+            if ![#key, #( #aliases ),*]
+                .iter()
+                .any(|field_key| map.keys().any(|key| #matches))
+            {
+                missing_required.push(#key);
+            }
+        }
+    });
+    quote! {
+        #[allow(unused_mut)]
+        let mut missing_required: ::std::vec::Vec<&'static str> = ::std::vec![];
+        #( #find_missing )*
+    }
+}
+
+/// `try_from_map`, for structs with `no_default` fields: these are loaded first and fail loading
+/// when missing or invalid, then the struct is built and all entries are loaded like in `from_map`.
+fn generate_try_from_map_impl(
+    options: &PerstructOptions,
+    fields: &[PerstructField],
+    skipped_fields: &[SkippedField],
+    load_entries: &proc_macro2::TokenStream,
+    collect_missing_required: &proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let crate_path = options.crate_path();
+    let required = fields
+        .iter()
+        .filter(|field| field.no_default)
+        .collect::<Vec<_>>();
+    let required_idents = required
+        .iter()
+        .map(|field| &field.ident)
+        .collect::<Vec<_>>();
+    let required_cfgs = required.iter().map(|field| field.cfg()).collect::<Vec<_>>();
+    let matches = options.key_matches();
+    let load_required = required.iter().map(|field| {
+        let cfg = field.cfg();
+        let ident = &field.ident;
+        let key = &field.persisted_key;
+        let aliases = &field.aliases;
+        let deserialize = field.deserialize_expr(options, quote! { value.as_ref() });
+        quote! {
+            #cfg
+            let #ident = match [#key, #( #aliases ),*].iter().find_map(|field_key| {
+                map.iter().find(|(key, _)| #matches)
+            }) {
+                ::std::option::Option::Some((_, value)) => match #deserialize {
+                    ::std::result::Result::Ok(value) => ::std::option::Option::Some(value),
+                    ::std::result::Result::Err((message, line, column)) => {
+                        deserialization_errors.push(#crate_path::PerstructFieldError {
+                            key: #key,
+                            message,
+                            line,
+                            column,
+                        });
+                        ::std::option::Option::None
+                    }
+                },
+                ::std::option::Option::None => {
+                    missing_fields.push(#key);
+                    ::std::option::Option::None
+                }
+            };
+        }
+    });
+    let default_fields = fields
+        .iter()
+        .filter(|field| !field.no_default)
+        .map(|field| {
+            let cfg = field.cfg();
+            let ident = &field.ident;
+            let default =
+                field.wrap_expr(options, field.try_default_expr(quote! { default_errors }));
+            quote! { #cfg #ident: #default, }
+        });
+    let skipped_inits = skipped_fields.iter().map(SkippedField::default_init);
+    let no_dirty = options.dirty_none();
+    let injected_inits = options.injected_inits();
+    let all_dirty = options.dirty_all(fields);
+    let load_version = options.load_version();
+    let (collect_raw, call_on_load_raw) = options.on_load_raw().unzip();
+    let mark_migrated_dirty = options.mark_migrated_dirty(fields, quote! { dirty_fields });
+    let stored_version = options.stored_version();
+    let check_required = (!required.is_empty()).then(|| {
+        quote! {
+            let mut missing_fields = ::std::vec![];
+            let mut deserialization_errors = ::std::vec![];
+            #( #load_required )*
+            #(
+                #required_cfgs
+                let ::std::option::Option::Some(#required_idents) = #required_idents else {
+                    return ::std::result::Result::Err(#crate_path::PerstructLoadError {
+                        missing_fields,
+                        unknown_fields: ::std::vec![],
+                        unknown_overflow: 0,
+                        deserialization_errors,
+                    });
+                };
+            )*
+        }
+    });
+    quote! {
+        /// Loads the struct from a map of keys to serialized values like `from_map`, but fails when
+        /// a `no_default` field is missing from the map or can't be deserialized. Failures of
+        /// `try_default_fn` for fields missing from the map are reported as deserialization errors.
+        pub fn try_from_map<TKey, TValue>(
+            map: &::std::collections::HashMap<TKey, TValue>
+        ) -> ::std::result::Result<#crate_path::PerstructLoadResult<Self>, #crate_path::PerstructLoadError>
+            where TKey: ::std::convert::AsRef<str>,
+                  TValue: ::std::convert::AsRef<str>
+        {
+            #collect_raw
+            #load_version
+            #check_required
+            // After the checks, a failed load is no load for `on_load_raw`
+            #call_on_load_raw
+            #[allow(unused_mut)]
+            let mut default_errors: ::std::vec::Vec<(&'static str, ::std::string::String)> = ::std::vec![];
+            let mut struct_value = Self {
+                _perstruct_dirty_fields: #no_dirty,
+                #injected_inits
+                #( #required_cfgs #required_idents, )*
+                #( #default_fields )*
+                #( #skipped_inits, )*
+            };
+            let mut dirty_fields = #all_dirty;
+            #load_entries
+            #collect_missing_required
+            for (key, message) in default_errors {
+                if dirty_fields.contains(key) {
+                    deserialization_errors.push(#crate_path::PerstructFieldError {
+                        key,
+                        message,
+                        line: 0,
+                        column: 0,
+                    });
+                }
+            }
+            #mark_migrated_dirty
+            struct_value._perstruct_dirty_fields = dirty_fields;
+            let validation_issues = struct_value.perstruct_validate();
+            ::std::result::Result::Ok(
+                #crate_path::PerstructLoadResult::new(struct_value)
+                    .with_deserialization_errors(deserialization_errors)
+                    .with_unknown_fields(unknown_fields, unknown_overflow)
+                    .with_shadowed_aliases(shadowed_aliases)
+                    .with_used_aliases(used_aliases)
+                    .with_env_overrides(env_overrides)
+                    .with_stored_version(#stored_version)
+                    .with_validation_issues(validation_issues)
+                    .with_missing_required(missing_required),
+            )
+        }
+    }
+}
+
+/// Accessors of the `dynamic_key` fields, and the diff of their rows against the saved ones.
+fn generate_dynamic_impl(options: &PerstructOptions) -> Option<proc_macro2::TokenStream> {
+    if options.dynamic_fields.is_empty() {
+        return None;
+    }
+    let crate_path = options.crate_path();
+    let getter_prefix = options.getter_prefix.as_deref().unwrap_or("");
+    let setter_prefix = options.setter_prefix.as_deref().unwrap_or("set_");
+    let accessors = options.dynamic_fields.iter().map(|field| {
+        let cfg = field.cfg();
+        let ident = &field.ident;
+        let ty = &field.value_ty;
+        let getter = method_ident(getter_prefix, ident, "");
+        let getter_for = method_ident(getter_prefix, ident, "_for");
+        let setter_for = method_ident(setter_prefix, ident, "_for");
+        let remove_for = method_ident("remove_", ident, "_for");
+        quote! {
+            #cfg
+            pub fn #getter(&self) -> &::std::collections::HashMap<::std::string::String, #ty> {
+                &self.#ident
+            }
+            #cfg
+            pub fn #getter_for(&self, suffix: &str) -> ::std::option::Option<&#ty> {
+                self.#ident.get(suffix)
+            }
+            #cfg
+            pub fn #setter_for(&mut self, suffix: impl ::std::convert::Into<::std::string::String>, value: #ty) {
+                self.#ident.insert(suffix.into(), value);
+            }
+            #cfg
+            pub fn #remove_for(&mut self, suffix: &str) -> ::std::option::Option<#ty> {
+                self.#ident.remove(suffix)
+            }
+        }
+    });
+    let rows = options.dynamic_fields.iter().map(|field| {
+        let cfg = field.cfg();
+        let ident = &field.ident;
+        let row_prefix = &field.row_prefix;
+        let encode = field.encode_expr(options, quote! { value });
+        quote! {
+            #cfg
+            for (suffix, value) in self.#ident.iter() {
+                rows.insert(
+                    ::std::format!("{}{}", #row_prefix, #crate_path::__private::escape_key_suffix(suffix)),
+                    #encode?,
+                );
+            }
+        }
+    });
+    Some(quote! {
+        #( #accessors )*
+        #[doc(hidden)]
+        pub fn __perstruct_dynamic_rows(
+            &self,
+        ) -> ::std::result::Result<::std::collections::HashMap<::std::string::String, ::std::string::String>, ::std::string::String> {
+            let mut rows = ::std::collections::HashMap::new();
+            #( #rows )*
+            ::std::result::Result::Ok(rows)
+        }
+        /// The rows of the `dynamic_key` fields that changed since they were loaded or saved, sorted
+        /// by key: `Some` with the value for rows to write, `None` for rows of removed entries to
+        /// delete. `perstruct_saved` marks them as saved.
+        pub fn perstruct_get_dynamic_changes(
+            &self,
+        ) -> ::std::result::Result<::std::vec::Vec<(::std::string::String, ::std::option::Option<::std::string::String>)>, ::std::string::String> {
+            let rows = self.__perstruct_dynamic_rows()?;
+            let mut changes: ::std::vec::Vec<_> = rows
+                .iter()
+                .filter(|(key, value)| self._perstruct_dynamic_saved.get(*key) != ::std::option::Option::Some(*value))
+                .map(|(key, value)| (::std::clone::Clone::clone(key), ::std::option::Option::Some(::std::clone::Clone::clone(value))))
+                .chain(
+                    self._perstruct_dynamic_saved
+                        .keys()
+                        .filter(|key| !rows.contains_key(*key))
+                        .map(|key| (::std::clone::Clone::clone(key), ::std::option::Option::None)),
+                )
+                .collect();
+            changes.sort();
+            ::std::result::Result::Ok(changes)
+        }
+    })
+}
+
+/// Statements loading the entries of `map` into the matching `fields` of `target`, removing
+/// loaded keys from `dirty_fields`, then the values of the fields' environment variables. Declares
+/// `deserialization_errors`, `unknown_fields`, `unknown_overflow`, `shadowed_aliases`,
+/// `used_aliases` and `env_overrides` for building a `PerstructLoadResult`.
+fn generate_load_entries(
+    options: &PerstructOptions,
+    fields: &[&PerstructField],
+    target: proc_macro2::TokenStream,
+    dirty_fields: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let crate_path = options.crate_path();
+    // With `from_alias`, a bool expression telling whether `value` is the value of the alias
+    // `loaded_key`, which is reported and keeps the field dirty
+    let load = |field: &PerstructField,
+                value: proc_macro2::TokenStream,
+                from_alias: Option<proc_macro2::TokenStream>| {
+        let key = &field.persisted_key;
+        let record_alias = from_alias.as_ref().map(|from_alias| {
+            quote! {
+                if #from_alias {
+                    used_aliases.push((#key, loaded_key.to_string()));
+                }
+            }
+        });
+        let from_alias = from_alias.map(|from_alias| quote! { #from_alias || });
+        let ident = &field.ident;
+        let record_loaded = options.record_loaded(&target, key, &value);
+        if field.lazy {
+            // Only the string is kept, deserialization errors are reported by the getter
+            let ty = &field.ty;
+            let deserialize = field.deserialize_expr(options, quote! { value });
+            let default = field.default_expr();
+            return quote! {
+                #target.#ident = #crate_path::Lazy::raw(
+                    ::std::string::ToString::to_string(#value),
+                    |value: &str| -> ::std::result::Result<#ty, #crate_path::PerstructFieldError> {
+                        #deserialize.map_err(|(message, line, column)| #crate_path::PerstructFieldError {
+                            key: #key,
+                            message,
+                            line,
+                            column,
+                        })
+                    },
+                    || -> #ty { #default },
+                );
+                #record_loaded
+                #record_alias
+                if !(#from_alias false) {
+                    #dirty_fields.remove(#key);
+                }
+            };
+        }
+        let normalize = field
+            .normalize_expr(options, field.reference_mut(target.clone()))
+            .unwrap_or(quote! { false });
+        let (deserialize, loaded, completed) =
+            match field.deserialize_completed_expr(options, &value) {
+                Some(deserialize) => (
+                    deserialize,
+                    quote! { (json_value, completed) },
+                    quote! { completed || },
+                ),
+                None => (
+                    field.deserialize_expr(options, value),
+                    quote! { json_value },
+                    quote! {},
+                ),
+            };
+        quote! {
+            match #deserialize {
+                ::std::result::Result::Ok(#loaded) => {
+                    #target.#ident = json_value;
+                    #record_loaded
+                    #record_alias
+                    // Keep values loaded from aliases, normalized and completed values dirty, so
+                    // they get persisted
+                    if !(#from_alias #completed #normalize) {
+                        #dirty_fields.remove(#key);
+                    }
+                }
+                ::std::result::Result::Err((message, line, column)) => {
+                    deserialization_errors.push(#crate_path::PerstructFieldError {
+                        key: #key,
+                        message,
+                        line,
+                        column,
+                    });
+                }
+            }
+        }
+    };
+    let mut arms = vec![];
+    let mut candidates = vec![];
+    let mut resolve_candidates = vec![];
+    for field in fields {
+        let key = &field.persisted_key;
+        if field.aliases.is_empty() && !options.case_insensitive {
+            arms.push((
+                key.clone(),
+                field.cfg(),
+                load(field, quote! { value.as_ref() }, None),
+            ));
+            continue;
+        }
+        // Several of the field's keys may be present: remember the one with the highest
+        // precedence (persisted key, then aliases in declared order, exact matches before
+        // case-insensitive ones) and load it after the loop.
+        let candidate = format_ident!("{}_candidate", field.ident);
+        let field_keys = std::iter::once(key.clone())
+            .chain(field.aliases.iter().cloned().map(PersistedKey::Literal));
+        for (precedence, field_key) in field_keys.enumerate() {
+            let body = quote! {
+                match #candidate {
+                    ::std::option::Option::Some((best, _, _)) if best < (inexact, #precedence) => {
+                        shadowed_aliases.push((#key, original_key.to_string()));
+                    }
+                    previous => {
+                        if let ::std::option::Option::Some((_, shadowed_key, _)) = previous {
+                            shadowed_aliases.push((#key, shadowed_key.to_string()));
+                        }
+                        #candidate = ::std::option::Option::Some(((inexact, #precedence), original_key, value.as_ref()));
+                    }
+                }
+            };
+            arms.push((field_key, field.cfg(), body));
+        }
+        let cfg = field.cfg();
+        candidates.push(quote! {
+            #cfg
+            let mut #candidate: ::std::option::Option<((bool, usize), &str, &str)> = ::std::option::Option::None;
+        });
+        let load = load(field, quote! { value }, Some(quote! { precedence > 0 }));
+        resolve_candidates.push(quote! {
+            #cfg
+            if let ::std::option::Option::Some(((_, precedence), loaded_key, value)) = #candidate {
+                #load
+            }
+        });
+    }
+    let env_overrides = fields.iter().filter_map(|field| {
+        let (variable, error_key) = field.env.as_ref()?;
+        let cfg = field.cfg();
+        let ident = &field.ident;
+        let key = &field.persisted_key;
+        let deserialize = field.deserialize_expr(options, quote! { env_value.as_str() });
+        let value = field.wrap_expr(options, quote! { value });
+        let push_error = quote! {
+            deserialization_errors.push(#crate_path::PerstructFieldError {
+                key: #error_key,
+                message,
+                line,
+                column,
+            });
+        };
+        Some(quote! {
+            #cfg
+            match ::std::env::var(#variable) {
+                ::std::result::Result::Ok(env_value) => match #deserialize {
+                    ::std::result::Result::Ok(value) => {
+                        #target.#ident = #value;
+                        // The value comes from the environment, not from the user
+                        #dirty_fields.remove(#key);
+                        env_overrides.push((#key, #variable));
+                    }
+                    ::std::result::Result::Err((message, line, column)) => {
+                        #push_error
+                    }
+                },
+                ::std::result::Result::Err(::std::env::VarError::NotPresent) => {}
+                ::std::result::Result::Err(error) => {
+                    let (message, line, column) = (error.to_string(), 0, 0);
+                    #push_error
+                }
+            }
+        })
+    });
+    if let Some(version_key) = options.version_key() {
+        arms.push((version_key, quote! {}, quote! {}));
+    }
+    let preserve_unknown = options.preserve_unknown.then(|| {
+        quote! {
+            #target._perstruct_unknown.insert(original_key.to_string(), value.as_ref().to_string());
+        }
+    });
+    let dynamic_rows = options.dynamic_fields.iter().map(|field| {
+        let cfg = field.cfg();
+        let ident = &field.ident;
+        let key = &field.key;
+        let row_prefix = &field.row_prefix;
+        let decode = field.decode_expr(options, quote! { value.as_ref() });
+        quote! {
+            #cfg
+            if let ::std::option::Option::Some(suffix) = original_key
+                .strip_prefix(#row_prefix)
+                .and_then(#crate_path::__private::unescape_key_suffix)
+            {
+                match #decode {
+                    ::std::result::Result::Ok(row) => {
+                        #target.#ident.insert(suffix, row);
+                        #target._perstruct_dynamic_saved.insert(
+                            original_key.to_string(),
+                            value.as_ref().to_string(),
+                        );
+                    }
+                    ::std::result::Result::Err((message, line, column)) => {
+                        deserialization_errors.push(#crate_path::PerstructFieldError {
+                            key: #key,
+                            message,
+                            line,
+                            column,
+                        });
+                    }
+                }
+                continue;
+            }
+        }
+    });
+    let dispatch = generate_key_dispatch(
+        quote! { key_ref },
+        arms,
+        quote! {
+            #( #dynamic_rows )*
+            // Bounded, since the map may be full of unrelated entries
+            if unknown_fields.len() < #crate_path::MAX_UNKNOWN_FIELDS {
+                unknown_fields.push(original_key.to_string());
+            } else {
+                unknown_overflow += 1;
+            }
+            #preserve_unknown
+        },
+    );
+    let resolve_key = if options.case_insensitive {
+        let known_keys = cfg_keys_with_aliases(fields);
+        quote! {
+            let original_key: &str = key.as_ref();
+            let known_keys: &[&'static str] = &[#( #known_keys ),*];
+            let (key_ref, inexact) = if known_keys.contains(&original_key) {
+                (original_key, false)
+            } else {
+                match known_keys.iter().find(|known| known.eq_ignore_ascii_case(original_key)) {
+                    ::std::option::Option::Some(known) => (*known, true),
+                    ::std::option::Option::None => (original_key, false),
+                }
+            };
+        }
+    } else {
+        quote! {
+            let original_key: &str = key.as_ref();
+            #[allow(unused_variables)]
+            let (key_ref, inexact) = (original_key, false);
+        }
+    };
+    quote! {
+        let mut deserialization_errors = ::std::vec![];
+        let mut unknown_fields: ::std::vec::Vec<::std::string::String> = ::std::vec![];
+        let mut unknown_overflow: usize = 0;
+        let mut shadowed_aliases = ::std::vec![];
+        let mut used_aliases = ::std::vec![];
+        let mut env_overrides = ::std::vec![];
+        #( #candidates )*
+        for (key, value) in map.iter() {
+            #resolve_key
+            #dispatch
+        }
+        #( #resolve_candidates )*
+        #( #env_overrides )*
+    }
+}
+
+fn generate_document_impl(
+    options: &PerstructOptions,
+    fields: &[PerstructField],
+) -> proc_macro2::TokenStream {
+    let crate_path = options.crate_path();
+    let serde_json = options.serde_json_path();
+    let insert_fields = fields.iter().map(|field| {
+        let cfg = field.cfg();
+        let key = &field.persisted_key;
+        let value = if let Some(proxy) = field.proxy_expr(&field.reference(quote! { self })) {
+            quote! { #serde_json::to_value(&#proxy).map_err(|e| e.to_string())? }
+        } else if field.is_json_encoded(options) {
+            let value = field.reference(quote! { self });
+            quote! { #serde_json::to_value(#value).map_err(|e| e.to_string())? }
+        } else {
+            let serialize = field.serialize_expr(options, field.reference(quote! { self }));
+            quote! { #serde_json::Value::String(#serialize?) }
+        };
+        quote! {
+            #cfg
+            {
+                object.insert(#key.to_string(), #value);
+            }
+        }
+    });
+    let insert_version = options.version.map(|version| {
+        let version_key = options.version_key();
+        quote! { object.insert(#version_key.to_string(), #serde_json::Value::from(#version)); }
+    });
+    // Fields that are not stored as json are stored as json strings in the document
+    let raw_keys = cfg_keys(
+        fields
+            .iter()
+            .filter(|field| !field.is_json_encoded(options)),
+    );
+    let where_default =
+        default_bound(options, fields, quote! { Self }).map(|bound| quote! { where #bound });
+    let from_document = options.single_document.as_ref().map(|document_key| {
+        quote! {
+            /// Loads the struct from the document returned by `perstruct_get_changes`, like
+            /// `from_map` does for the entries of a map.
+            pub fn from_document(document: &str) -> #crate_path::PerstructLoadResult<Self>
+                #where_default
+            {
+                Self::__perstruct_from_json_object(document, #document_key)
+            }
+        }
+    });
+    quote! {
+        /// Serializes all fields into a json object keyed by their persisted keys.
+        pub fn perstruct_to_json_string(&self) -> ::std::result::Result<::std::string::String, ::std::string::String> {
+            ::std::result::Result::Ok(self.__perstruct_to_json_object()?.to_string())
+        }
+        /// Like `perstruct_to_json_string`, but pretty-printed.
+        pub fn perstruct_to_json_string_pretty(&self) -> ::std::result::Result<::std::string::String, ::std::string::String> {
+            #serde_json::to_string_pretty(&self.__perstruct_to_json_object()?)
+                .map_err(|e| e.to_string())
+        }
+        /// Loads the struct from a json object keyed by persisted keys, like `from_map` does for
+        /// the entries of a map. If `json` is not an object, a single error with an empty key is
+        /// reported.
+        pub fn from_json_str(json: &str) -> #crate_path::PerstructLoadResult<Self>
+            #where_default
+        {
+            Self::__perstruct_from_json_object(json, "")
+        }
+        #from_document
+        #[doc(hidden)]
+        fn __perstruct_from_json_object(
+            document: &str,
+            document_key: &'static str
+        ) -> #crate_path::PerstructLoadResult<Self>
+            #where_default
+        {
+            match #serde_json::from_str::<#serde_json::Map<::std::string::String, #serde_json::Value>>(document) {
+                ::std::result::Result::Ok(object) => {
+                    Self::from_map(&Self::__perstruct_json_object_to_map(object))
+                }
+                ::std::result::Result::Err(e) => {
+                    let mut result = Self::from_map(&::std::collections::HashMap::<::std::string::String, ::std::string::String>::new());
+                    result.deserialization_errors.push(#crate_path::PerstructFieldError {
+                        key: document_key,
+                        message: e.to_string(),
+                        line: e.line(),
+                        column: e.column(),
+                    });
+                    result
+                }
+            }
+        }
+        #[doc(hidden)]
+        fn __perstruct_json_object_to_map(
+            object: #serde_json::Map<::std::string::String, #serde_json::Value>
+        ) -> ::std::collections::HashMap<::std::string::String, ::std::string::String> {
+            let raw_keys: &[&str] = &[#( #raw_keys ),*];
+            object
+                .into_iter()
+                .map(|(key, value)| {
+                    let value = match value {
+                        #serde_json::Value::String(value) if raw_keys.contains(&key.as_str()) => value,
+                        value => value.to_string(),
+                    };
+                    (key, value)
+                })
+                .collect()
+        }
+        #[doc(hidden)]
+        fn __perstruct_to_json_object(&self) -> ::std::result::Result<#serde_json::Value, ::std::string::String> {
+            let mut object = #serde_json::Map::new();
+            #( #insert_fields )*
+            #insert_version
+            ::std::result::Result::Ok(#serde_json::Value::Object(object))
+        }
+    }
+}
+
+/// `perstruct_export` and `perstruct_import`, exchanging the json document of the fields in an
+/// envelope with the struct name, the version and the time of the export.
+fn generate_export_impl(
+    options: &PerstructOptions,
+    struct_ty: &StructTy,
+    fields: &[PerstructField],
+) -> proc_macro2::TokenStream {
+    let crate_path = options.crate_path();
+    let serde_json = options.serde_json_path();
+    let schema = struct_ty.ident.to_string();
+    let migrates = options.migrate_fn.is_some();
+    let (export_version, import_version) = match (options.version, options.version_key()) {
+        (Some(version), Some(version_key)) => (
+            quote! {
+                values.remove(#version_key);
+                envelope.insert("version".to_string(), #serde_json::Value::from(#version));
+            },
+            quote! {
+                let exported_version = match envelope.get("version") {
+                    ::std::option::Option::Some(version) => version
+                        .as_u64()
+                        .and_then(|version| <u32 as ::std::convert::TryFrom<u64>>::try_from(version).ok())
+                        .ok_or_else(|| #crate_path::ImportError::Malformed("invalid version".to_string()))?,
+                    ::std::option::Option::None => 0,
+                };
+                if exported_version > #version {
+                    return ::std::result::Result::Err(#crate_path::ImportError::UnsupportedVersion {
+                        supported: #version,
+                        found: exported_version,
+                    });
+                }
+                // Migrated by `perstruct_load_into` like a stored map, which marks all fields dirty
+                map.insert(#version_key.to_string(), exported_version.to_string());
+                let migrated = #migrates && exported_version < #version;
+                let exported_version = ::std::option::Option::Some(exported_version);
+            },
+        ),
+        _ => (
+            quote! {},
+            quote! {
+                let migrated = false;
+                let exported_version = ::std::option::Option::None;
+            },
+        ),
+    };
+    let all_keys = cfg_keys(fields);
+    quote! {
+        /// Exports all fields for importing them elsewhere with `perstruct_import`: a json object
+        /// with the struct name as `schema`, the `version` of structs with one, the time of the
+        /// export as `exported_at` in seconds since the Unix epoch, and the json document of
+        /// `perstruct_to_json_string` as `values`.
+        pub fn perstruct_export(&self) -> ::std::result::Result<::std::string::String, ::std::string::String> {
+            let #serde_json::Value::Object(mut values) = self.__perstruct_to_json_object()? else {
+                ::std::unreachable!("the document is an object");
+            };
+            let exported_at = ::std::time::SystemTime::now()
+                .duration_since(::std::time::UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs());
+            let mut envelope = #serde_json::Map::new();
+            envelope.insert("schema".to_string(), #serde_json::Value::from(#schema));
+            #export_version
+            envelope.insert("exported_at".to_string(), #serde_json::Value::from(exported_at));
+            envelope.insert("values".to_string(), #serde_json::Value::Object(values));
+            #serde_json::to_string_pretty(&envelope).map_err(|e| e.to_string())
+        }
+        /// Imports the fields of an export of `perstruct_export`, migrating it if it was made with
+        /// an older `version`. The imported fields are dirty, so they are persisted with the next
+        /// changes. Fails without changing anything when the input is not an export of this
+        /// struct or was made with a newer version.
+        pub fn perstruct_import(
+            &mut self,
+            export: &str
+        ) -> ::std::result::Result<#crate_path::ImportReport, #crate_path::ImportError> {
+            let malformed = |message: &str| #crate_path::ImportError::Malformed(message.to_string());
+            let mut envelope = #serde_json::from_str::<#serde_json::Map<::std::string::String, #serde_json::Value>>(export)
+                .map_err(|e| #crate_path::ImportError::Malformed(e.to_string()))?;
+            match envelope.get("schema").and_then(#serde_json::Value::as_str) {
+                ::std::option::Option::Some(#schema) => {}
+                ::std::option::Option::Some(found) => {
+                    return ::std::result::Result::Err(#crate_path::ImportError::SchemaMismatch {
+                        expected: #schema,
+                        found: found.to_string(),
+                    });
+                }
+                ::std::option::Option::None => return ::std::result::Result::Err(malformed("missing schema")),
+            }
+            let exported_at = envelope
+                .get("exported_at")
+                .and_then(#serde_json::Value::as_u64)
+                .ok_or_else(|| malformed("missing exported_at"))?;
+            let ::std::option::Option::Some(#serde_json::Value::Object(values)) = envelope.remove("values") else {
+                return ::std::result::Result::Err(malformed("missing values"));
+            };
+            let mut map = Self::__perstruct_json_object_to_map(values);
+            #import_version
+            let present = Self::perstruct_present_keys(&map);
+            let result = self.perstruct_load_into(&map);
+            let all_keys: &[&'static str] = &[#( #all_keys ),*];
+            let applied = all_keys
+                .iter()
+                .copied()
+                .filter(|key| {
+                    (migrated || present.contains(key))
+                        && !result.deserialization_errors().iter().any(|error| error.key == *key)
+                })
+                .collect::<::std::vec::Vec<_>>();
+            for key in applied.iter().copied() {
+                self._perstruct_dirty_fields.insert(key);
+            }
+            ::std::result::Result::Ok(#crate_path::ImportReport {
+                applied,
+                unknown: result.unknown_fields,
+                invalid: result.deserialization_errors,
+                exported_version,
+                exported_at,
+            })
+        }
+    }
+}
+
+/// `serde::Serialize` and `serde::Deserialize` for the whole struct with `serde`, using the
+/// persisted keys as field names. Fields are (de)serialized with their own serde impl, or as the
+/// string of `serialize_with`/`deserialize_with`, or through their `serialize_as` proxy.
+fn generate_serde_impl(
+    options: &PerstructOptions,
+    struct_ty: &StructTy,
+    fields: &[PerstructField],
+    skipped_fields: &[SkippedField],
+) -> proc_macro2::TokenStream {
+    let name = struct_ty.ident.to_string();
+    let serialize_fields = fields.iter().map(|field| {
+        let cfg = field.cfg();
+        let key = &field.persisted_key;
+        let value = if let Some(proxy) = field.proxy_expr(&field.reference(quote! { self })) {
+            quote! { &#proxy }
+        } else if let Some(serialize_with) = &field.serialize_with {
+            let value = field.reference(quote! { self });
+            quote! { &#serialize_with(#value).map_err(::serde::ser::Error::custom)? }
+        } else {
+            field.reference(quote! { self })
+        };
+        quote! {
+            #cfg
+            {
+                state.serialize_field(#key, #value)?;
+            }
+        }
+    });
+    let locals = fields
+        .iter()
+        .map(|field| format_ident!("{}_value", field.ident))
+        .collect::<Vec<_>>();
+    let declare_locals = fields.iter().zip(&locals).map(|(field, local)| {
+        let cfg = field.cfg();
+        let ty = &field.ty;
+        quote! {
+            #cfg
+            let mut #local: ::std::option::Option<#ty> = ::std::option::Option::None;
+        }
+    });
+    let mut arms = vec![];
+    for (field, local) in fields.iter().zip(&locals) {
+        let ty = &field.ty;
+        let value = if let Some(proxy) = &field.serialize_as {
+            quote! {
+                <#proxy as ::std::convert::TryInto<#ty>>::try_into(map.next_value::<#proxy>()?)
+                    .map_err(::serde::de::Error::custom)?
+            }
+        } else if let Some(deserialize_with) = &field.deserialize_with {
+            quote! {
+                #deserialize_with(&map.next_value::<::std::string::String>()?).map_err(::serde::de::Error::custom)?
+            }
+        } else {
+            quote! { map.next_value::<#ty>()? }
+        };
+        // Values a `one_of` or `validate` field doesn't allow are treated like missing values
+        let store = match field.check_allowed_expr(options, value.clone()) {
+            Some(check) if field.no_default => quote! {
+                #local = ::std::option::Option::Some(#check.map_err(::serde::de::Error::custom)?);
+            },
+            Some(check) => quote! { #local = #check.ok(); },
+            None => quote! { #local = ::std::option::Option::Some(#value); },
+        };
+        let field_keys = std::iter::once(field.persisted_key.clone())
+            .chain(field.aliases.iter().cloned().map(PersistedKey::Literal));
+        for field_key in field_keys {
+            arms.push((field_key, field.cfg(), store.clone()));
+        }
+    }
+    let dispatch = generate_key_dispatch(
+        quote! { key.as_str() },
+        arms,
+        quote! { map.next_value::<::serde::de::IgnoredAny>()?; },
+    );
+    let resolve_fields = fields.iter().zip(&locals).map(|(field, local)| {
+        let cfg = field.cfg();
+        let ident = &field.ident;
+        let key = &field.persisted_key;
+        let missing = if field.no_default {
+            quote! { return ::std::result::Result::Err(::serde::de::Error::missing_field(#key)) }
+        } else {
+            let default = field.default_expr();
+            quote! {
+                {
+                    defaulted.push(#key);
+                    #default
+                }
+            }
+        };
+        let value = field.wrap_expr(
+            options,
+            quote! {
+                match #local {
+                    ::std::option::Option::Some(value) => value,
+                    ::std::option::Option::None => #missing,
+                }
+            },
+        );
+        quote! {
+            #cfg
+            let #ident = #value;
+        }
+    });
+    let idents = fields.iter().map(|field| {
+        let cfg = field.cfg();
+        let ident = &field.ident;
+        quote! { #cfg #ident }
+    });
+    let skipped_inits = skipped_fields.iter().map(SkippedField::default_init);
+    let no_dirty = options.dirty_none();
+    let injected_inits = options.injected_inits();
+    let expecting = format!("a map of the fields of {name}");
+
+    let ty = struct_ty.ty();
+    let path = struct_ty.path();
+    let (impl_generics, ty_generics, _) = struct_ty.generics.split_for_impl();
+    let where_clause = struct_ty.where_clause(None);
+    let mut de_generics = struct_ty.generics.clone();
+    de_generics.params.insert(0, syn::parse_quote! { 'de });
+    let (de_impl_generics, _, _) = de_generics.split_for_impl();
+    let impl_header = struct_ty.impl_header(None, None);
+    let serialize_header = struct_ty.impl_header(Some(quote! { ::serde::Serialize }), None);
+    quote! {
+        #impl_header {
+            #[doc(hidden)]
+            fn __perstruct_visit_map<'de, A: ::serde::de::MapAccess<'de>>(
+                mut map: A
+            ) -> ::std::result::Result<Self, A::Error> {
+                #( #declare_locals )*
+                while let ::std::option::Option::Some(key) = map.next_key::<::std::string::String>()? {
+                    #dispatch
+                }
+                let mut defaulted: ::std::vec::Vec<&'static str> = ::std::vec::Vec::new();
+                #( #resolve_fields )*
+                let mut value = Self {
+                    _perstruct_dirty_fields: #no_dirty,
+                    #injected_inits
+                    #( #idents, )*
+                    #( #skipped_inits, )*
+                };
+                for key in defaulted {
+                    value._perstruct_dirty_fields.insert(key);
+                }
+                ::std::result::Result::Ok(value)
+            }
+        }
+
+        #[automatically_derived]
+        #serialize_header {
+            fn serialize<S: ::serde::Serializer>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error> {
+                use ::serde::ser::SerializeStruct as _;
+                let len = Self::__perstruct_key_slice().len();
+                let mut state = serializer.serialize_struct(#name, len)?;
+                #( #serialize_fields )*
+                state.end()
+            }
+        }
+
+        const _: () = {
+            struct PerstructVisitor #impl_generics #where_clause {
+                marker: ::std::marker::PhantomData<fn() -> #ty>,
+            }
+
+            impl #de_impl_generics ::serde::de::Visitor<'de> for PerstructVisitor #ty_generics
+                #where_clause
+            {
+                type Value = #ty;
+                fn expecting(&self, formatter: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                    formatter.write_str(#expecting)
+                }
+                fn visit_map<A: ::serde::de::MapAccess<'de>>(
+                    self,
+                    map: A
+                ) -> ::std::result::Result<Self::Value, A::Error> {
+                    #path::__perstruct_visit_map(map)
+                }
+            }
+
+            #[automatically_derived]
+            impl #de_impl_generics ::serde::Deserialize<'de> for #ty #where_clause {
+                fn deserialize<D: ::serde::Deserializer<'de>>(deserializer: D) -> ::std::result::Result<Self, D::Error> {
+                    deserializer.deserialize_map(PerstructVisitor {
+                        marker: ::std::marker::PhantomData,
+                    })
+                }
+            }
+        };
+    }
+}
+
+/// `Debug` with `debug`, formatting the declared fields (persisted and skipped) by their names,
+/// without the dirty fields and the other fields used by the generated code. The values of
+/// `secret` fields are redacted.
+fn generate_debug_impl(
+    struct_ty: &StructTy,
+    declared_fields: &syn::Fields,
+    fields: &[PerstructField],
+) -> proc_macro2::TokenStream {
+    let name = struct_ty.ident.to_string();
+    let debug_fields = declared_fields
+        .iter()
+        .filter_map(|field| field.ident.as_ref().map(|ident| (field, ident)))
+        .filter(|(_, ident)| *ident != DIRTY_FIELDS)
+        .map(|(field, ident)| {
+            let cfg_attrs = field
+                .attrs
+                .iter()
+                .filter(|attr| attr.path().is_ident("cfg"));
+            let name = ident.to_string();
+            let name = name.trim_start_matches("r#");
+            let secret = fields
+                .iter()
+                .any(|field| field.secret && field.ident == *ident);
+            let value = if secret {
+                quote! { &"<redacted>" }
+            } else {
+                quote! { &self.#ident }
+            };
+            quote! {
+                #( #cfg_attrs )*
+                {
+                    debug.field(#name, #value);
+                }
+            }
+        });
+    let debug_bounds = struct_ty.generics.type_params().map(|param| {
+        let ident = &param.ident;
+        quote! { #ident: ::std::fmt::Debug, }
+    });
+    let impl_header = struct_ty.impl_header(
+        Some(quote! { ::std::fmt::Debug }),
+        Some(quote! { #( #debug_bounds )* }),
+    );
+    quote! {
+        #[automatically_derived]
+        #impl_header {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                let mut debug = f.debug_struct(#name);
+                #( #debug_fields )*
+                debug.finish()
+            }
+        }
+    }
+}
+
+fn generate_apply_impl(
+    options: &PerstructOptions,
+    fields: &[PerstructField],
+) -> proc_macro2::TokenStream {
+    let mut arms = vec![];
+    for field in fields {
+        let ident = &field.ident;
+        let key = &field.persisted_key;
+        let deserialize = field.deserialize_expr(options, quote! { value.as_ref() });
+        let normalize = field
+            .normalize_expr(options, field.reference_mut(quote! { self }))
+            .unwrap_or(quote! { false });
+        let record_loaded =
+            options.record_loaded(&quote! { self }, key, &quote! { value.as_ref() });
+        let value = field.wrap_expr(
+            options,
+            quote! { #deserialize.map_err(|(message, _, _)| message)? },
+        );
+        let body = quote! {
+            self.#ident = #value;
+            #record_loaded
+            if !#normalize {
+                self._perstruct_dirty_fields.remove(#key);
+            }
+            ::std::result::Result::Ok(true)
+        };
+        let field_keys = std::iter::once(key.clone())
+            .chain(field.aliases.iter().cloned().map(PersistedKey::Literal));
+        arms.extend(field_keys.map(|field_key| (field_key, field.cfg(), body.clone())));
+    }
+    let dispatch = generate_key_dispatch(
+        quote! { key },
+        arms,
+        quote! { ::std::result::Result::Ok(false) },
+    );
+    let crate_path = options.crate_path();
+    quote! {
+        /// Applies a single stored key/value, like `from_map` does for each entry of the map.
+        /// Returns `Ok(false)` for unknown keys. The field is not marked dirty, since the value
+        /// comes from the store.
+        pub fn perstruct_apply<V: ::std::convert::AsRef<str>>(
+            &mut self,
+            key: &str,
+            value: V
+        ) -> ::std::result::Result<bool, ::std::string::String> {
+            #dispatch
+        }
+        /// Feeds a single stored key/value into the struct as it arrives, e.g. from a change data
+        /// capture stream: like `perstruct_apply`, with the outcome as a `FeedOutcome`.
+        pub fn perstruct_feed(&mut self, key: &str, value: &str) -> #crate_path::FeedOutcome {
+            match self.perstruct_apply(key, value) {
+                ::std::result::Result::Ok(true) => #crate_path::FeedOutcome::Applied,
+                ::std::result::Result::Ok(false) => #crate_path::FeedOutcome::Unknown,
+                ::std::result::Result::Err(message) => #crate_path::FeedOutcome::DeserError(message),
+            }
+        }
+    }
+}
+
+fn generate_by_key_impl(
+    options: &PerstructOptions,
+    struct_ty: &StructTy,
+    fields: &[PerstructField],
+) -> proc_macro2::TokenStream {
+    let crate_path = options.crate_path();
+    let struct_ident = &struct_ty.ident;
+    let get_arms = fields
+        .iter()
+        .map(|field| {
+            let key = &field.persisted_key;
+            let serialize = field.serialize_expr(options, field.reference(quote! { self }));
+            let body = quote! {
+                ::std::option::Option::Some(#serialize.map_err(|message| #crate_path::DynAccessError::Serialize {
+                    key: #key,
+                    message,
+                }))
+            };
+            (key.clone(), field.cfg(), body)
+        })
+        .collect();
+    let get_dispatch = generate_key_dispatch(
+        quote! { key },
+        get_arms,
+        quote! { ::std::option::Option::None },
+    );
+    let set_arms = fields
+        .iter()
+        .map(|field| {
+            let ident = &field.ident;
+            let key = &field.persisted_key;
+            let deserialize = field.deserialize_expr(options, quote! { value });
+            let normalize = field
+                .normalize_expr(options, field.reference_mut(quote! { self }))
+                .map(|normalize| quote! { #normalize; });
+            if field.readonly {
+                let body = quote! {
+                    ::std::result::Result::Err(#crate_path::DynAccessError::ReadOnly(#key))
+                };
+                return (key.clone(), field.cfg(), body);
+            }
+            let notify = notify_observer(options, struct_ident, field);
+            let post_set = options.post_set(key);
+            let value = field.wrap_expr(options, quote! { value });
+            let body = quote! {
+                let value = #deserialize.map_err(|(message, line, column)| {
+                    #crate_path::DynAccessError::Deserialize(#crate_path::PerstructFieldError {
+                        key: #key,
+                        message,
+                        line,
+                        column,
+                    })
+                })?;
+                self.#ident = #value;
+                #normalize
+                self._perstruct_dirty_fields.insert(#key);
+                #notify
+                #post_set
+                ::std::result::Result::Ok(())
+            };
+            (key.clone(), field.cfg(), body)
+        })
+        .collect();
+    let set_dispatch = generate_key_dispatch(
+        quote! { key },
+        set_arms,
+        quote! { ::std::result::Result::Err(#crate_path::DynAccessError::UnknownKey(key.to_string())) },
+    );
+    let impl_header = struct_ty.impl_header(None, None);
+    let dyn_access_header = struct_ty.impl_header(Some(quote! { #crate_path::DynAccess }), None);
+    quote! {
+        #impl_header {
+            /// Serializes the field with the persisted key `key`, `None` if there is no such field.
+            pub fn perstruct_get_by_key(
+                &self,
+                key: &str
+            ) -> ::std::option::Option<::std::result::Result<::std::string::String, #crate_path::DynAccessError>> {
+                #get_dispatch
+            }
+            /// Deserializes `value` into the field with the persisted key `key` and marks it
+            /// dirty, like its setter.
+            pub fn perstruct_set_by_key(
+                &mut self,
+                key: &str,
+                value: &str
+            ) -> ::std::result::Result<(), #crate_path::DynAccessError> {
+                #set_dispatch
+            }
+        }
+
+        #[automatically_derived]
+        #dyn_access_header {
+            fn get_json(&self, key: &str) -> ::std::option::Option<::std::result::Result<::std::string::String, #crate_path::DynAccessError>> {
+                self.perstruct_get_by_key(key)
+            }
+            fn set_json(&mut self, key: &str, value: &str) -> ::std::result::Result<(), #crate_path::DynAccessError> {
+                self.perstruct_set_by_key(key, value)
+            }
+        }
+    }
+}
+
+fn generate_diff_map_impl(
+    options: &PerstructOptions,
+    fields: &[PerstructField],
+) -> proc_macro2::TokenStream {
+    let crate_path = options.crate_path();
+    let compare_fields = fields.iter().map(|field| {
+        let cfg = field.cfg();
+        let key = &field.persisted_key;
+        let serialize = field.serialize_expr(options, field.reference(quote! { self }));
+        quote! {
+            #cfg
+            {
+                let value = #serialize?;
+                match stored.remove(#key) {
+                    ::std::option::Option::None => diff.inserts.push((#key, value)),
+                    ::std::option::Option::Some(stored_value) if stored_value != value => diff.updates.push((#key, value)),
+                    ::std::option::Option::Some(_) => {}
+                }
+            }
+        }
+    });
+    quote! {
+        /// Compares all fields with the contents of a store. Values are compared in their
+        /// serialized form.
+        pub fn perstruct_diff_map<TKey, TValue>(
+            &self,
+            stored: &::std::collections::HashMap<TKey, TValue>
+        ) -> ::std::result::Result<#crate_path::PerstructMapDiff, ::std::string::String>
+            where TKey: ::std::convert::AsRef<str>,
+                  TValue: ::std::convert::AsRef<str>
+        {
+            let mut stored = stored
+                .iter()
+                .map(|(key, value)| (key.as_ref(), value.as_ref()))
+                .collect::<::std::collections::HashMap<&str, &str>>();
+            let mut diff = #crate_path::PerstructMapDiff::default();
+            #( #compare_fields )*
+            diff.deletes = stored.into_keys().map(|key| key.to_string()).collect();
+            diff.deletes.sort();
+            ::std::result::Result::Ok(diff)
+        }
+    }
+}
+
+fn generate_diff_impl(
+    options: &PerstructOptions,
+    fields: &[PerstructField],
+) -> proc_macro2::TokenStream {
+    let compare_fields = fields.iter().map(|field| {
+        let cfg = field.cfg();
+        let key = &field.persisted_key;
+        let serialize_new = field.serialize_expr(options, field.reference(quote! { self }));
+        let serialize_old = field.serialize_expr(options, field.reference(quote! { other }));
+        quote! {
+            #cfg
+            {
+                let value = #serialize_new?;
+                if value != #serialize_old? {
+                    diff.push((#key, value));
+                }
+            }
+        }
+    });
+    quote! {
+        /// The keys whose serialized value differs between `self` and `other`, with the value of
+        /// `self`, in the order of `perstruct_keys`. Dirty flags are not consulted.
+        pub fn perstruct_diff(
+            &self,
+            other: &Self,
+        ) -> ::std::result::Result<::std::vec::Vec<(&'static str, ::std::string::String)>, ::std::string::String> {
+            let mut diff = ::std::vec![];
+            #( #compare_fields )*
+            ::std::result::Result::Ok(diff)
+        }
+    }
+}
+
+fn generate_effective_values_impl(
+    options: &PerstructOptions,
+    fields: &[PerstructField],
+) -> proc_macro2::TokenStream {
+    let crate_path = options.crate_path();
+    let effective_values = fields
+        .iter()
+        .filter(|field| !field.no_default)
+        .map(|field| {
+            let cfg = field.cfg();
+            let ty = &field.ty;
+            let key = &field.persisted_key;
+            let default = field.default_expr();
+            let serialize_current = field.serialize_expr(options, field.reference(quote! { self }));
+            let serialize_default = field.serialize_expr(options, quote! { &default });
+            quote! {
+                #cfg
+                {
+                    let default: #ty = #default;
+                    let current = #serialize_current?;
+                    let default = #serialize_default?;
+                    values.push(#crate_path::EffectiveValue {
+                        key: #key,
+                        differs_from_default: current != default,
+                        current,
+                        default,
+                        is_dirty: self._perstruct_dirty_fields.contains(#key),
+                    });
+                }
+            }
+        });
+    quote! {
+        /// The current and default value of every field with a default, in their serialized form
+        /// and in the order of `perstruct_keys`.
+        pub fn perstruct_effective_values(
+            &self,
+        ) -> ::std::result::Result<::std::vec::Vec<#crate_path::EffectiveValue>, ::std::string::String> {
+            let mut values = ::std::vec![];
+            #( #effective_values )*
+            ::std::result::Result::Ok(values)
+        }
+    }
+}
+
+fn generate_stores_impl(
+    options: &PerstructOptions,
+    fields: &[PerstructField],
+) -> proc_macro2::TokenStream {
+    let crate_path = options.crate_path();
+    let mut stores = vec![];
+    for field in fields {
+        let store = field.store.as_deref().unwrap_or(DEFAULT_STORE);
+        if !stores.contains(&store) {
+            stores.push(store);
+        }
+    }
+    if !stores.contains(&DEFAULT_STORE) {
+        stores.insert(0, DEFAULT_STORE);
+    }
+    let store_fields = stores
+        .iter()
+        .map(|store| {
+            fields
+                .iter()
+                .filter(|field| field.store.as_deref().unwrap_or(DEFAULT_STORE) == *store)
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let keys_arms = stores.iter().zip(&store_fields).map(|(store, fields)| {
+        let keys = cfg_keys(fields.iter().copied());
+        quote! { #store => ::std::vec![#( #keys ),*], }
+    });
+    let load_arms = stores.iter().zip(&store_fields).map(|(store, fields)| {
+        let load_entries = generate_load_entries(
+            options,
+            fields,
+            quote! { self },
+            quote! { self._perstruct_dirty_fields },
+        );
+        let collect_missing_required = generate_collect_missing_required(options, fields);
+        quote! {
+            #store => {
+                #load_entries
+                #collect_missing_required
+                // No validation, the other stores may not be loaded yet
+                #crate_path::PerstructLoadResult::new(())
+                    .with_deserialization_errors(deserialization_errors)
+                    .with_unknown_fields(unknown_fields, unknown_overflow)
+                    .with_shadowed_aliases(shadowed_aliases)
+                    .with_used_aliases(used_aliases)
+                    .with_env_overrides(env_overrides)
+                    .with_missing_required(missing_required)
+            }
+        }
+    });
+    let changes_arms = stores.iter().zip(&store_fields).map(|(store, fields)| {
+        let collect_changes = generate_collect_changes(options, fields);
+        quote! {
+            #store => {
+                #collect_changes
+                ::std::result::Result::Ok(changes)
+            }
+        }
+    });
+    let pending_all = if options.single_document.is_some() {
+        quote! { usize::from(!self._perstruct_dirty_fields.is_empty()) }
+    } else {
+        // Counted like `perstruct_get_changes` emits them, the version and the preserved unknown
+        // entries only follow when any field is dirty
+        let version = options.version.map(|_| quote! { + 1 });
+        let unknown = options
+            .preserve_unknown
+            .then(|| quote! { + self._perstruct_unknown.len() });
+        quote! {
+            match self._perstruct_dirty_fields.len() {
+                0 => 0,
+                dirty => dirty #version #unknown,
+            }
+        }
+    };
+    quote! {
+        /// Number of entries the `perstruct_get_changes` call for `scope` would return.
+        pub fn perstruct_pending_for(&self, scope: #crate_path::SaveScope<'_>) -> usize {
+            match scope {
+                #crate_path::SaveScope::All => #pending_all,
+                #crate_path::SaveScope::Store(store) => Self::perstruct_keys_for_store(store)
+                    .into_iter()
+                    .filter(|key| self._perstruct_dirty_fields.contains(key))
+                    .count(),
+            }
+        }
+        /// Names of the stores the fields are routed to, the default store first.
+        pub fn perstruct_stores() -> &'static [&'static str] {
+            &[#( #stores ),*]
+        }
+        /// Keys of the fields routed to `store`.
+        pub fn perstruct_keys_for_store(store: &str) -> ::std::vec::Vec<&'static str> {
+            match store {
+                #( #keys_arms )*
+                _ => ::std::vec![],
+            }
+        }
+        /// Loads the fields routed to `store` from `map`. Keys of fields routed to other stores
+        /// are reported as unknown fields.
+        pub fn perstruct_load_store<TKey, TValue>(
+            &mut self,
+            store: &str,
+            map: &::std::collections::HashMap<TKey, TValue>
+        ) -> #crate_path::PerstructLoadResult<()>
+            where TKey: ::std::convert::AsRef<str>,
+                  TValue: ::std::convert::AsRef<str>
+        {
+            match store {
+                #( #load_arms )*
+                _ => #crate_path::PerstructLoadResult::new(()).with_unknown_fields(
+                    map.keys()
+                        .take(#crate_path::MAX_UNKNOWN_FIELDS)
+                        .map(|key| key.as_ref().to_string())
+                        .collect(),
+                    map.len().saturating_sub(#crate_path::MAX_UNKNOWN_FIELDS),
+                ),
+            }
+        }
+        /// Like `perstruct_get_changes`, but only for the fields routed to `store`.
+        pub fn perstruct_get_changes_for_store(
+            &self,
+            store: &str
+        ) -> ::std::result::Result<::std::vec::Vec<(&'static str, ::std::string::String)>, ::std::string::String> {
+            match store {
+                #( #changes_arms )*
+                _ => ::std::result::Result::Ok(::std::vec![]),
+            }
+        }
+    }
+}
+
+/// The `<Struct>Loader` definition and its impls, returned separately so only the impls are
+/// wrapped in the const block of `process_struct`.
+fn generate_loader_impl(
+    options: &PerstructOptions,
+    vis: &syn::Visibility,
+    struct_ty: &StructTy,
+    fields: &[PerstructField],
+) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    let crate_path = options.crate_path();
+    let ident = &struct_ty.ident;
+    let loader_ident = format_ident!("{}Loader", ident);
+    let doc =
+        format!("Loads a [`{ident}`] from a map, with some fields pre-seeded with typed values.");
+    let loader_fields = fields.iter().map(|field| {
+        let cfg = field.cfg();
+        let ident = &field.ident;
+        let ty = &field.ty;
+        quote! { #cfg #ident: ::std::option::Option<#ty> }
+    });
+    let setters = fields.iter().map(|field| {
+        let cfg = field.cfg();
+        let ident = &field.ident;
+        let ty = &field.ty;
+        let set_ident = format_ident!("set_{}", ident);
+        let doc = format!("Pre-seeds `{ident}` with a typed value.");
+        quote! {
+            #cfg
+            #[doc = #doc]
+            pub fn #set_ident(mut self, value: #ty) -> Self {
+                self.#ident = ::std::option::Option::Some(value);
+                self
+            }
+        }
+    });
+    let ty = struct_ty.ty();
+    let path = struct_ty.path();
+    let default_bound = default_bound(options, fields, ty.clone());
+    let loader_idents = fields.iter().map(|field| {
+        let cfg = field.cfg();
+        let ident = &field.ident;
+        quote! { #cfg #ident }
+    });
+    let (impl_generics, ty_generics, _) = struct_ty.generics.split_for_impl();
+    let where_clause = struct_ty.where_clause(None);
+    let loader_impl_header =
+        quote! { impl #impl_generics #loader_ident #ty_generics #where_clause };
+    let impl_header = struct_ty.impl_header(None, None);
+    let apply_seeds = fields.iter().map(|field| {
+        let cfg = field.cfg();
+        let ident = &field.ident;
+        let key = &field.persisted_key;
+        let value = field.wrap_expr(options, quote! { value });
+        quote! {
+            #cfg
+            if let ::std::option::Option::Some(value) = self.#ident {
+                result.value_mut().#ident = #value;
+                result.value_mut()._perstruct_dirty_fields.remove(#key);
+                result.deserialization_errors.retain(|error| error.key != #key);
+            }
+        }
+    });
+    let definition = quote! {
+        #[doc = #doc]
+        #vis struct #loader_ident #impl_generics #where_clause {
+            #( #loader_fields, )*
+            _perstruct_marker: ::std::marker::PhantomData<fn() -> #ty>,
+        }
+    };
+    let impls = quote! {
+        #[automatically_derived]
+        impl #impl_generics ::std::default::Default for #loader_ident #ty_generics #where_clause {
+            fn default() -> Self {
+                Self {
+                    #( #loader_idents: ::std::option::Option::None, )*
+                    _perstruct_marker: ::std::marker::PhantomData,
+                }
+            }
+        }
+
+        #loader_impl_header {
+            #( #setters )*
+
+            /// Loads the struct like `from_map`, then applies the pre-seeded values.
+            /// Pre-seeded fields win over the map and are not marked dirty.
+            pub fn apply_map<TKey, TValue>(
+                self,
+                map: &::std::collections::HashMap<TKey, TValue>
+            ) -> #crate_path::PerstructLoadResult<#ty>
+                where TKey: ::std::convert::AsRef<str>,
+                      TValue: ::std::convert::AsRef<str>,
+                      #default_bound
+            {
+                let mut result = #path::from_map(map);
+                #( #apply_seeds )*
+                result
+            }
+        }
+
+        #impl_header {
+            /// Returns a loader for pre-seeding fields with typed values before loading a map.
+            pub fn loader() -> #loader_ident #ty_generics {
+                #loader_ident::default()
+            }
+        }
+    };
+    (definition, impls)
+}
+
+/// Generates the `<Struct>Updater` collecting typed values for `perstruct_update`, returned
+/// separately like in `generate_loader_impl`.
+fn generate_updater_impl(
+    options: &PerstructOptions,
+    vis: &syn::Visibility,
+    struct_ty: &StructTy,
+    fields: &[PerstructField],
+) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    let ident = &struct_ty.ident;
+    let updater_ident = format_ident!("{}Updater", ident);
+    let doc =
+        format!("Collects new values for fields of a [`{ident}`], which are only set by `commit`.");
+    // The updater sets the fields through their setters, which `readonly` fields don't have
+    let fields = fields
+        .iter()
+        .filter(|field| !field.readonly)
+        .collect::<Vec<_>>();
+    let updater_fields = fields.iter().map(|field| {
+        let cfg = field.cfg();
+        let ident = &field.ident;
+        let ty = &field.ty;
+        quote! { #cfg #ident: ::std::option::Option<#ty>, }
+    });
+    let updater_idents = fields.iter().map(|field| {
+        let cfg = field.cfg();
+        let ident = &field.ident;
+        quote! { #cfg #ident: ::std::option::Option::None, }
+    });
+    let methods = fields.iter().map(|field| {
+        let cfg = field.cfg();
+        let ident = &field.ident;
+        let ty = &field.ty;
+        let (value_ty, value) = if options.into_setters || field.into_setter {
+            (
+                quote! { impl ::std::convert::Into<#ty> },
+                quote! { value.into() },
+            )
+        } else {
+            (quote! { #ty }, quote! { value })
+        };
+        let doc = format!("Sets `{ident}` on `commit`.");
+        let method = field.updater_ident(options);
+        quote! {
+            #cfg
+            #[doc = #doc]
+            pub fn #method(mut self, value: #value_ty) -> Self {
+                self.#ident = ::std::option::Option::Some(#value);
+                self
+            }
+        }
+    });
+    // The values of `validate` fields are checked before any field is set, so a rejected value
+    // leaves the struct untouched
+    let check = fields.iter().filter_map(|field| {
+        let cfg = field.cfg();
+        let ident = &field.ident;
+        let check = field
+            .validate
+            .as_ref()
+            .and(field.check_allowed_expr(options, quote! { value }))?;
+        Some(quote! {
+            #cfg
+            if let ::std::option::Option::Some(value) = self.#ident.take() {
+                self.#ident = ::std::option::Option::Some(#check?);
+            }
+        })
+    });
+    let apply = fields.iter().map(|field| {
+        let cfg = field.cfg();
+        let ident = &field.ident;
+        let set_ident = field.setter_ident(options);
+        let set = if field.validate.is_some() {
+            quote! { self._perstruct_settings.#set_ident(value)?; }
+        } else {
+            quote! { self._perstruct_settings.#set_ident(value); }
+        };
+        quote! {
+            #cfg
+            if let ::std::option::Option::Some(value) = self.#ident {
+                #set
+            }
+        }
+    });
+    let commit = if fields.iter().any(|field| field.validate.is_some()) {
+        quote! {
+            /// Sets the collected values through the setters, which mark the fields dirty, or
+            /// returns the error of the first value a `validate` function rejects without setting
+            /// any field.
+            pub fn commit(mut self) -> ::std::result::Result<(), ::std::string::String> {
+                #( #check )*
+                #( #apply )*
+                ::std::result::Result::Ok(())
+            }
+        }
+    } else {
+        quote! {
+            /// Sets the collected values through the setters, which mark the fields dirty.
+            pub fn commit(self) {
+                #( #apply )*
+            }
+        }
+    };
+    let ty = struct_ty.ty();
+    let mut updater_generics = struct_ty.generics.clone();
+    updater_generics
+        .params
+        .insert(0, syn::parse_quote! { 'perstruct });
+    let (impl_generics, ty_generics, _) = updater_generics.split_for_impl();
+    let where_clause = struct_ty.where_clause(None);
+    let impl_header = struct_ty.impl_header(None, None);
+    let definition = quote! {
+        #[doc = #doc]
+        #[must_use = "the values are only set by `commit`"]
+        #vis struct #updater_ident #impl_generics #where_clause {
+            _perstruct_settings: &'perstruct mut #ty,
+            #( #updater_fields )*
+        }
+    };
+    let impls = quote! {
+        impl #impl_generics #updater_ident #ty_generics #where_clause {
+            #( #methods )*
+
+            #commit
+        }
+
+        #impl_header {
+            /// Returns an updater for setting several fields at once: chain a method per field
+            /// and finish with `commit`. Nothing changes before `commit`, so a panic while
+            /// collecting the values leaves the struct untouched.
+            pub fn perstruct_update<'perstruct>(
+                &'perstruct mut self,
+            ) -> #updater_ident #ty_generics {
+                #updater_ident {
+                    _perstruct_settings: self,
+                    #( #updater_idents )*
+                }
+            }
+        }
+    };
+    (definition, impls)
+}
+
+fn generate_methods_impl(
+    options: &PerstructOptions,
+    struct_ty: &StructTy,
+    fields: &[PerstructField],
+) -> proc_macro2::TokenStream {
+    let struct_ident = &struct_ty.ident;
+    let methods = fields.iter().map(|field| {
+        let cfg = field.cfg();
+        let ident = &field.ident;
+        let ty = &field.ty;
+        let (reference_ty, reference_return) = if field.lazy {
+            let crate_path = options.crate_path();
+            (
+                quote! { ::std::result::Result<&#ty, &#crate_path::PerstructFieldError> },
+                quote! { self.#ident.get() },
+            )
+        } else {
+            getter_return(ty, quote! { self.#ident })
+        };
+        let get_ident = field.getter_ident(options);
+        let set_ident = field.setter_ident(options);
+        let key_lit = &field.persisted_key;
+        let update_ident = method_ident("update_", ident, "");
+        let reset_ident = method_ident("reset_", ident, "");
+        let default = field.default_expr();
+        let getter_doc = if field.lazy {
+            format!(
+                "Returns the value of `{ident}`, deserializing it on first access, or the error \
+                deserializing it."
+            )
+        } else {
+            format!("Returns the value of `{ident}`.")
+        };
+        // The setters of a `validate` field return the error instead of changing the field
+        let fallible = field.validate.is_some();
+        let setter_doc = if fallible {
+            format!(
+                "Sets `{ident}` and marks it dirty, or returns the error of the `validate` \
+                function without changing the field."
+            )
+        } else if field.is_checked() && !field.reject_invalid {
+            format!(
+                "Sets `{ident}` and marks it dirty. A value that is not one of the allowed values \
+                stores the default instead, `{}` returns an error for it.",
+                field.try_setter_ident(options)
+            )
+        } else if field.is_checked() {
+            format!(
+                "Sets `{ident}` and marks it dirty. A value that is not one of the allowed values \
+                is ignored."
+            )
+        } else {
+            format!("Sets `{ident}` and marks it dirty.")
+        };
+        let (setter_ty, setter_value) = if options.into_setters || field.into_setter {
+            (
+                quote! { impl ::std::convert::Into<#ty> },
+                quote! { value.into() },
+            )
+        } else {
+            (quote! { #ty }, quote! { value })
+        };
+        let update_doc = if fallible {
+            format!(
+                "Updates a copy of `{ident}` and sets it like `{set_ident}`, so the field is \
+                unchanged and not marked dirty when the `validate` function rejects the updated \
+                value."
+            )
+        } else if field.precise_updates {
+            format!(
+                "Updates `{ident}` in place and marks it dirty if it changed, comparing it with a \
+                clone taken before `f` if it's `PartialEq + Clone`."
+            )
+        } else {
+            format!("Updates `{ident}` in place and marks it dirty.")
+        };
+        let reset_doc = format!("Resets `{ident}` to its default value and marks it dirty.");
+        let is_dirty_ident = method_ident("is_", ident, "_dirty");
+        let is_dirty_doc = format!("Whether `{ident}` changed since it was loaded or saved.");
+        let mark_dirty_ident = method_ident("mark_", ident, "_dirty");
+        let mark_dirty_doc =
+            format!("Marks `{ident}` dirty so it's persisted with the next changes.");
+        let normalize = field
+            .normalize_expr(options, field.reference_mut(quote! { self }))
+            .map(|normalize| quote! { #normalize; });
+        let notify = notify_observer(options, struct_ident, field);
+        let post_set = options.post_set(key_lit);
+        // Values a `one_of` field doesn't allow are replaced by the default or ignored, the
+        // setters of a `validate` field return the error
+        let place = field.place(quote! { self });
+        let reference = field.reference(quote! { self });
+        let reference_mut = field.reference_mut(quote! { self });
+        let wrapped_value = field.wrap_expr(options, quote! { value });
+        let wrapped_default = field.wrap_expr(options, default.clone());
+        let assign = match field.check_allowed_expr(options, quote! { value }) {
+            Some(check) if fallible => quote! {
+                let value: #ty = #setter_value;
+                let value = #check?;
+                self.#ident = #wrapped_value;
+            },
+            Some(check) if field.reject_invalid => quote! {
+                let value: #ty = #setter_value;
+                let ::std::result::Result::Ok(value) = #check else {
+                    return;
+                };
+                self.#ident = #wrapped_value;
+            },
+            Some(check) => quote! {
+                let value: #ty = #setter_value;
+                let value = #check.unwrap_or_else(|_| #default);
+                self.#ident = #wrapped_value;
+            },
+            None => quote! {
+                let value: #ty = #setter_value;
+                self.#ident = #wrapped_value;
+            },
+        };
+        let update = match field.check_allowed_expr(options, reference.clone()) {
+            Some(_) if fallible => {
+                let check = field.check_allowed_expr(options, quote! { value });
+                quote! {
+                    let mut value: #ty = ::std::clone::Clone::clone(#reference);
+                    f(&mut value);
+                    let value = #check?;
+                    self.#ident = #wrapped_value;
+                }
+            }
+            Some(check) if field.reject_invalid => quote! {
+                let previous = ::std::clone::Clone::clone(#reference);
+                f(#reference_mut);
+                if #check.is_err() {
+                    #place = previous;
+                    return;
+                }
+            },
+            Some(check) => quote! {
+                f(#reference_mut);
+                if #check.is_err() {
+                    self.#ident = #wrapped_default;
+                }
+            },
+            None => quote! { f(#reference_mut); },
+        };
+        let (returns, ok) = fallible
+            .then(|| {
+                (
+                    quote! { -> ::std::result::Result<(), ::std::string::String> },
+                    quote! { ::std::result::Result::Ok(()) },
+                )
+            })
+            .unzip();
+        let update_bound = fallible.then(|| quote! { where for<'perstruct> #ty: ::std::clone::Clone });
+        let (take_snapshot, skip_unchanged) = field
+            .precise_updates
+            .then(|| {
+                let crate_path = options.crate_path();
+                (
+                    quote! {
+                        use #crate_path::__private::{CloneSnapshot as _, NoSnapshot as _};
+                        let snapshot = (&#crate_path::__private::UpdateProbe(#reference)).snapshot();
+                    },
+                    quote! {
+                        if snapshot.is_unchanged(#reference) {
+                            return #ok;
+                        }
+                    },
+                )
+            })
+            .unzip();
+        let try_set = field
+            .check_allowed_expr(options, quote! { value })
+            .map(|check| {
+                let try_set_ident = field.try_setter_ident(options);
+                let doc = format!(
+                    "Sets `{ident}` and marks it dirty, or returns why the value is not allowed \
+                    without changing the field."
+                );
+                let try_update_ident = format_ident!("try_update_{}", ident);
+                let try_update_doc = format!(
+                    "Updates a copy of `{ident}` and sets it like `{try_set_ident}`, so the field \
+                    is unchanged and not marked dirty when the updated value is not allowed."
+                );
+                let (try_set_body, try_update_body) = if fallible {
+                    (
+                        quote! { self.#set_ident(value) },
+                        quote! { self.#update_ident(f) },
+                    )
+                } else {
+                    (
+                        quote! {
+                            let value: #ty = #setter_value;
+                            let value = #check?;
+                            self.#set_ident(value);
+                            ::std::result::Result::Ok(())
+                        },
+                        quote! {
+                            let mut value: #ty = ::std::clone::Clone::clone(#reference);
+                            f(&mut value);
+                            let value = #check?;
+                            self.#set_ident(value);
+                            ::std::result::Result::Ok(())
+                        },
+                    )
+                };
+                quote! {
+                    #cfg
+                    #[doc = #doc]
+                    pub fn #try_set_ident(
+                        &mut self,
+                        value: #setter_ty,
+                    ) -> ::std::result::Result<(), ::std::string::String> {
+                        #try_set_body
+                    }
+                    #cfg
+                    #[doc = #try_update_doc]
+                    pub fn #try_update_ident(
+                        &mut self,
+                        f: impl ::std::ops::FnOnce(&mut #ty),
+                    ) -> ::std::result::Result<(), ::std::string::String>
+                        where for<'perstruct> #ty: ::std::clone::Clone
+                    {
+                        #try_update_body
+                    }
+                }
+            });
+        let reset = (!field.no_default && !field.readonly).then(|| {
+            quote! {
+                #cfg
+                #[doc = #reset_doc]
+                pub fn #reset_ident(&mut self) {
+                    self.#ident = #wrapped_default;
+                    #normalize
+                    self._perstruct_dirty_fields.insert(#key_lit);
+                    #notify
+                    #post_set
+                }
+            }
+        });
+        // `readonly` fields only get the getter and the dirty state methods
+        let setters = (!field.readonly).then(|| {
+            quote! {
+                #cfg
+                #[doc = #setter_doc]
+                pub fn #set_ident(&mut self, value: #setter_ty) #returns {
+                    #assign
+                    #normalize
+                    self._perstruct_dirty_fields.insert(#key_lit);
+                    #notify
+                    #post_set
+                    #ok
+                }
+                #cfg
+                #[doc = #update_doc]
+                pub fn #update_ident(&mut self, f: impl ::std::ops::FnOnce(&mut #ty)) #returns
+                    #update_bound
+                {
+                    #take_snapshot
+                    #update
+                    #normalize
+                    #skip_unchanged
+                    self._perstruct_dirty_fields.insert(#key_lit);
+                    #notify
+                    #post_set
+                    #ok
+                }
+                #try_set
+            }
+        });
+        quote! {
+            #cfg
+            #[doc = #getter_doc]
+            pub fn #get_ident(&self) -> #reference_ty {
+                #reference_return
+            }
+            #setters
+            #reset
+            #cfg
+            #[doc = #is_dirty_doc]
+            pub fn #is_dirty_ident(&self) -> bool {
+                self._perstruct_dirty_fields.contains(#key_lit)
+            }
+            #cfg
+            #[doc = #mark_dirty_doc]
+            pub fn #mark_dirty_ident(&mut self) {
+                self._perstruct_dirty_fields.insert(#key_lit);
+            }
+        }
+    });
+    let impl_header = struct_ty.impl_header(None, None);
+    quote::quote! {
+        #impl_header {
+            #(#methods)*
+        }
+    }
+}
+
+fn generate_field_handles_impl(
+    options: &PerstructOptions,
+    fields: &[PerstructField],
+) -> proc_macro2::TokenStream {
+    let crate_path = options.crate_path();
+    // Handles can set the field, so `readonly` fields have none
+    let handles = fields.iter().filter(|field| !field.readonly).map(|field| {
+        let cfg = field.cfg();
+        let ident = &field.ident;
+        let ty = &field.ty;
+        let key = &field.persisted_key;
+        let name = ident.to_string();
+        let const_ident =
+            syn::Ident::new(&name.trim_start_matches("r#").to_uppercase(), ident.span());
+        let set_ident = field.setter_ident(options);
+        let doc = format!("Handle to the field `{name}`.");
+        let allowed_values = field
+            .allowed_values()
+            .map(|allowed| quote! { .with_allowed_values(#allowed) });
+        let cfg_predicate = field
+            .cfg_predicate()
+            .map(|predicate| quote! { .with_cfg(#predicate) });
+        let secret = field.secret.then(|| quote! { .with_secret() });
+        let reference = field.reference(quote! { settings });
+        // Like `Field::set` documents, a value the `validate` function rejects is ignored
+        let set = if field.validate.is_some() {
+            quote! { { let _ = settings.#set_ident(value); } }
+        } else {
+            quote! { settings.#set_ident(value) }
+        };
+        quote! {
+            #cfg
+            #[doc = #doc]
+            pub const #const_ident: #crate_path::Field<Self, #ty> =
+                #crate_path::Field::<Self, #ty>::new(
+                    || #key,
+                    |settings| #reference,
+                    |settings, value| #set,
+                )#allowed_values #cfg_predicate #secret;
+        }
+    });
+    quote! {
+        #( #handles )*
+    }
+}
+
+/// The enum of typed changes, named `<Struct>Change` with a variant per field, and
+/// `perstruct_set_observer`, returned separately like in `generate_loader_impl`.
+fn generate_observer_impl(
+    vis: &syn::Visibility,
+    ident: &syn::Ident,
+    fields: &[PerstructField],
+) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    let change_ident = format_ident!("{}Change", ident);
+    let enum_doc = format!("A change of a field of `{ident}`, carrying the new value.");
+    let variants = fields.iter().map(|field| {
+        let cfg = field.cfg();
+        let variant = change_variant(field);
+        let ty = &field.ty;
+        let doc = format!("`{}` changed.", field.ident);
+        quote! {
+            #cfg
+            #[doc = #doc]
+            #variant(#ty)
+        }
+    });
+    let definition = quote! {
+        #[doc = #enum_doc]
+        #[derive(::std::clone::Clone)]
+        #vis enum #change_ident {
+            #( #variants ),*
+        }
+    };
+    let impls = quote! {
+        impl #ident {
+            /// Sends a change to `observer` after every change through the setters.
+            pub fn perstruct_set_observer(&mut self, observer: ::std::sync::mpsc::Sender<#change_ident>) {
+                self._perstruct_observer = ::std::option::Option::Some(observer);
+            }
+        }
+    };
+    (definition, impls)
+}
+
+/// Generates the `<Struct>View` struct with a member per persisted field and `perstruct_view`,
+/// returned separately like in `generate_loader_impl`. Members are copies for the types the
+/// getters return by value and references otherwise.
+fn generate_view_impl(
+    vis: &syn::Visibility,
+    ident: &syn::Ident,
+    fields: &[PerstructField],
+) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    let view_ident = format_ident!("{}View", ident);
+    let struct_doc = format!(
+        "The persisted fields of `{ident}`, returned by `{ident}::perstruct_view`. Copies for \
+        primitives and references otherwise."
+    );
+    let members = fields.iter().map(|field| {
+        let cfg = field.cfg();
+        let member = &field.ident;
+        let ty = &field.ty;
+        let ty = if is_copy_primitive_type(ty) {
+            quote! { #ty }
+        } else {
+            quote! { &'a #ty }
+        };
+        let doc = format!("The value of `{member}`.");
+        quote! {
+            #cfg
+            #[doc = #doc]
+            pub #member: #ty,
+        }
+    });
+    let initializers = fields.iter().map(|field| {
+        let cfg = field.cfg();
+        let member = &field.ident;
+        let value = if is_copy_primitive_type(&field.ty) {
+            quote! { self.#member }
+        } else {
+            quote! { &self.#member }
+        };
+        quote! {
+            #cfg
+            #member: #value,
+        }
+    });
+    let definition = quote! {
+        #[doc = #struct_doc]
+        #[derive(::std::clone::Clone, ::std::marker::Copy)]
+        #[non_exhaustive]
+        #vis struct #view_ident<'a> {
+            #( #members )*
+            _settings: ::std::marker::PhantomData<&'a #ident>,
+        }
+    };
+    let impls = quote! {
+        impl #ident {
+            /// All persisted fields at once, for code reading several of them in a hot loop.
+            #[inline]
+            pub fn perstruct_view(&self) -> #view_ident<'_> {
+                #view_ident {
+                    #( #initializers )*
+                    _settings: ::std::marker::PhantomData,
+                }
+            }
+        }
+    };
+    (definition, impls)
+}
+
+/// Variant of the change enum for `field`, the field name in `PascalCase`.
+fn change_variant(field: &PerstructField) -> syn::Ident {
+    let name = field.ident.to_string();
+    let pascal = to_pascal_case(name.trim_start_matches("r#"));
+    syn::Ident::new(&pascal, field.ident.span())
+}
+
+/// Statements sending the new value of `field` to the observer, with `observe = "channel"`, or
+/// calling the callbacks with its key, with `observe = "callbacks"`.
+fn notify_observer(
+    options: &PerstructOptions,
+    struct_ident: &syn::Ident,
+    field: &PerstructField,
+) -> Option<proc_macro2::TokenStream> {
+    if options.callbacks {
+        let key = &field.persisted_key;
+        return Some(quote! {
+            for callback in self._perstruct_callbacks.iter_mut() {
+                callback(#key);
+            }
+        });
+    }
+    if !options.observe {
+        return None;
+    }
+    let change_ident = format_ident!("{}Change", struct_ident);
+    let variant = change_variant(field);
+    let place = field.place(quote! { self });
+    Some(quote! {
+        if let ::std::option::Option::Some(observer) = &self._perstruct_observer {
+            // The receiver may be gone, which is not the setter's concern
+            let _ = observer.send(#change_ident::#variant(#place.clone()));
+        }
+    })
+}
+
+fn generate_dirty_fields_impl(options: &PerstructOptions) -> proc_macro2::TokenStream {
+    if options.dirty == DirtyTracking::HashSet {
+        return quote! {
+            /// Keys of the fields that changed since they were loaded or saved.
+            pub fn perstruct_dirty_fields(&self) -> &::std::collections::HashSet<&str> {
+                &self._perstruct_dirty_fields
+            }
+        };
+    }
+    quote! {
+        /// Keys of the fields that changed since they were loaded or saved.
+        pub fn perstruct_dirty_fields(&self) -> ::std::collections::HashSet<&'static str> {
+            self._perstruct_dirty_fields.iter().copied().collect()
+        }
+    }
+}
+
+/// Hidden `__perstruct_key_slice` returning the persisted keys as a static slice.
+fn generate_key_slice_impl(
+    options: &PerstructOptions,
+    fields: &[PerstructField],
+) -> proc_macro2::TokenStream {
+    let keys = cfg_keys(fields);
+    let slice = if options.maps_keys() {
+        // The mapped keys are indexed by field, including fields removed by `cfg`
+        quote! {
+            static KEYS: ::std::sync::OnceLock<::std::vec::Vec<&'static str>> = ::std::sync::OnceLock::new();
+            KEYS.get_or_init(|| ::std::vec![#( #keys ),*])
+        }
+    } else {
+        quote! { &[#( #keys ),*] }
+    };
+    quote! {
+        #[doc(hidden)]
+        fn __perstruct_key_slice() -> &'static [&'static str] {
+            #slice
+        }
+    }
+}
+
+fn generate_trait_impl(
+    options: &PerstructOptions,
+    struct_ty: &StructTy,
+    fields: &[PerstructField],
+) -> proc_macro2::TokenStream {
+    let crate_path = options.crate_path();
+    let path = struct_ty.path();
+    let default_bound = default_bound(options, fields, struct_ty.ty());
+    let perstruct_header =
+        struct_ty.impl_header(Some(quote! { #crate_path::Perstruct }), default_bound);
+    let erased_header = struct_ty.impl_header(Some(quote! { #crate_path::ErasedPerstruct }), None);
+    quote! {
+        #[automatically_derived]
+        #perstruct_header {
+            fn keys() -> &'static [&'static str] {
+                #path::__perstruct_key_slice()
+            }
+            fn from_map<TKey, TValue>(
+                map: &::std::collections::HashMap<TKey, TValue>
+            ) -> #crate_path::PerstructLoadResult<Self>
+                where TKey: ::std::convert::AsRef<str>,
+                      TValue: ::std::convert::AsRef<str>
+            {
+                #path::from_map(map)
+            }
+            fn get_changes(&self) -> ::std::result::Result<::std::vec::Vec<(&str, ::std::string::String)>, ::std::string::String> {
+                self.perstruct_get_changes()
+            }
+            fn saved(&mut self) {
+                self.perstruct_saved();
+            }
+        }
+
+        #[automatically_derived]
+        #erased_header {
+            fn dirty_keys(&self) -> ::std::vec::Vec<::std::string::String> {
+                self._perstruct_dirty_fields.iter().map(|key| key.to_string()).collect()
+            }
+            fn serialize_changes(&self) -> ::std::result::Result<::std::vec::Vec<(::std::string::String, ::std::string::String)>, ::std::string::String> {
+                ::std::result::Result::Ok(self
+                    .perstruct_get_changes()?
+                    .into_iter()
+                    .map(|(key, value)| (key.to_string(), value))
+                    .collect())
+            }
+            fn apply_entry(&mut self, key: &str, value: &str) -> ::std::result::Result<(), ::std::string::String> {
+                self.perstruct_apply(key, value).map(|_| ())
+            }
+            fn mark_saved(&mut self) {
+                self.perstruct_saved();
+            }
+        }
+    }
+}
+
+fn generate_default_impl(
+    options: &PerstructOptions,
+    struct_ty: &StructTy,
+    fields: &[PerstructField],
+    skipped_fields: &[SkippedField],
+) -> proc_macro2::TokenStream {
+    let default_fields = fields.iter().map(|field| {
+        let cfg = field.cfg();
+        let ident = &field.ident;
+        let default = field.wrap_expr(options, field.default_expr());
+        quote::quote! { #cfg #ident: #default }
     });
+    let no_dirty = options.dirty_none();
+    let injected_inits = options.injected_inits();
+    let default_skipped_fields = skipped_fields.iter().map(SkippedField::default_init);
+    let impl_header = struct_ty.impl_header(Some(quote! { ::std::default::Default }), None);
     quote::quote! {
         #[automatically_derived]
-        impl Default for #ident {
+        #impl_header {
             fn default() -> Self {
                 Self {
-                    _perstruct_dirty_fields: Default::default(),
-                    #(#default_fields),*,
-                    #(#default_skipped_fields),*
+                    _perstruct_dirty_fields: #no_dirty,
+                    #injected_inits
+                    #( #default_fields, )*
+                    #( #default_skipped_fields, )*
+                }
+            }
+        }
+    }
+}
+
+/// `try_default`, for structs with `try_default_fn` fields and no `no_default` fields.
+fn generate_try_default_impl(
+    options: &PerstructOptions,
+    fields: &[PerstructField],
+    skipped_fields: &[SkippedField],
+) -> Option<proc_macro2::TokenStream> {
+    if fields.iter().any(|field| field.no_default)
+        || fields.iter().all(|field| field.try_default_fn.is_none())
+    {
+        return None;
+    }
+    let default_fields = fields.iter().map(|field| {
+        let cfg = field.cfg();
+        let ident = &field.ident;
+        let default = field.wrap_expr(options, field.try_default_expr(quote! { errors }));
+        quote! { #cfg #ident: #default, }
+    });
+    let skipped_inits = skipped_fields.iter().map(SkippedField::default_init);
+    let no_dirty = options.dirty_none();
+    let injected_inits = options.injected_inits();
+    Some(quote! {
+        /// Like `Default::default()`, but fails with the key and error of every field whose
+        /// `try_default_fn` failed, instead of falling back to `Default::default()` for them.
+        pub fn try_default() -> ::std::result::Result<Self, ::std::vec::Vec<(&'static str, ::std::string::String)>> {
+            let mut errors = ::std::vec![];
+            let value = Self {
+                _perstruct_dirty_fields: #no_dirty,
+                #injected_inits
+                #( #default_fields )*
+                #( #skipped_inits, )*
+            };
+            if errors.is_empty() {
+                ::std::result::Result::Ok(value)
+            } else {
+                ::std::result::Result::Err(errors)
+            }
+        }
+    })
+}
+
+/// Name and generics of the struct, for the headers of the generated impls.
+struct StructTy {
+    ident: syn::Ident,
+    generics: syn::Generics,
+    /// Bounds added to the impls: `bound`, or serde and `Default` bounds on every type parameter.
+    bounds: Vec<syn::WherePredicate>,
+}
+
+impl StructTy {
+    /// Rejects lifetime parameters, which the generated `'static` keys and owned values can't
+    /// support.
+    fn new(input: &ItemStruct, options: &PerstructOptions) -> syn::Result<Self> {
+        if let Some(lifetime) = input.generics.lifetimes().next() {
+            return Err(syn::Error::new_spanned(
+                lifetime,
+                "Perstruct: structs with lifetime parameters are not supported",
+            ));
+        }
+        let bounds = match &options.bound {
+            Some(bound) => bound.iter().cloned().collect(),
+            None => input
+                .generics
+                .type_params()
+                .map(|param| {
+                    let ident = &param.ident;
+                    syn::parse_quote! {
+                        #ident: ::serde::Serialize + ::serde::de::DeserializeOwned + ::std::default::Default
+                    }
+                })
+                .collect(),
+        };
+        Ok(StructTy {
+            ident: input.ident.clone(),
+            generics: input.generics.clone(),
+            bounds,
+        })
+    }
+
+    /// The struct type, `Name<T>`.
+    fn ty(&self) -> proc_macro2::TokenStream {
+        let ident = &self.ident;
+        let (_, ty_generics, _) = self.generics.split_for_impl();
+        quote! { #ident #ty_generics }
+    }
+
+    /// The struct type for paths in expressions, `<Name<T>>`.
+    fn path(&self) -> proc_macro2::TokenStream {
+        let ty = self.ty();
+        quote! { <#ty> }
+    }
+
+    /// Where clause with the struct's own predicates, the added bounds and `extra`.
+    fn where_clause(&self, extra: Option<proc_macro2::TokenStream>) -> proc_macro2::TokenStream {
+        let own = self
+            .generics
+            .where_clause
+            .iter()
+            .flat_map(|where_clause| where_clause.predicates.iter());
+        let bounds = &self.bounds;
+        quote! { where #( #own, )* #( #bounds, )* #extra }
+    }
+
+    /// `impl<T> Name<T> where ...`, or `impl<T> #trait_path for Name<T> where ...`.
+    fn impl_header(
+        &self,
+        trait_path: Option<proc_macro2::TokenStream>,
+        extra_bound: Option<proc_macro2::TokenStream>,
+    ) -> proc_macro2::TokenStream {
+        let (impl_generics, _, _) = self.generics.split_for_impl();
+        let ty = self.ty();
+        let trait_path = trait_path.map(|trait_path| quote! { #trait_path for });
+        let where_clause = self.where_clause(extra_bound);
+        quote! { impl #impl_generics #trait_path #ty #where_clause }
+    }
+}
+
+#[derive(Default)]
+struct PerstructOptions {
+    key_fn: Option<syn::Path>,
+    crate_path: Option<syn::Path>,
+    serde_json_path: Option<syn::Path>,
+    /// Implementation of `perstruct::PerstructCodec` the values are stored with instead of json.
+    codec: Option<syn::Path>,
+    /// Store strings verbatim and primitives in their `Display` form instead of as json.
+    plain_values: bool,
+    /// Key of the single document holding all fields.
+    single_document: Option<syn::LitStr>,
+    /// Generate setters taking `impl Into<T>` for all fields.
+    into_setters: bool,
+    dirty: DirtyTracking,
+    /// Match keys in `from_map` ignoring ASCII case, preferring exact matches.
+    case_insensitive: bool,
+    /// Prepended to the literal keys and aliases of all fields.
+    prefix: String,
+    /// Generate a change enum and send changes to an observer channel.
+    observe: bool,
+    /// Call the callbacks registered with `perstruct_on_change` with the key of every change.
+    callbacks: bool,
+    /// Case conversion deriving keys from field names without a `key`.
+    rename_all: Option<RenameRule>,
+    /// Prefix of the getter names, none by default.
+    getter_prefix: Option<String>,
+    /// Prefix of the setter names, `set_` by default.
+    setter_prefix: Option<String>,
+    /// Remember the last loaded or saved value of every field for `perstruct_revert_field`.
+    track_loaded: bool,
+    /// Keep the declared visibility of the persisted fields instead of making them private.
+    keep_visibility: bool,
+    /// Keep the entries with unknown keys loaded by `from_map` and write them back with the changes.
+    preserve_unknown: bool,
+    /// Version of the stored format, persisted under the `__version` key.
+    version: Option<u32>,
+    /// Function migrating maps stored with an older `version` before loading them.
+    migrate_fn: Option<syn::Path>,
+    /// Bounds of the generated impls of generic structs, replacing the default serde bounds.
+    bound: Option<Punctuated<syn::WherePredicate, syn::Token![,]>>,
+    /// Implement `serde::Serialize` and `serde::Deserialize` for the whole struct.
+    serde: bool,
+    /// Function called with the struct and the persisted key after every setter.
+    post_set: Option<syn::Path>,
+    /// Function called with the raw entries of the maps loaded by `from_map`.
+    on_load_raw: Option<syn::Path>,
+    /// Function called with the entries returned by `perstruct_get_changes`.
+    on_save_raw: Option<syn::Path>,
+    /// Function checking the struct as a whole, `fn(&Self) -> Vec<perstruct::ValidationIssue>`.
+    validate: Option<syn::Path>,
+    /// The fields with `dynamic_key`, collected by `process_struct`.
+    dynamic_fields: Vec<DynamicField>,
+    /// Implement `Debug` with the declared fields only.
+    debug: bool,
+    /// Generate a view struct borrowing all persisted fields at once.
+    view: bool,
+}
+
+/// Case conversion of `rename_all`, from the `snake_case` field names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RenameRule {
+    Camel,
+    Pascal,
+    Kebab,
+    ScreamingSnake,
+}
+
+impl RenameRule {
+    fn apply(self, name: &str) -> String {
+        match self {
+            RenameRule::Camel => {
+                let pascal = to_pascal_case(name);
+                let mut chars = pascal.chars();
+                chars
+                    .next()
+                    .map(|first| first.to_lowercase().chain(chars).collect())
+                    .unwrap_or_default()
+            }
+            RenameRule::Pascal => to_pascal_case(name),
+            RenameRule::Kebab => name.replace('_', "-"),
+            RenameRule::ScreamingSnake => name.to_uppercase(),
+        }
+    }
+}
+
+/// `snake_case` of a `PascalCase` name.
+fn to_snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (index, c) in name.chars().enumerate() {
+        if c.is_uppercase() && index > 0 {
+            snake.push('_');
+        }
+        snake.extend(c.to_lowercase());
+    }
+    snake
+}
+
+fn to_pascal_case(name: &str) -> String {
+    name.split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+/// Container used for the keys of the dirty fields.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum DirtyTracking {
+    #[default]
+    HashSet,
+    Vec,
+    Bitset,
+}
+
+impl PerstructOptions {
+    /// Initializers of the fields injected besides the dirty fields, for struct literals.
+    fn injected_inits(&self) -> proc_macro2::TokenStream {
+        let observer = self
+            .observe
+            .then(|| quote! { _perstruct_observer: ::std::option::Option::None, });
+        let callbacks = self
+            .callbacks
+            .then(|| quote! { _perstruct_callbacks: ::std::vec::Vec::new(), });
+        let loaded = self
+            .track_loaded
+            .then(|| quote! { _perstruct_loaded: ::std::default::Default::default(), });
+        let unknown = self
+            .preserve_unknown
+            .then(|| quote! { _perstruct_unknown: ::std::default::Default::default(), });
+        let dynamic_saved = (!self.dynamic_fields.is_empty())
+            .then(|| quote! { _perstruct_dynamic_saved: ::std::default::Default::default(), });
+        quote! { #observer #callbacks #loaded #unknown #dynamic_saved }
+    }
+
+    /// With a `version`, the key the version is persisted under.
+    fn version_key(&self) -> Option<PersistedKey> {
+        self.version.map(|_| {
+            PersistedKey::Literal(syn::LitStr::new(
+                &format!("{}__version", self.prefix),
+                proc_macro2::Span::call_site(),
+            ))
+        })
+    }
+
+    /// With a `version`, statements reading the stored version of `map` into `stored_version`,
+    /// 0 when missing. With a `migrate_fn`, `map` is then shadowed by an owned copy that was
+    /// migrated if the stored version is older, and `migrated` tells whether it was.
+    fn load_version(&self) -> Option<proc_macro2::TokenStream> {
+        let version = self.version?;
+        let version_key = self.version_key();
+        let migrate = self.migrate_fn.as_ref().map(|migrate_fn| {
+            quote! {
+                let migrated = stored_version < #version;
+                let mut migrated_map = map
+                    .iter()
+                    .map(|(key, value)| (key.as_ref().to_string(), value.as_ref().to_string()))
+                    .collect::<::std::collections::HashMap<::std::string::String, ::std::string::String>>();
+                if migrated {
+                    #migrate_fn(stored_version, &mut migrated_map);
                 }
+                let map = &migrated_map;
+            }
+        });
+        Some(quote! {
+            let stored_version: u32 = map
+                .iter()
+                .find(|(key, _)| key.as_ref() == #version_key)
+                .and_then(|(_, value)| value.as_ref().trim().parse().ok())
+                .unwrap_or(0);
+            #migrate
+        })
+    }
+
+    /// With a `migrate_fn`, statement marking all fields dirty after a migration, so the migrated
+    /// values are persisted with the new version.
+    fn mark_migrated_dirty(
+        &self,
+        fields: &[PerstructField],
+        dirty_fields: proc_macro2::TokenStream,
+    ) -> Option<proc_macro2::TokenStream> {
+        let all_dirty = self.dirty_all(fields);
+        self.migrate_fn.as_ref().map(|_| {
+            quote! {
+                if migrated {
+                    #dirty_fields = #all_dirty;
+                }
+            }
+        })
+    }
+
+    /// The `stored_version` of a `PerstructLoadResult` built after `load_version`.
+    fn stored_version(&self) -> proc_macro2::TokenStream {
+        match self.version {
+            Some(_) => quote! { ::std::option::Option::Some(stored_version) },
+            None => quote! { ::std::option::Option::None },
+        }
+    }
+
+    /// With `track_loaded`, statement remembering `value` (a `&str`) as the loaded value of `key`.
+    fn record_loaded(
+        &self,
+        target: &proc_macro2::TokenStream,
+        key: &PersistedKey,
+        value: &proc_macro2::TokenStream,
+    ) -> Option<proc_macro2::TokenStream> {
+        self.track_loaded.then(|| {
+            quote! { #target._perstruct_loaded.insert(#key, #value.to_string()); }
+        })
+    }
+
+    /// Whether the keys are mapped at runtime by `generate_mapped_keys_impl`, with `key_fn` or the
+    /// `intern` feature.
+    fn maps_keys(&self) -> bool {
+        self.key_fn.is_some() || cfg!(feature = "intern")
+    }
+
+    /// With `on_load_raw`, statements collecting the entries of `map` sorted by key before they are
+    /// migrated, and calling the hook with them.
+    fn on_load_raw(&self) -> Option<(proc_macro2::TokenStream, proc_macro2::TokenStream)> {
+        self.on_load_raw.as_ref().map(|on_load_raw| {
+            let collect = quote! {
+                let mut raw_entries = map
+                    .iter()
+                    .map(|(key, value)| (key.as_ref().to_string(), value.as_ref().to_string()))
+                    .collect::<::std::vec::Vec<_>>();
+                raw_entries.sort();
+            };
+            (collect, quote! { #on_load_raw(&raw_entries); })
+        })
+    }
+
+    /// Expression telling whether `key`, a reference to a map key, is the key `field_key` (a
+    /// `&&str`) of a field, ignoring the case with `case_insensitive`.
+    fn key_matches(&self) -> proc_macro2::TokenStream {
+        if self.case_insensitive {
+            quote! { field_key.eq_ignore_ascii_case(key.as_ref()) }
+        } else {
+            quote! { *field_key == key.as_ref() }
+        }
+    }
+
+    /// With `post_set`, statement calling the hook after the field with the persisted `key` was
+    /// set through a setter.
+    fn post_set(&self, key: &PersistedKey) -> Option<proc_macro2::TokenStream> {
+        self.post_set.as_ref().map(|post_set| {
+            quote! { #post_set(self, #key); }
+        })
+    }
+
+    /// Type of the callbacks in the `_perstruct_callbacks` field, `Send` and `Sync` so the struct
+    /// stays shareable between threads.
+    fn callback_ty(&self) -> proc_macro2::TokenStream {
+        quote! {
+            ::std::boxed::Box<dyn ::std::ops::FnMut(&'static str) + ::std::marker::Send + ::std::marker::Sync>
+        }
+    }
+
+    /// Type of the `_perstruct_dirty_fields` field.
+    fn dirty_ty(&self) -> proc_macro2::TokenStream {
+        let crate_path = self.crate_path();
+        match self.dirty {
+            DirtyTracking::HashSet => quote! { ::std::collections::HashSet<&'static str> },
+            DirtyTracking::Vec => quote! { #crate_path::__private::DirtyVec },
+            DirtyTracking::Bitset => quote! { #crate_path::__private::DirtyBits },
+        }
+    }
+
+    /// Expression for dirty tracking without any dirty field.
+    fn dirty_none(&self) -> proc_macro2::TokenStream {
+        let crate_path = self.crate_path();
+        match self.dirty {
+            DirtyTracking::HashSet | DirtyTracking::Vec => {
+                quote! { ::std::default::Default::default() }
+            }
+            DirtyTracking::Bitset => quote! {
+                #crate_path::__private::DirtyBits::new(Self::__perstruct_key_slice())
+            },
+        }
+    }
+
+    /// Expression for dirty tracking with all `fields` dirty.
+    fn dirty_all(&self, fields: &[PerstructField]) -> proc_macro2::TokenStream {
+        let crate_path = self.crate_path();
+        let keys = cfg_keys(fields);
+        match self.dirty {
+            DirtyTracking::HashSet => quote! {
+                [#( #keys ),*].into_iter().collect::<::std::collections::HashSet<&'static str>>()
+            },
+            DirtyTracking::Vec => quote! {
+                #crate_path::__private::DirtyVec::all(&[#( #keys ),*])
+            },
+            DirtyTracking::Bitset => quote! {
+                #crate_path::__private::DirtyBits::all(Self::__perstruct_key_slice())
+            },
+        }
+    }
+
+    /// Path to the `perstruct` runtime crate in generated code.
+    fn crate_path(&self) -> proc_macro2::TokenStream {
+        match &self.crate_path {
+            Some(path) => path.to_token_stream(),
+            None => quote! { ::perstruct },
+        }
+    }
+
+    /// With `codec`, the codec as a qualified path to call the `PerstructCodec` functions on.
+    fn codec(&self) -> Option<proc_macro2::TokenStream> {
+        let crate_path = self.crate_path();
+        self.codec
+            .as_ref()
+            .map(|codec| quote! { <#codec as #crate_path::PerstructCodec> })
+    }
+
+    /// Path to the `serde_json` crate in generated code.
+    fn serde_json_path(&self) -> proc_macro2::TokenStream {
+        match &self.serde_json_path {
+            Some(path) => path.to_token_stream(),
+            None => {
+                let crate_path = self.crate_path();
+                quote! { #crate_path::__private::serde_json }
             }
         }
     }
@@ -336,8 +5022,671 @@ fn generate_default_impl(
 #[derive(Debug)]
 struct PerstructField {
     ident: syn::Ident,
-    key: Option<String>,
-    default_fn: Option<String>,
-    default_lit: Option<syn::Lit>,
+    persisted_key: PersistedKey,
+    default_fn: Option<syn::ExprPath>,
+    /// Function returning `Result<T, String>`, see `try_default_expr`.
+    try_default_fn: Option<syn::ExprPath>,
+    /// Name of the getter, overriding `getter_prefix`.
+    getter: Option<syn::Ident>,
+    /// Name of the setter, overriding `setter_prefix`.
+    setter: Option<syn::Ident>,
+    default: Option<syn::Expr>,
+    serialize_with: Option<syn::Path>,
+    deserialize_with: Option<syn::Path>,
+    /// Additional keys accepted by `from_map`, in order of precedence after the persisted key.
+    aliases: Vec<syn::LitStr>,
+    normalize: VecNormalization,
+    /// Name of the store the field is routed to, `None` for the default store.
+    store: Option<String>,
+    /// Generate a setter taking `impl Into<T>`.
+    into_setter: bool,
+    /// The field has no default, so it's required by `try_from_map` and the struct gets no
+    /// `Default` impl.
+    no_default: bool,
+    /// Only the getter is generated, the field is only written by loading.
+    readonly: bool,
+    /// The struct field is a `Lazy<T>`, deserialized on first access instead of when loading.
+    lazy: bool,
+    /// The value is redacted in the `Debug` output and listed by `perstruct_redacted_keys`.
+    secret: bool,
+    /// The key is deleted instead of storing the default value.
+    skip_default: bool,
+    /// A missing key is reported in `missing_required` when loading, set for `no_default` fields.
+    required: bool,
+    /// `update_<field>` only marks the field dirty if the closure changed it.
+    precise_updates: bool,
+    /// Members missing from the stored json object get their value from the field's default.
+    value_default_missing: bool,
+    /// Proxy type the field is converted to and from when it's stored.
+    serialize_as: Option<syn::Type>,
+    /// Values the field is restricted to, empty for unrestricted fields.
+    one_of: Vec<syn::LitStr>,
+    /// Function checking the value, `fn(&T) -> Result<(), String>`.
+    validate: Option<syn::Path>,
+    /// Setters of a `one_of` or `validate` field reject values that are not allowed instead of
+    /// storing the default, with `on_invalid = "reject"` or `validate`.
+    reject_invalid: bool,
+    /// Environment variable overriding the loaded value, and the key of its errors.
+    env: Option<(syn::LitStr, syn::LitStr)>,
+    /// Position of the key among the persisted keys, from `order` or the index of the field among
+    /// the persisted fields.
+    order: usize,
+    /// Whether the position comes from `order`.
+    has_order: bool,
+    /// `#[cfg]` attributes of the field, repeated on everything generated for it.
+    cfg_attrs: Vec<syn::Attribute>,
     ty: syn::Type,
 }
+
+/// A field with `dynamic_key`, a `HashMap<String, T>` persisted as a row per entry, under the key,
+/// a `.` and the escaped map key.
+struct DynamicField {
+    ident: syn::Ident,
+    key: syn::LitStr,
+    /// The key followed by the separator.
+    row_prefix: syn::LitStr,
+    value_ty: syn::Type,
+    cfg_attrs: Vec<syn::Attribute>,
+}
+
+impl DynamicField {
+    fn cfg(&self) -> proc_macro2::TokenStream {
+        let cfg_attrs = &self.cfg_attrs;
+        quote! { #( #cfg_attrs )* }
+    }
+
+    /// Expression serializing the row `value` with the struct's codec.
+    fn encode_expr(
+        &self,
+        options: &PerstructOptions,
+        value: proc_macro2::TokenStream,
+    ) -> proc_macro2::TokenStream {
+        let serde_json = options.serde_json_path();
+        match options.codec() {
+            Some(codec) => quote! { #codec::encode(#value) },
+            None => quote! { #serde_json::to_string(#value).map_err(|e| e.to_string()) },
+        }
+    }
+
+    /// Expression deserializing the row `value`, failing with `(message, line, column)` like
+    /// `PerstructField::deserialize_expr`.
+    fn decode_expr(
+        &self,
+        options: &PerstructOptions,
+        value: proc_macro2::TokenStream,
+    ) -> proc_macro2::TokenStream {
+        let serde_json = options.serde_json_path();
+        let ty = &self.value_ty;
+        match options.codec() {
+            Some(codec) => quote! {
+                #codec::decode::<#ty>(#value).map_err(|message| (message, 0, 0))
+            },
+            None => quote! {
+                #serde_json::from_str::<#ty>(#value).map_err(|e| (e.to_string(), e.line(), e.column()))
+            },
+        }
+    }
+}
+
+/// A `skip`ped field, which is only initialized with `Default::default()`.
+struct SkippedField {
+    ident: syn::Ident,
+    cfg_attrs: Vec<syn::Attribute>,
+}
+
+impl SkippedField {
+    /// Initializer of the field for struct literals.
+    fn default_init(&self) -> proc_macro2::TokenStream {
+        let cfg_attrs = &self.cfg_attrs;
+        let ident = &self.ident;
+        quote! { #( #cfg_attrs )* #ident: ::std::default::Default::default() }
+    }
+}
+
+/// Normalization applied to `Vec` fields when they are loaded or modified.
+#[derive(Debug, Default)]
+struct VecNormalization {
+    dedup: bool,
+    max_len: Option<usize>,
+    truncate_front: bool,
+}
+
+/// The key a field is persisted under, as an expression of type `&'static str`.
+#[derive(Debug, Clone)]
+enum PersistedKey {
+    Literal(syn::LitStr),
+    Runtime(proc_macro2::TokenStream),
+}
+
+impl ToTokens for PersistedKey {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        match self {
+            PersistedKey::Literal(lit) => lit.to_tokens(tokens),
+            PersistedKey::Runtime(expr) => expr.to_tokens(tokens),
+        }
+    }
+}
+
+impl PerstructField {
+    /// Name of the field's getter: the `getter` name, or the field name with the getter prefix.
+    fn getter_ident(&self, options: &PerstructOptions) -> syn::Ident {
+        self.getter.clone().unwrap_or_else(|| {
+            let prefix = options.getter_prefix.as_deref().unwrap_or("");
+            method_ident(prefix, &self.ident, "")
+        })
+    }
+
+    /// The field's `#[cfg]` attributes, to put on the items, arms, statements and initializers
+    /// generated for it so they are removed together with the field.
+    fn cfg(&self) -> proc_macro2::TokenStream {
+        let cfg_attrs = &self.cfg_attrs;
+        quote! { #( #cfg_attrs )* }
+    }
+
+    /// The predicate of the field's `#[cfg]` attributes as written, combined with `all(...)` when
+    /// there are several, for `Field::cfg`.
+    fn cfg_predicate(&self) -> Option<String> {
+        let predicates = self
+            .cfg_attrs
+            .iter()
+            .filter_map(|attr| match &attr.meta {
+                syn::Meta::List(list) => Some(list.tokens.to_string()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        match predicates.as_slice() {
+            [] => None,
+            [predicate] => Some(predicate.clone()),
+            predicates => Some(format!("all({})", predicates.join(", "))),
+        }
+    }
+
+    /// Name of the fallible setter of a `one_of` field, the setter prefixed with `try_`.
+    fn try_setter_ident(&self, options: &PerstructOptions) -> syn::Ident {
+        let setter = self.setter_ident(options);
+        method_ident("try_", &setter, "")
+    }
+
+    /// Name of the field's method on the updater: the field name, or the setter name for a field
+    /// named like the updater's `commit`.
+    fn updater_ident(&self, options: &PerstructOptions) -> syn::Ident {
+        if self.ident == "commit" {
+            self.setter_ident(options)
+        } else {
+            self.ident.clone()
+        }
+    }
+
+    /// Name of the field's setter: the `setter` name, or the field name with the setter prefix.
+    fn setter_ident(&self, options: &PerstructOptions) -> syn::Ident {
+        self.setter.clone().unwrap_or_else(|| {
+            let prefix = options.setter_prefix.as_deref().unwrap_or("set_");
+            method_ident(prefix, &self.ident, "")
+        })
+    }
+
+    /// The field of `receiver` as a place of type `T`, dereferencing the `Lazy` of a `lazy` field.
+    fn place(&self, receiver: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        let ident = &self.ident;
+        if self.lazy {
+            quote! { (*#receiver.#ident) }
+        } else {
+            quote! { #receiver.#ident }
+        }
+    }
+
+    /// `&T` of the field of `receiver`.
+    fn reference(&self, receiver: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        let place = self.place(receiver);
+        quote! { &#place }
+    }
+
+    /// `&mut T` of the field of `receiver`.
+    fn reference_mut(&self, receiver: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        let place = self.place(receiver);
+        quote! { &mut #place }
+    }
+
+    /// `value` (a `T`) as the value of the struct field, wrapped in a `Lazy` for `lazy` fields so
+    /// assigning it doesn't deserialize the stored string first.
+    fn wrap_expr(
+        &self,
+        options: &PerstructOptions,
+        value: proc_macro2::TokenStream,
+    ) -> proc_macro2::TokenStream {
+        if self.lazy {
+            let crate_path = options.crate_path();
+            quote! { #crate_path::Lazy::new(#value) }
+        } else {
+            value
+        }
+    }
+
+    /// Expression for the default value of the field.
+    fn default_expr(&self) -> proc_macro2::TokenStream {
+        if let Some(default_fn) = &self.default_fn {
+            quote::quote! { #default_fn() }
+        } else if let Some(try_default_fn) = &self.try_default_fn {
+            quote::quote! { #try_default_fn().unwrap_or_default() }
+        } else if let Some(
+            default @ syn::Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Str(_),
+                ..
+            }),
+        ) = &self.default
+        {
+            // Also converts string literals for `String` fields
+            let ty = &self.ty;
+            quote::quote! { <#ty as ::std::convert::From<&'static str>>::from(#default) }
+        } else if let Some(default) = &self.default {
+            quote::quote! { #default }
+        } else {
+            quote::quote! { ::std::default::Default::default() }
+        }
+    }
+
+    /// Like `default_expr`, but a failing `try_default_fn` pushes `(key, message)` to `errors`
+    /// before falling back to `Default::default()`.
+    fn try_default_expr(&self, errors: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        let Some(try_default_fn) = &self.try_default_fn else {
+            return self.default_expr();
+        };
+        let key = &self.persisted_key;
+        quote::quote! {
+            match #try_default_fn() {
+                ::std::result::Result::Ok(value) => value,
+                ::std::result::Result::Err(message) => {
+                    #errors.push((#key, message));
+                    ::std::default::Default::default()
+                }
+            }
+        }
+    }
+
+    /// Expression normalizing `value` (a `&mut Vec<_>`), evaluating to whether it changed.
+    fn normalize_expr(
+        &self,
+        options: &PerstructOptions,
+        value: proc_macro2::TokenStream,
+    ) -> Option<proc_macro2::TokenStream> {
+        let VecNormalization {
+            dedup,
+            max_len,
+            truncate_front,
+        } = &self.normalize;
+        if !dedup && max_len.is_none() {
+            return None;
+        }
+        let crate_path = options.crate_path();
+        let max_len = match max_len {
+            Some(max_len) => quote! { ::std::option::Option::Some(#max_len) },
+            None => quote! { ::std::option::Option::None },
+        };
+        Some(quote! {
+            #crate_path::__private::normalize_vec(#value, #dedup, #max_len, #truncate_front)
+        })
+    }
+
+    /// Whether the field is stored in its json representation, or encoded with the `codec`.
+    fn is_json_encoded(&self, options: &PerstructOptions) -> bool {
+        self.serialize_as.is_some()
+            || (self.serialize_with.is_none()
+                && !(options.plain_values
+                    && (is_string_type(&self.ty) || is_copy_primitive_type(&self.ty))))
+    }
+
+    /// With `serialize_as`, expression converting `value` (a `&T`) into the proxy type.
+    fn proxy_expr(&self, value: &proc_macro2::TokenStream) -> Option<proc_macro2::TokenStream> {
+        let ty = &self.ty;
+        self.serialize_as.as_ref().map(|proxy| {
+            quote! { <#proxy as ::std::convert::From<&#ty>>::from(#value) }
+        })
+    }
+
+    /// With `value_default_missing`, expression deserializing `value` (a `&str`) into a
+    /// `Result<(T, bool), (String, usize, usize)>` after filling in the members missing from the
+    /// stored json object from the field's default. The bool tells whether any member was missing.
+    fn deserialize_completed_expr(
+        &self,
+        options: &PerstructOptions,
+        value: &proc_macro2::TokenStream,
+    ) -> Option<proc_macro2::TokenStream> {
+        if !self.value_default_missing || !self.is_json_encoded(options) {
+            return None;
+        }
+        let serde_json = options.serde_json_path();
+        let ty = &self.ty;
+        let default = self.default_expr();
+        Some(quote! {
+            (|| -> ::std::result::Result<(#ty, bool), (::std::string::String, usize, usize)> {
+                let mut stored = #serde_json::from_str::<#serde_json::Value>(#value)
+                    .map_err(|e| (e.to_string(), e.line(), e.column()))?;
+                let mut completed = false;
+                if let #serde_json::Value::Object(stored) = &mut stored {
+                    let default: #ty = #default;
+                    if let ::std::result::Result::Ok(#serde_json::Value::Object(default)) = #serde_json::to_value(&default) {
+                        for (member, value) in default {
+                            if !stored.contains_key(&member) {
+                                stored.insert(member, value);
+                                completed = true;
+                            }
+                        }
+                    }
+                }
+                #serde_json::from_value::<#ty>(stored)
+                    .map(|value| (value, completed))
+                    .map_err(|e| (e.to_string(), 0, 0))
+            })()
+        })
+    }
+
+    /// Expression serializing `value` (a `&T`) into a `Result<String, String>`.
+    fn serialize_expr(
+        &self,
+        options: &PerstructOptions,
+        value: proc_macro2::TokenStream,
+    ) -> proc_macro2::TokenStream {
+        let serde_json = options.serde_json_path();
+        let codec = options.codec();
+        if let Some(proxy) = self.proxy_expr(&value) {
+            return match codec {
+                Some(codec) => quote! { #codec::encode(&#proxy) },
+                None => quote! { #serde_json::to_string(&#proxy).map_err(|e| e.to_string()) },
+            };
+        }
+        match &self.serialize_with {
+            Some(serialize_with) => quote! { #serialize_with(#value) },
+            None if !self.is_json_encoded(options) => {
+                quote! { ::std::result::Result::Ok::<::std::string::String, ::std::string::String>(::std::string::ToString::to_string(#value)) }
+            }
+            None => match codec {
+                Some(codec) => quote! { #codec::encode(#value) },
+                None => quote! { #serde_json::to_string(#value).map_err(|e| e.to_string()) },
+            },
+        }
+    }
+
+    /// Expression serializing `value` (a `&T`) into `writer` (a `&mut dyn std::io::Write`), giving
+    /// a `std::io::Result<()>`.
+    fn write_expr(
+        &self,
+        options: &PerstructOptions,
+        value: proc_macro2::TokenStream,
+    ) -> proc_macro2::TokenStream {
+        let serde_json = options.serde_json_path();
+        if options.codec.is_some() {
+            // Codecs only encode into strings
+            let serialize = self.serialize_expr(options, value);
+            quote! {
+                let value = #serialize.map_err(::std::io::Error::other)?;
+                writer.write_all(value.as_bytes())
+            }
+        } else if let Some(proxy) = self.proxy_expr(&value) {
+            quote! { #serde_json::to_writer(writer, &#proxy).map_err(::std::io::Error::from) }
+        } else if self.is_json_encoded(options) {
+            quote! { #serde_json::to_writer(writer, #value).map_err(::std::io::Error::from) }
+        } else {
+            let serialize = self.serialize_expr(options, value);
+            quote! {
+                let value = #serialize.map_err(::std::io::Error::other)?;
+                writer.write_all(value.as_bytes())
+            }
+        }
+    }
+
+    /// With `one_of`, the allowed values as a `&'static [&'static str]`.
+    fn allowed_values(&self) -> Option<proc_macro2::TokenStream> {
+        let values = &self.one_of;
+        (!values.is_empty()).then(|| quote! { &[#( #values ),*] })
+    }
+
+    /// Whether values are checked with `one_of` or `validate`, giving the field a `try_` setter.
+    fn is_checked(&self) -> bool {
+        !self.one_of.is_empty() || self.validate.is_some()
+    }
+
+    /// With `one_of` or `validate`, expression checking `value` (a `T` or `&T`), giving a
+    /// `Result` with the value or the message of why it's not allowed.
+    fn check_allowed_expr(
+        &self,
+        options: &PerstructOptions,
+        value: proc_macro2::TokenStream,
+    ) -> Option<proc_macro2::TokenStream> {
+        let crate_path = options.crate_path();
+        let ty = &self.ty;
+        let check_valid = |value| {
+            self.validate.as_ref().map(|validate| {
+                quote! { #crate_path::__private::check_valid::<_, #ty>(#value, #validate) }
+            })
+        };
+        match self.allowed_values() {
+            Some(allowed) => {
+                let check = quote! { #crate_path::__private::check_one_of(#value, #allowed) };
+                Some(match check_valid(quote! { value }) {
+                    Some(check_valid) => quote! { #check.and_then(|value| #check_valid) },
+                    None => check,
+                })
+            }
+            None => check_valid(value),
+        }
+    }
+
+    /// Expression deserializing `value` (a `&str`) into a `Result<T, (String, usize, usize)>`,
+    /// where the error has the message, line and column of the error (0 when unknown). Values a
+    /// `one_of` or `validate` field doesn't allow are errors.
+    fn deserialize_expr(
+        &self,
+        options: &PerstructOptions,
+        value: proc_macro2::TokenStream,
+    ) -> proc_macro2::TokenStream {
+        let deserialize = self.deserialize_unchecked_expr(options, value);
+        match self.check_allowed_expr(options, quote! { value }) {
+            Some(check) => quote! {
+                #deserialize.and_then(|value| #check.map_err(|message| (message, 0, 0)))
+            },
+            None => deserialize,
+        }
+    }
+
+    /// `deserialize_expr` without the `one_of` and `validate` checks.
+    fn deserialize_unchecked_expr(
+        &self,
+        options: &PerstructOptions,
+        value: proc_macro2::TokenStream,
+    ) -> proc_macro2::TokenStream {
+        if let Some(deserialize) = self.deserialize_completed_expr(options, &value) {
+            return quote! { #deserialize.map(|(value, _)| value) };
+        }
+        let serde_json = options.serde_json_path();
+        let codec = options.codec();
+        let ty = &self.ty;
+        let decode = |ty: &syn::Type| match &codec {
+            Some(codec) => quote! {
+                #codec::decode::<#ty>(#value).map_err(|message| (message, 0, 0))
+            },
+            None => quote! {
+                #serde_json::from_str::<#ty>(#value).map_err(|e| (e.to_string(), e.line(), e.column()))
+            },
+        };
+        if let Some(proxy) = &self.serialize_as {
+            let decode = decode(proxy);
+            return quote! {
+                #decode
+                    .and_then(|proxy| {
+                        <#proxy as ::std::convert::TryInto<#ty>>::try_into(proxy)
+                            .map_err(|e| (e.to_string(), 0, 0))
+                    })
+            };
+        }
+        match &self.deserialize_with {
+            Some(deserialize_with) => quote! {
+                #deserialize_with(#value).map_err(|message: ::std::string::String| (message, 0, 0))
+            },
+            None if options.plain_values && is_string_type(ty) => quote! {
+                ::std::result::Result::Ok::<#ty, (::std::string::String, usize, usize)>(::std::string::ToString::to_string(#value))
+            },
+            None if options.plain_values && is_copy_primitive_type(ty) => quote! {
+                str::trim(#value).parse::<#ty>().map_err(|e| (e.to_string(), 0, 0))
+            },
+            None => decode(ty),
+        }
+    }
+}
+
+/// Integer, float, bool and char types, which are `Copy` and returned by value from getters.
+const COPY_PRIMITIVE_TYPES: &[&str] = &[
+    "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize", "f32",
+    "f64", "bool", "char",
+];
+
+/// Whether `ty` is one of the `COPY_PRIMITIVE_TYPES`.
+fn is_copy_primitive_type(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(syn::TypePath { qself: None, path }) if path.segments.len() == 1 => {
+            COPY_PRIMITIVE_TYPES.contains(&path.segments[0].ident.to_string().as_str())
+        }
+        _ => false,
+    }
+}
+
+/// Return type and expression of a getter returning `field` of type `ty`. Copy primitives are
+/// returned by value and other types by reference. `Option`s are returned as `Option<T>` for copy
+/// primitives, as `Option<&str>`, `Option<&Path>` and `Option<&OsStr>` for owned strings and
+/// paths, and as `Option<&T>` otherwise.
+fn getter_return(
+    ty: &syn::Type,
+    field: proc_macro2::TokenStream,
+) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    if is_copy_primitive_type(ty) {
+        return (quote! { #ty }, field);
+    }
+    let Some(inner) = option_inner_type(ty) else {
+        return (quote! { &#ty }, quote! { &#field });
+    };
+    if is_copy_primitive_type(inner) {
+        return (quote! { ::std::option::Option<#inner> }, field);
+    }
+    let borrowed = match inner {
+        syn::Type::Path(syn::TypePath { qself: None, path }) => path
+            .segments
+            .last()
+            .filter(|segment| segment.arguments.is_none())
+            .and_then(|segment| match segment.ident.to_string().as_str() {
+                "String" => Some(quote! { str }),
+                "PathBuf" => Some(quote! { ::std::path::Path }),
+                "OsString" => Some(quote! { ::std::ffi::OsStr }),
+                _ => None,
+            }),
+        _ => None,
+    };
+    match borrowed {
+        Some(borrowed) => (
+            quote! { ::std::option::Option<&#borrowed> },
+            quote! { #field.as_deref() },
+        ),
+        None => (
+            quote! { ::std::option::Option<&#inner> },
+            quote! { #field.as_ref() },
+        ),
+    }
+}
+
+/// Whether `ty` is `Option<...>`.
+fn is_option_type(ty: &syn::Type) -> bool {
+    option_inner_type(ty).is_some()
+}
+
+/// The `T` of `ty` if it's `Option<T>`.
+fn option_inner_type(ty: &syn::Type) -> Option<&syn::Type> {
+    let syn::Type::Path(syn::TypePath { qself: None, path }) = ty else {
+        return None;
+    };
+    let segment = path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    let syn::PathArguments::AngleBracketed(arguments) = &segment.arguments else {
+        return None;
+    };
+    match arguments.args.first()? {
+        syn::GenericArgument::Type(inner) => Some(inner),
+        _ => None,
+    }
+}
+
+/// `T` of a `HashMap<String, T>`.
+fn string_map_value_type(ty: &syn::Type) -> Option<&syn::Type> {
+    let syn::Type::Path(syn::TypePath { qself: None, path }) = ty else {
+        return None;
+    };
+    let segment = path.segments.last()?;
+    if segment.ident != "HashMap" {
+        return None;
+    }
+    let syn::PathArguments::AngleBracketed(arguments) = &segment.arguments else {
+        return None;
+    };
+    let mut args = arguments.args.iter();
+    match (args.next()?, args.next()?, args.next()) {
+        (syn::GenericArgument::Type(key), syn::GenericArgument::Type(value), None)
+            if is_string_type(key) =>
+        {
+            Some(value)
+        }
+        _ => None,
+    }
+}
+
+/// Whether `ty` is `String`.
+fn is_string_type(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(syn::TypePath { qself: None, path }) => path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "String" && segment.arguments.is_none()),
+        _ => false,
+    }
+}
+
+fn parse_lit_str(lit: syn::Lit) -> syn::Result<syn::LitStr> {
+    match lit {
+        syn::Lit::Str(s) => Ok(s),
+        lit => Err(syn::Error::new_spanned(lit, "Expected string literal")),
+    }
+}
+
+/// The name of a method generated for the field `ident`, without the `r#` of a raw identifier
+/// between `prefix` and `suffix`, and raw itself if it's a keyword like the name of `r#type`.
+fn method_ident(prefix: &str, ident: &syn::Ident, suffix: &str) -> syn::Ident {
+    let name = format!("{prefix}{}{suffix}", syn::ext::IdentExt::unraw(ident));
+    if syn::parse_str::<syn::Ident>(&name).is_ok() {
+        syn::Ident::new(&name, ident.span())
+    } else {
+        syn::Ident::new_raw(&name, ident.span())
+    }
+}
+
+/// Parses the path of a function given as a string literal, like `default_fn`.
+fn parse_fn_path(lit: syn::Lit) -> syn::Result<syn::ExprPath> {
+    let lit = parse_lit_str(lit)?;
+    lit.parse().map_err(|_| {
+        syn::Error::new_spanned(
+            &lit,
+            "Perstruct: expected the path of a function, like \"themes::load\"",
+        )
+    })
+}
+
+/// Parses a `getter_prefix` or `setter_prefix`, which has to make identifiers when followed by
+/// the field names.
+fn parse_method_prefix(lit: syn::Lit) -> syn::Result<String> {
+    let lit = parse_lit_str(lit)?;
+    let prefix = lit.value();
+    if prefix.contains('#') || syn::parse_str::<syn::Ident>(&format!("{prefix}field")).is_err() {
+        return Err(syn::Error::new_spanned(
+            lit,
+            "Perstruct: the prefix must be the start of an identifier, like \"set_\"",
+        ));
+    }
+    Ok(prefix)
+}
@@ -0,0 +1,78 @@
+//! Compares `perstruct_get_changes` with `perstruct_write_changes` into a reused buffer, for a
+//! struct with a multi-MB field. Run with `cargo bench -p perstruct --bench write_changes`.
+
+use std::hint::black_box;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use perstruct::{perstruct, ChangeSink};
+
+#[perstruct]
+struct Snapshot {
+    name: String,
+    samples: Vec<u32>,
+}
+
+/// A backend's write batch, whose buffer is reused between saves.
+#[derive(Default)]
+struct Batch {
+    buffer: Vec<u8>,
+}
+
+impl ChangeSink for Batch {
+    fn entry(
+        &mut self,
+        key: &'static str,
+        value_writer: &mut dyn FnMut(&mut dyn Write) -> std::io::Result<()>,
+    ) -> std::io::Result<()> {
+        self.buffer.extend_from_slice(key.as_bytes());
+        value_writer(&mut self.buffer)
+    }
+}
+
+const ITERATIONS: u32 = 20;
+
+fn measure(name: &str, mut run: impl FnMut()) -> Duration {
+    // Warm up, so the batch buffer has its capacity
+    run();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        run();
+    }
+    let elapsed = start.elapsed() / ITERATIONS;
+    println!("{name:<40} {elapsed:>12?} per save");
+    elapsed
+}
+
+fn main() {
+    let mut snapshot = Snapshot::default();
+    snapshot.set_name("capture".to_string());
+    // About 10 MB of json
+    snapshot.set_samples((0..1_000_000).map(|i| i * 7919).collect());
+    let size = snapshot
+        .perstruct_get_changes()
+        .unwrap()
+        .iter()
+        .map(|(_, value)| value.len())
+        .sum::<usize>();
+    println!("serializing {:.1} MB of changes", size as f64 / 1e6);
+
+    let mut batch = Batch::default();
+    let get_changes = measure("get_changes, copied into the batch", || {
+        batch.buffer.clear();
+        for (key, value) in snapshot.perstruct_get_changes().unwrap() {
+            batch.buffer.extend_from_slice(key.as_bytes());
+            batch.buffer.extend_from_slice(value.as_bytes());
+        }
+        black_box(&batch.buffer);
+    });
+    let write_changes = measure("write_changes, into the batch", || {
+        batch.buffer.clear();
+        snapshot.perstruct_write_changes(&mut batch).unwrap();
+        black_box(&batch.buffer);
+    });
+    println!(
+        "write_changes takes {:.0}% of the time of get_changes",
+        write_changes.as_secs_f64() / get_changes.as_secs_f64() * 100.0
+    );
+}
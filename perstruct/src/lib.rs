@@ -2,13 +2,1093 @@
 
 #[doc(hidden)]
 pub use ::perstruct_proc_macros::perstruct;
+#[doc(hidden)]
+pub use ::perstruct_proc_macros::Perstruct;
+
+/// Keys of the dirty fields, the type of the `_perstruct_dirty_fields` field a struct deriving
+/// `Perstruct` has to declare.
+pub type DirtyFields = std::collections::HashSet<&'static str>;
+
+/// Name of the store of fields without a `#[perstruct(store = "...")]` attribute.
+pub const DEFAULT_STORE: &str = "default";
+
+/// Maximum number of keys listed in the `unknown_fields` of a [`PerstructLoadResult`] or
+/// [`PerstructLoadError`]. Further unknown keys are only counted in `unknown_overflow`, so a map
+/// full of unrelated entries doesn't allocate a `String` for each of them.
+pub const MAX_UNKNOWN_FIELDS: usize = 1000;
 
 /// Result of the method `from_map` of a struct generated by the `perstruct` macro.
-/// It contains the deserialized struct, a list of deserialization errors and a list of unknown fields.
-/// The deserialization errors are a list of tuples containing the name of the field and the error message.
-/// The unknown fields are a list of keys in the map that did not match any field.
+/// It contains the deserialized struct and what was noticed while loading it, see the accessors.
+/// It's `non_exhaustive` so more can be reported without breaking code that reads it: read it
+/// through the accessors or destructure it with `..`. Code loading structs by other means builds it
+/// with [`PerstructLoadResult::new`] and the `with_` methods.
+#[non_exhaustive]
 pub struct PerstructLoadResult<T> {
     pub value: T,
-    pub deserialization_errors: Vec<(&'static str, String)>,
+    pub deserialization_errors: Vec<PerstructFieldError>,
     pub unknown_fields: Vec<String>,
+    pub unknown_overflow: usize,
+    pub shadowed_aliases: Vec<(&'static str, String)>,
+    pub used_aliases: Vec<(&'static str, String)>,
+    pub env_overrides: Vec<(&'static str, &'static str)>,
+    pub stored_version: Option<u32>,
+    pub validation_issues: Vec<ValidationIssue>,
+    pub missing_required: Vec<&'static str>,
+}
+
+impl<T> PerstructLoadResult<T> {
+    /// A result for `value` with nothing to report.
+    pub fn new(value: T) -> Self {
+        PerstructLoadResult {
+            value,
+            deserialization_errors: vec![],
+            unknown_fields: vec![],
+            unknown_overflow: 0,
+            shadowed_aliases: vec![],
+            used_aliases: vec![],
+            env_overrides: vec![],
+            stored_version: None,
+            validation_issues: vec![],
+            missing_required: vec![],
+        }
+    }
+
+    pub fn with_deserialization_errors(mut self, errors: Vec<PerstructFieldError>) -> Self {
+        self.deserialization_errors = errors;
+        self
+    }
+
+    pub fn with_unknown_fields(mut self, keys: Vec<String>, overflow: usize) -> Self {
+        self.unknown_fields = keys;
+        self.unknown_overflow = overflow;
+        self
+    }
+
+    pub fn with_shadowed_aliases(mut self, aliases: Vec<(&'static str, String)>) -> Self {
+        self.shadowed_aliases = aliases;
+        self
+    }
+
+    pub fn with_used_aliases(mut self, aliases: Vec<(&'static str, String)>) -> Self {
+        self.used_aliases = aliases;
+        self
+    }
+
+    pub fn with_env_overrides(mut self, overrides: Vec<(&'static str, &'static str)>) -> Self {
+        self.env_overrides = overrides;
+        self
+    }
+
+    pub fn with_stored_version(mut self, version: Option<u32>) -> Self {
+        self.stored_version = version;
+        self
+    }
+
+    pub fn with_validation_issues(mut self, issues: Vec<ValidationIssue>) -> Self {
+        self.validation_issues = issues;
+        self
+    }
+
+    pub fn with_missing_required(mut self, keys: Vec<&'static str>) -> Self {
+        self.missing_required = keys;
+        self
+    }
+
+    /// The loaded struct.
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    pub fn value_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+
+    pub fn into_value(self) -> T {
+        self.value
+    }
+
+    /// Errors of the fields that could not be deserialized, which got their default.
+    pub fn deserialization_errors(&self) -> &[PerstructFieldError] {
+        &self.deserialization_errors
+    }
+
+    /// Keys in the map that did not match any field, at most [`MAX_UNKNOWN_FIELDS`].
+    pub fn unknown_fields(&self) -> &[String] {
+        &self.unknown_fields
+    }
+
+    /// Number of unknown keys beyond those in `unknown_fields`.
+    pub fn unknown_overflow(&self) -> usize {
+        self.unknown_overflow
+    }
+
+    /// The key of a field and an alias of that field that was present in the map but ignored
+    /// because a key with higher precedence was also present.
+    pub fn shadowed_aliases(&self) -> &[(&'static str, String)] {
+        &self.shadowed_aliases
+    }
+
+    /// The key of a field and the alias its value was loaded from. These fields stay dirty, so the
+    /// next save writes them under their key.
+    pub fn used_aliases(&self) -> &[(&'static str, String)] {
+        &self.used_aliases
+    }
+
+    /// The key of a field and the environment variable its value was loaded from instead of the
+    /// map. These fields are not dirty.
+    pub fn env_overrides(&self) -> &[(&'static str, &'static str)] {
+        &self.env_overrides
+    }
+
+    /// The `__version` found in the map for structs with a `version`, 0 when missing, and `None`
+    /// for other structs.
+    pub fn stored_version(&self) -> Option<u32> {
+        self.stored_version
+    }
+
+    /// Issues found by the struct's `validate` function after loading.
+    pub fn validation_issues(&self) -> &[ValidationIssue] {
+        &self.validation_issues
+    }
+
+    /// Keys of the `required` fields missing from the map, which got their default.
+    /// [`PerstructLoadResult::into_result`] turns them into an error.
+    pub fn missing_required(&self) -> &[&'static str] {
+        &self.missing_required
+    }
+
+    /// The loaded value, or an error listing the missing `required` fields together with the
+    /// unknown keys and deserialization errors, for callers that must not proceed with defaults.
+    pub fn into_result(self) -> Result<T, PerstructLoadError> {
+        if self.missing_required.is_empty() {
+            return Ok(self.value);
+        }
+        Err(PerstructLoadError {
+            missing_fields: self.missing_required,
+            unknown_fields: self.unknown_fields,
+            unknown_overflow: self.unknown_overflow,
+            deserialization_errors: self.deserialization_errors,
+        })
+    }
+}
+
+/// Issue found by the `validate` function of a struct, involving the values of several fields, like
+/// a minimum above a maximum. `keys` are the persisted keys of the related fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationIssue {
+    pub keys: Vec<&'static str>,
+    pub message: String,
+}
+
+impl ValidationIssue {
+    pub fn new(keys: impl IntoIterator<Item = &'static str>, message: impl Into<String>) -> Self {
+        ValidationIssue {
+            keys: keys.into_iter().collect(),
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.keys.join(", "), self.message)
+    }
+}
+
+impl std::error::Error for ValidationIssue {}
+
+/// Error deserializing the stored value of a field.
+/// The line and column point into the stored value, and are 0 when the location is unknown,
+/// for example when the error comes from a custom `deserialize_with` function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PerstructFieldError {
+    pub key: &'static str,
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl std::fmt::Display for PerstructFieldError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.key, self.message)
+    }
+}
+
+impl std::error::Error for PerstructFieldError {}
+
+/// Selects the changes counted by the method `perstruct_pending_for` of a struct generated by the
+/// `perstruct` macro.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveScope<'a> {
+    /// The changes of `perstruct_get_changes`.
+    All,
+    /// The changes of `perstruct_get_changes_for_store` for the given store.
+    Store(&'a str),
+}
+
+/// Changes being persisted by a save that completes later, such as a slow request to a remote API.
+/// Created by the method `perstruct_begin_save` of a struct generated by the `perstruct` macro, or
+/// from the output of `perstruct_get_changes` or `perstruct_get_changes_for_store`, and passed to
+/// `perstruct_complete_save` when the save succeeded.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SaveSession {
+    changes: Vec<(String, String)>,
+}
+
+impl SaveSession {
+    pub fn new<K: Into<String>>(changes: impl IntoIterator<Item = (K, String)>) -> Self {
+        SaveSession {
+            changes: changes
+                .into_iter()
+                .map(|(key, value)| (key.into(), value))
+                .collect(),
+        }
+    }
+
+    /// The keys and serialized values to persist.
+    pub fn changes(&self) -> &[(String, String)] {
+        &self.changes
+    }
+}
+
+/// Format the values of fields are stored in, selected with `#[perstruct(codec = "MyCodec")]` on the
+/// struct. Structs without a `codec` store json, like with `JsonCodec`.
+pub trait PerstructCodec {
+    /// Serializes a field value.
+    fn encode<T: serde::Serialize>(value: &T) -> Result<String, String>;
+    /// Deserializes a stored field value.
+    fn decode<T: serde::de::DeserializeOwned>(value: &str) -> Result<T, String>;
+}
+
+/// Codec storing values as json with `serde_json`.
+#[cfg(feature = "json")]
+pub struct JsonCodec;
+
+#[cfg(feature = "json")]
+impl PerstructCodec for JsonCodec {
+    fn encode<T: serde::Serialize>(value: &T) -> Result<String, String> {
+        serde_json::to_string(value).map_err(|e| e.to_string())
+    }
+
+    fn decode<T: serde::de::DeserializeOwned>(value: &str) -> Result<T, String> {
+        serde_json::from_str(value).map_err(|e| e.to_string())
+    }
+}
+
+/// Outcome of the method `perstruct_feed` of a struct generated by the `perstruct` macro.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FeedOutcome {
+    /// The value was loaded into the field with the key, which is no longer dirty.
+    Applied,
+    /// The value could not be deserialized, the field is unchanged.
+    DeserError(String),
+    /// No field has the key.
+    Unknown,
+}
+
+/// Result of the method `perstruct_diff_map` of a struct generated by the `perstruct` macro.
+/// The inserts are the keys missing from the store, the updates are the keys whose stored value
+/// differs from the current value, both with the current serialized value. The deletes are the
+/// keys in the store that did not match any field, in sorted order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PerstructMapDiff {
+    pub inserts: Vec<(&'static str, String)>,
+    pub updates: Vec<(&'static str, String)>,
+    pub deletes: Vec<String>,
+}
+
+/// Entry of the method `perstruct_effective_values` of a struct generated by the `perstruct`
+/// macro, with the current and default value of a field in their serialized form.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EffectiveValue {
+    pub key: &'static str,
+    pub current: String,
+    pub default: String,
+    pub is_dirty: bool,
+    pub differs_from_default: bool,
+}
+
+/// Error of the methods `from_map_strict` and `try_from_map` of a struct generated by the
+/// `perstruct` macro. It lists every key in the map that did not match any field, every field that
+/// could not be deserialized and, for `try_from_map`, every missing `no_default` field, or for
+/// [`PerstructLoadResult::into_result`], every missing `required` field. Like in
+/// [`PerstructLoadResult`], unknown keys beyond [`MAX_UNKNOWN_FIELDS`] are only counted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PerstructLoadError {
+    pub missing_fields: Vec<&'static str>,
+    pub unknown_fields: Vec<String>,
+    pub unknown_overflow: usize,
+    pub deserialization_errors: Vec<PerstructFieldError>,
+}
+
+impl std::fmt::Display for PerstructLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failed to load settings")?;
+        if !self.missing_fields.is_empty() {
+            write!(f, "; missing keys: {}", self.missing_fields.join(", "))?;
+        }
+        if !self.unknown_fields.is_empty() {
+            write!(f, "; unknown keys: {}", self.unknown_fields.join(", "))?;
+            if self.unknown_overflow > 0 {
+                write!(f, " and {} more", self.unknown_overflow)?;
+            }
+        }
+        for error in &self.deserialization_errors {
+            write!(f, "; {error}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for PerstructLoadError {}
+
+/// Report of the method `perstruct_import` of a struct generated by the `perstruct` macro.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    /// Keys of the fields set from the export, which are dirty.
+    pub applied: Vec<&'static str>,
+    /// Keys in the export that did not match any field.
+    pub unknown: Vec<String>,
+    /// Fields whose exported value could not be deserialized, which are unchanged.
+    pub invalid: Vec<PerstructFieldError>,
+    /// The version the export was made with, for structs with a `version`.
+    pub exported_version: Option<u32>,
+    /// When the export was made, in seconds since the Unix epoch.
+    pub exported_at: u64,
+}
+
+/// Error of the method `perstruct_import` of a struct generated by the `perstruct` macro. Nothing
+/// is imported when it fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportError {
+    /// The input is not an export.
+    Malformed(String),
+    /// The export was made from another struct.
+    SchemaMismatch {
+        expected: &'static str,
+        found: String,
+    },
+    /// The export was made with a newer `version` of the struct.
+    UnsupportedVersion { supported: u32, found: u32 },
+}
+
+impl std::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImportError::Malformed(message) => write!(f, "malformed export: {message}"),
+            ImportError::SchemaMismatch { expected, found } => {
+                write!(f, "export of {found} can't be imported into {expected}")
+            }
+            ImportError::UnsupportedVersion { supported, found } => {
+                write!(f, "export version {found} is newer than {supported}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+/// Report of the method `from_map_recovering` of a struct generated by the `perstruct` macro,
+/// grouping the keys by what happened to them. Keys of fields are in the order of `keys()`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecoveryReport {
+    /// Fields loaded from the map.
+    pub loaded: Vec<&'static str>,
+    /// Fields missing from the map, which got their default.
+    pub defaulted_missing: Vec<&'static str>,
+    /// Fields whose stored value could not be deserialized, which got their default.
+    pub defaulted_corrupt: Vec<CorruptEntry>,
+    /// Keys in the map that did not match any field.
+    pub unknown: Vec<String>,
+}
+
+/// A field of a [`RecoveryReport`] whose stored value could not be deserialized.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorruptEntry {
+    pub key: &'static str,
+    pub error: String,
+    /// The start of the stored value, with control characters replaced by spaces.
+    pub snippet: String,
+}
+
+impl RecoveryReport {
+    /// Number of characters of a stored value kept in [`CorruptEntry::snippet`].
+    pub const SNIPPET_LEN: usize = 40;
+
+    /// Whether every field was loaded and there were no unknown keys.
+    pub fn is_clean(&self) -> bool {
+        self.defaulted_missing.is_empty()
+            && self.defaulted_corrupt.is_empty()
+            && self.unknown.is_empty()
+    }
+
+    /// A readable summary for the user, one line per group and per corrupt field.
+    pub fn to_user_message(&self) -> String {
+        let total = self.loaded.len() + self.defaulted_missing.len() + self.defaulted_corrupt.len();
+        let mut lines = vec![format!("Loaded {} of {total} settings.", self.loaded.len())];
+        if !self.defaulted_missing.is_empty() {
+            lines.push(format!(
+                "Missing, reset to default: {}",
+                self.defaulted_missing.join(", ")
+            ));
+        }
+        if !self.defaulted_corrupt.is_empty() {
+            lines.push("Unreadable, reset to default:".to_string());
+            for entry in &self.defaulted_corrupt {
+                lines.push(format!(
+                    "  {}: {} (stored: {})",
+                    entry.key, entry.error, entry.snippet
+                ));
+            }
+        }
+        if !self.unknown.is_empty() {
+            lines.push(format!("Unknown, ignored: {}", self.unknown.join(", ")));
+        }
+        lines.join("\n")
+    }
+}
+
+/// Implemented by the structs generated by the `perstruct` macro, for persistence code that is
+/// generic over them. The methods forward to the inherent `perstruct_*` methods and `from_map`.
+pub trait Perstruct: Sized {
+    /// Keys of all persisted fields, in declaration order or as changed by
+    /// `#[perstruct(order = N)]`.
+    fn keys() -> &'static [&'static str];
+    /// Loads the struct from a map of keys to serialized values.
+    fn from_map<TKey, TValue>(
+        map: &std::collections::HashMap<TKey, TValue>,
+    ) -> PerstructLoadResult<Self>
+    where
+        TKey: AsRef<str>,
+        TValue: AsRef<str>;
+    /// Serialized values of the dirty fields, to be persisted. The keys borrow from the struct
+    /// when it preserves unknown entries.
+    fn get_changes(&self) -> Result<Vec<(&str, String)>, String>;
+    /// Marks all fields as saved, clearing the dirty fields.
+    fn saved(&mut self);
+}
+
+/// Object-safe counterpart of [`Perstruct`] implemented by the structs generated by the
+/// `perstruct` macro, for keeping different settings structs in one `Vec<Box<dyn ErasedPerstruct>>`.
+pub trait ErasedPerstruct {
+    /// Keys of the dirty fields.
+    fn dirty_keys(&self) -> Vec<String>;
+    /// Serialized values of the dirty fields, to be persisted.
+    fn serialize_changes(&self) -> Result<Vec<(String, String)>, String>;
+    /// Applies a single stored key/value without marking the field dirty. Unknown keys are
+    /// ignored, so entries of a store shared with other structs can be applied as they are.
+    fn apply_entry(&mut self, key: &str, value: &str) -> Result<(), String>;
+    /// Marks all fields as saved, clearing the dirty fields.
+    fn mark_saved(&mut self);
+}
+
+/// Destination of the changes serialized by `perstruct_write_changes`, e.g. the write buffer of a
+/// storage backend, so values are serialized into it without an intermediate `String`.
+pub trait ChangeSink {
+    /// Called for every change with its key and a function serializing its value into a writer.
+    fn entry(
+        &mut self,
+        key: &'static str,
+        value_writer: &mut dyn FnMut(&mut dyn std::io::Write) -> std::io::Result<()>,
+    ) -> std::io::Result<()>;
+}
+
+/// Collects the changes like `perstruct_get_changes` does.
+impl ChangeSink for Vec<(&'static str, String)> {
+    fn entry(
+        &mut self,
+        key: &'static str,
+        value_writer: &mut dyn FnMut(&mut dyn std::io::Write) -> std::io::Result<()>,
+    ) -> std::io::Result<()> {
+        let mut buffer = vec![];
+        value_writer(&mut buffer)?;
+        let value = String::from_utf8(buffer)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        self.push((key, value));
+        Ok(())
+    }
+}
+
+/// Collects the changes with byte values, like `perstruct_get_changes_bytes` does.
+impl ChangeSink for Vec<(&'static str, Vec<u8>)> {
+    fn entry(
+        &mut self,
+        key: &'static str,
+        value_writer: &mut dyn FnMut(&mut dyn std::io::Write) -> std::io::Result<()>,
+    ) -> std::io::Result<()> {
+        let mut value = vec![];
+        value_writer(&mut value)?;
+        self.push((key, value));
+        Ok(())
+    }
+}
+
+/// Object-safe access to the fields of a struct generated by the `perstruct` macro by their
+/// persisted keys, for use behind `Box<dyn DynAccess>` (e.g. from a scripting engine).
+/// The values use the same representation as the stored values.
+pub trait DynAccess {
+    /// Serializes the field with the persisted key `key`, `None` if there is no such field.
+    fn get_json(&self, key: &str) -> Option<Result<String, DynAccessError>>;
+    /// Deserializes `value` into the field with the persisted key `key` and marks it dirty, like
+    /// the typed setters do.
+    fn set_json(&mut self, key: &str, value: &str) -> Result<(), DynAccessError>;
+}
+
+/// Error of the methods of [`DynAccess`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DynAccessError {
+    /// No field has the given key.
+    UnknownKey(String),
+    /// The value of the field could not be serialized.
+    Serialize { key: &'static str, message: String },
+    /// The given value could not be deserialized into the field.
+    Deserialize(PerstructFieldError),
+    /// The field with the key is `readonly`, so it can only be loaded.
+    ReadOnly(&'static str),
+}
+
+impl std::fmt::Display for DynAccessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DynAccessError::UnknownKey(key) => write!(f, "unknown key: {key}"),
+            DynAccessError::Serialize { key, message } => write!(f, "{key}: {message}"),
+            DynAccessError::Deserialize(error) => error.fmt(f),
+            DynAccessError::ReadOnly(key) => write!(f, "{key} is read-only"),
+        }
+    }
+}
+
+impl std::error::Error for DynAccessError {}
+
+/// Copyable handle to a field of a struct `S` generated by the `perstruct` macro, available as an
+/// associated constant named after the field in upper case, e.g. `MySettings::FONT_SIZE`.
+/// Useful for binding UI widgets to fields in generic code without borrowing closures.
+pub struct Field<S, T> {
+    key: fn() -> &'static str,
+    get: fn(&S) -> &T,
+    set: fn(&mut S, T),
+    allowed_values: Option<&'static [&'static str]>,
+    cfg: Option<&'static str>,
+    secret: bool,
+}
+
+impl<S, T> Field<S, T> {
+    #[doc(hidden)]
+    pub const fn new(key: fn() -> &'static str, get: fn(&S) -> &T, set: fn(&mut S, T)) -> Self {
+        Field {
+            key,
+            get,
+            set,
+            allowed_values: None,
+            cfg: None,
+            secret: false,
+        }
+    }
+
+    #[doc(hidden)]
+    pub const fn with_allowed_values(mut self, allowed_values: &'static [&'static str]) -> Self {
+        self.allowed_values = Some(allowed_values);
+        self
+    }
+
+    #[doc(hidden)]
+    pub const fn with_cfg(mut self, cfg: &'static str) -> Self {
+        self.cfg = Some(cfg);
+        self
+    }
+
+    #[doc(hidden)]
+    pub const fn with_secret(mut self) -> Self {
+        self.secret = true;
+        self
+    }
+
+    /// The persisted key of the field.
+    pub fn key(&self) -> &'static str {
+        (self.key)()
+    }
+
+    /// The value of the field in `settings`.
+    pub fn get<'a>(&self, settings: &'a S) -> &'a T {
+        (self.get)(settings)
+    }
+
+    /// Sets the field in `settings` and marks it dirty, like the generated setter. A value the
+    /// field's `validate` function rejects leaves the field unchanged.
+    pub fn set(&self, settings: &mut S, value: T) {
+        (self.set)(settings, value);
+    }
+
+    /// The values of a field with `#[perstruct(one_of(...))]`, e.g. to offer them in a dropdown,
+    /// and `None` for other fields.
+    pub fn allowed_values(&self) -> Option<&'static [&'static str]> {
+        self.allowed_values
+    }
+
+    /// The predicate of the field's `#[cfg(...)]` attributes, like `feature = "telemetry"`, and
+    /// `None` for fields that are always compiled. Several attributes are combined with `all(...)`.
+    pub fn cfg(&self) -> Option<&'static str> {
+        self.cfg
+    }
+
+    /// Whether the field is marked `#[perstruct(secret)]`, so its value should be masked or left
+    /// out when the struct is logged or exported.
+    pub fn is_secret(&self) -> bool {
+        self.secret
+    }
+}
+
+impl<S, T> Clone for Field<S, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<S, T> Copy for Field<S, T> {}
+
+impl<S, T> std::fmt::Debug for Field<S, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Field")
+            .field("key", &self.key())
+            .field("allowed_values", &self.allowed_values)
+            .field("cfg", &self.cfg)
+            .field("secret", &self.secret)
+            .finish()
+    }
+}
+
+/// Value of a field with `#[perstruct(lazy)]`. Loading only keeps the stored string, which is
+/// deserialized on first access and cached. Dereferences to the value, or to the field's default
+/// when the stored string can't be deserialized, like an eagerly loaded field.
+pub struct Lazy<T> {
+    raw: Option<LazyRaw<T>>,
+    value: std::sync::OnceLock<(T, Option<PerstructFieldError>)>,
+}
+
+struct LazyRaw<T> {
+    value: String,
+    parse: fn(&str) -> Result<T, PerstructFieldError>,
+    default: fn() -> T,
+}
+
+impl<T> Lazy<T> {
+    /// An already deserialized value.
+    pub fn new(value: T) -> Self {
+        Lazy {
+            raw: None,
+            value: std::sync::OnceLock::from((value, None)),
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn raw(
+        value: String,
+        parse: fn(&str) -> Result<T, PerstructFieldError>,
+        default: fn() -> T,
+    ) -> Self {
+        Lazy {
+            raw: Some(LazyRaw {
+                value,
+                parse,
+                default,
+            }),
+            value: std::sync::OnceLock::new(),
+        }
+    }
+
+    fn force(&self) -> &(T, Option<PerstructFieldError>) {
+        self.value.get_or_init(|| {
+            let raw = self
+                .raw
+                .as_ref()
+                .expect("a lazy value is either deserialized or has a stored string");
+            match (raw.parse)(&raw.value) {
+                Ok(value) => (value, None),
+                Err(error) => ((raw.default)(), Some(error)),
+            }
+        })
+    }
+
+    /// The value, deserializing the stored string on first access, or the error deserializing it.
+    pub fn get(&self) -> Result<&T, &PerstructFieldError> {
+        match self.force() {
+            (value, None) => Ok(value),
+            (_, Some(error)) => Err(error),
+        }
+    }
+
+    /// Whether the value was deserialized, or set, so accessing it is cheap.
+    pub fn is_parsed(&self) -> bool {
+        self.value.get().is_some()
+    }
+}
+
+impl<T> std::ops::Deref for Lazy<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.force().0
+    }
+}
+
+impl<T> std::ops::DerefMut for Lazy<T> {
+    /// Changing the value drops the stored string and the error deserializing it.
+    fn deref_mut(&mut self) -> &mut T {
+        self.force();
+        self.raw = None;
+        let (value, error) = self.value.get_mut().expect("forced above");
+        *error = None;
+        value
+    }
+}
+
+impl<T> From<T> for Lazy<T> {
+    fn from(value: T) -> Self {
+        Lazy::new(value)
+    }
+}
+
+impl<T: Default> Default for Lazy<T> {
+    fn default() -> Self {
+        Lazy::new(T::default())
+    }
+}
+
+impl<T: Clone> Clone for Lazy<T> {
+    fn clone(&self) -> Self {
+        Lazy {
+            raw: self.raw.as_ref().map(|raw| LazyRaw {
+                value: raw.value.clone(),
+                parse: raw.parse,
+                default: raw.default,
+            }),
+            value: self.value.clone(),
+        }
+    }
+}
+
+impl<T: PartialEq> PartialEq for Lazy<T> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<T: Eq> Eq for Lazy<T> {}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for Lazy<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// Returns the interned copy of `key`: the first `&'static str` with its contents passed to
+/// `intern`. With the `intern` feature, the keys of all structs are interned, so equal keys of
+/// different structs are the same `&'static str` and can be compared with [`std::ptr::eq`].
+#[cfg(feature = "intern")]
+pub fn intern(key: &'static str) -> &'static str {
+    static KEYS: std::sync::Mutex<Option<std::collections::HashSet<&'static str>>> =
+        std::sync::Mutex::new(None);
+    let mut keys = KEYS
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    let keys = keys.get_or_insert_with(Default::default);
+    match keys.get(key) {
+        Some(interned) => interned,
+        None => {
+            keys.insert(key);
+            key
+        }
+    }
+}
+
+#[doc(hidden)]
+pub mod __private {
+    /// Used by the generated code, so crates using the macro don't need their own dependency.
+    #[cfg(feature = "json")]
+    pub use serde_json;
+
+    /// Dirty fields of a struct with `#[perstruct(dirty = "vec")]`, with the same API as the
+    /// `HashSet` used by default. Doesn't allocate until a field becomes dirty.
+    #[derive(Debug, Clone, Default, PartialEq, Eq)]
+    pub struct DirtyVec(Vec<&'static str>);
+
+    impl DirtyVec {
+        pub fn all(keys: &[&'static str]) -> Self {
+            DirtyVec(keys.to_vec())
+        }
+        pub fn insert(&mut self, key: &'static str) -> bool {
+            if self.contains(key) {
+                return false;
+            }
+            self.0.push(key);
+            true
+        }
+        pub fn remove(&mut self, key: &str) -> bool {
+            let len = self.0.len();
+            self.0.retain(|dirty| *dirty != key);
+            self.0.len() != len
+        }
+        pub fn contains(&self, key: &str) -> bool {
+            self.0.contains(&key)
+        }
+        pub fn iter(&self) -> std::slice::Iter<'_, &'static str> {
+            self.0.iter()
+        }
+        pub fn clear(&mut self) {
+            self.0.clear();
+        }
+        pub fn is_empty(&self) -> bool {
+            self.0.is_empty()
+        }
+        pub fn len(&self) -> usize {
+            self.0.len()
+        }
+    }
+
+    /// Dirty fields of a struct with `#[perstruct(dirty = "bitset")]`, with the same API as the
+    /// `HashSet` used by default. Stores one bit per field index in `keys`, so never allocates.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct DirtyBits {
+        bits: u128,
+        keys: &'static [&'static str],
+    }
+
+    impl DirtyBits {
+        pub fn new(keys: &'static [&'static str]) -> Self {
+            DirtyBits { bits: 0, keys }
+        }
+        pub fn all(keys: &'static [&'static str]) -> Self {
+            let bits = if keys.len() == 128 {
+                u128::MAX
+            } else {
+                (1 << keys.len()) - 1
+            };
+            DirtyBits { bits, keys }
+        }
+        fn bit(&self, key: &str) -> u128 {
+            self.keys
+                .iter()
+                .position(|candidate| *candidate == key)
+                .map_or(0, |index| 1 << index)
+        }
+        pub fn insert(&mut self, key: &'static str) -> bool {
+            let inserted = !self.contains(key);
+            self.bits |= self.bit(key);
+            inserted
+        }
+        pub fn remove(&mut self, key: &str) -> bool {
+            let removed = self.contains(key);
+            self.bits &= !self.bit(key);
+            removed
+        }
+        pub fn contains(&self, key: &str) -> bool {
+            self.bits & self.bit(key) != 0
+        }
+        pub fn iter(&self) -> impl Iterator<Item = &&'static str> + '_ {
+            self.keys
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| self.bits & (1 << index) != 0)
+                .map(|(_, key)| key)
+        }
+        pub fn clear(&mut self) {
+            self.bits = 0;
+        }
+        pub fn is_empty(&self) -> bool {
+            self.bits == 0
+        }
+        pub fn len(&self) -> usize {
+            self.bits.count_ones() as usize
+        }
+    }
+
+    /// Builds the report of `from_map_recovering` from the result of `from_map`, the keys of all
+    /// fields and of the fields missing from the map, and the stored value of a field by key.
+    pub fn recovery_report<T>(
+        result: &crate::PerstructLoadResult<T>,
+        keys: Vec<&'static str>,
+        missing: Vec<&'static str>,
+        stored_value: impl Fn(&str) -> Option<String>,
+    ) -> crate::RecoveryReport {
+        let corrupt = result
+            .deserialization_errors()
+            .iter()
+            .map(|error| {
+                let stored = stored_value(error.key).unwrap_or_default();
+                let mut snippet = stored
+                    .chars()
+                    .take(crate::RecoveryReport::SNIPPET_LEN)
+                    .map(|c| if c.is_control() { ' ' } else { c })
+                    .collect::<String>();
+                if stored.chars().count() > crate::RecoveryReport::SNIPPET_LEN {
+                    snippet.push('…');
+                }
+                crate::CorruptEntry {
+                    key: error.key,
+                    error: error.message.clone(),
+                    snippet,
+                }
+            })
+            .collect::<Vec<_>>();
+        crate::RecoveryReport {
+            loaded: keys
+                .into_iter()
+                .filter(|key| {
+                    !missing.contains(key) && !corrupt.iter().any(|entry| entry.key == *key)
+                })
+                .collect(),
+            defaulted_missing: missing,
+            defaulted_corrupt: corrupt,
+            unknown: result.unknown_fields().to_vec(),
+        }
+    }
+
+    /// Serializer of a `display_fromstr` field.
+    pub fn display_to_string<T: std::fmt::Display + ?Sized>(value: &T) -> Result<String, String> {
+        Ok(value.to_string())
+    }
+
+    /// Deserializer of a `display_fromstr` field, with the `Display` of the parse error.
+    pub fn parse_from_str<T>(value: &str) -> Result<T, String>
+    where
+        T: std::str::FromStr,
+        T::Err: std::fmt::Display,
+    {
+        value.parse().map_err(|e: T::Err| e.to_string())
+    }
+
+    /// Escapes the suffix of a row of a `dynamic_key` field, so a `.` in it can't be mistaken for
+    /// the separator: `%` becomes `%25` and `.` becomes `%2E`.
+    pub fn escape_key_suffix(suffix: &str) -> String {
+        suffix.replace('%', "%25").replace('.', "%2E")
+    }
+
+    /// Reverses `escape_key_suffix`, or `None` for a suffix it can't have produced.
+    pub fn unescape_key_suffix(suffix: &str) -> Option<String> {
+        let mut unescaped = String::with_capacity(suffix.len());
+        let mut rest = suffix;
+        while let Some(index) = rest.find(['%', '.']) {
+            unescaped.push_str(&rest[..index]);
+            rest = &rest[index..];
+            if rest.starts_with("%25") {
+                unescaped.push('%');
+            } else if rest.starts_with("%2E") {
+                unescaped.push('.');
+            } else {
+                return None;
+            }
+            rest = &rest[3..];
+        }
+        unescaped.push_str(rest);
+        Some(unescaped)
+    }
+
+    /// The value of a `precise_updates` field before `update_<field>`, to compare with after the
+    /// closure. `Unavailable` for types that aren't `PartialEq + Clone`, which are always dirty.
+    pub enum UpdateSnapshot<T> {
+        Value(T, fn(&T, &T) -> bool),
+        Unavailable,
+    }
+
+    impl<T> UpdateSnapshot<T> {
+        pub fn is_unchanged(&self, current: &T) -> bool {
+            match self {
+                UpdateSnapshot::Value(previous, eq) => eq(previous, current),
+                UpdateSnapshot::Unavailable => false,
+            }
+        }
+    }
+
+    /// Takes the `UpdateSnapshot` of a field: `(&UpdateProbe(&value)).snapshot()` resolves to
+    /// `CloneSnapshot` when the type is `PartialEq + Clone` and to `NoSnapshot` otherwise, since
+    /// method lookup tries the receiver before borrowing it again.
+    pub struct UpdateProbe<'a, T>(pub &'a T);
+
+    pub trait CloneSnapshot<T> {
+        fn snapshot(&self) -> UpdateSnapshot<T>;
+    }
+
+    impl<T: PartialEq + Clone> CloneSnapshot<T> for UpdateProbe<'_, T> {
+        fn snapshot(&self) -> UpdateSnapshot<T> {
+            UpdateSnapshot::Value(self.0.clone(), |previous, current| previous == current)
+        }
+    }
+
+    pub trait NoSnapshot<T> {
+        fn snapshot(&self) -> UpdateSnapshot<T>;
+    }
+
+    impl<T> NoSnapshot<T> for &UpdateProbe<'_, T> {
+        fn snapshot(&self) -> UpdateSnapshot<T> {
+            UpdateSnapshot::Unavailable
+        }
+    }
+
+    /// `Default`, as the bound of the methods building a struct with `no_default` fields through
+    /// it, explaining that these need a hand-written `Default` impl or `try_from_map`.
+    #[diagnostic::on_unimplemented(
+        message = "`{Self}` has `no_default` fields, so it has no `Default` impl to build it with",
+        label = "`{Self}` doesn't implement `Default`",
+        note = "load it with `try_from_map`, which fails when a `no_default` field is missing, or \
+            implement `Default` for it by hand, `perstruct` rejects deriving it"
+    )]
+    pub trait NoDefaultFields: Default {}
+
+    impl<T: Default> NoDefaultFields for T {}
+
+    /// Returns `value` if it's one of the `allowed` values of a `one_of` field, or the message of
+    /// why it's not allowed.
+    pub fn check_one_of<T: AsRef<str>>(value: T, allowed: &[&str]) -> Result<T, String> {
+        if allowed.contains(&value.as_ref()) {
+            return Ok(value);
+        }
+        let allowed = allowed
+            .iter()
+            .map(|allowed| format!("{allowed:?}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        Err(format!("{:?} is not one of {allowed}", value.as_ref()))
+    }
+
+    /// Returns `value` if the `validate` function of its field accepts it, or the message of why
+    /// it doesn't.
+    pub fn check_valid<V: std::borrow::Borrow<T>, T: ?Sized>(
+        value: V,
+        validate: impl FnOnce(&T) -> Result<(), String>,
+    ) -> Result<V, String> {
+        validate(value.borrow())?;
+        Ok(value)
+    }
+
+    /// Normalizes a `Vec` field marked with `dedup` and/or `max_len`, returning whether it changed.
+    /// Duplicates are removed keeping the first occurrence, then the length is capped by dropping
+    /// elements from the front or the back.
+    pub fn normalize_vec<T: PartialEq>(
+        value: &mut Vec<T>,
+        dedup: bool,
+        max_len: Option<usize>,
+        truncate_front: bool,
+    ) -> bool {
+        let len = value.len();
+        if dedup {
+            let mut index = 0;
+            while index < value.len() {
+                if value[..index].contains(&value[index]) {
+                    value.remove(index);
+                } else {
+                    index += 1;
+                }
+            }
+        }
+        if let Some(max_len) = max_len {
+            if value.len() > max_len {
+                if truncate_front {
+                    value.drain(..value.len() - max_len);
+                } else {
+                    value.truncate(max_len);
+                }
+            }
+        }
+        value.len() != len
+    }
 }
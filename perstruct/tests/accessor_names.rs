@@ -0,0 +1,25 @@
+use perstruct::perstruct;
+
+#[perstruct(getter_prefix = "get_", setter_prefix = "put_")]
+struct FontSettings {
+    size: u8,
+    #[perstruct(getter = "font", setter = "set_font_family")]
+    family: String,
+}
+
+#[test]
+fn accessor_names_are_configurable() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = FontSettings::default();
+    settings.put_size(12);
+    settings.set_font_family("mono".to_string());
+    assert_eq!(settings.get_size(), 12);
+    assert_eq!(settings.font(), "mono");
+    settings.update_family(|family| family.push('!'));
+    assert_eq!(settings.font(), "mono!");
+
+    FontSettings::SIZE.set(&mut settings, 14);
+    assert_eq!(settings.get_size(), 14);
+    assert_eq!(settings.perstruct_dirty_fields().len(), 2);
+}
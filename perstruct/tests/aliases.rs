@@ -0,0 +1,136 @@
+use perstruct::perstruct;
+
+#[perstruct]
+struct AliasedSettings {
+    #[perstruct(key = "font_size", alias = ["fontSize", "size"])]
+    font_size: u32,
+    #[perstruct(alias = "colour")]
+    color: String,
+}
+
+#[test]
+fn alias_is_loaded_when_key_is_absent() {
+    use pretty_assertions::assert_eq;
+
+    let result = AliasedSettings::from_map(
+        &vec![("size", "12"), ("colour", "\"red\"")]
+            .into_iter()
+            .collect(),
+    );
+    assert_eq!(result.value().font_size(), 12);
+    assert_eq!(result.value().color(), "red");
+    assert!(result.unknown_fields().is_empty());
+    assert!(result.shadowed_aliases().is_empty());
+    assert_eq!(
+        AliasedSettings::perstruct_keys(),
+        vec!["font_size", "color"]
+    );
+}
+
+#[test]
+fn canonical_key_wins_then_aliases_in_declared_order() {
+    use pretty_assertions::assert_eq;
+
+    let result = AliasedSettings::from_map(
+        &vec![("font_size", "10"), ("fontSize", "11"), ("size", "12")]
+            .into_iter()
+            .collect(),
+    );
+    assert_eq!(result.value().font_size(), 10);
+    let mut shadowed_aliases = result.shadowed_aliases().to_vec();
+    shadowed_aliases.sort();
+    assert_eq!(
+        shadowed_aliases,
+        vec![
+            ("font_size", "fontSize".to_string()),
+            ("font_size", "size".to_string())
+        ]
+    );
+
+    let result = AliasedSettings::from_map(
+        &vec![("size", "12"), ("fontSize", "11")]
+            .into_iter()
+            .collect(),
+    );
+    assert_eq!(result.value().font_size(), 11);
+    assert_eq!(
+        result.shadowed_aliases(),
+        vec![("font_size", "size".to_string())]
+    );
+}
+
+#[test]
+fn values_loaded_from_aliases_are_rewritten_under_the_key() {
+    use pretty_assertions::assert_eq;
+
+    let result = AliasedSettings::from_map(
+        &vec![("fontSize", "12"), ("color", "\"red\"")]
+            .into_iter()
+            .collect(),
+    );
+    assert_eq!(
+        result.used_aliases(),
+        vec![("font_size", "fontSize".to_string())]
+    );
+    let mut settings = result.into_value();
+    assert_eq!(
+        settings.perstruct_get_changes().unwrap(),
+        vec![("font_size", "12".to_string())]
+    );
+    settings.perstruct_saved();
+
+    let result = AliasedSettings::from_map(
+        &vec![("font_size", "10"), ("size", "12"), ("color", "\"red\"")]
+            .into_iter()
+            .collect(),
+    );
+    assert!(result.used_aliases().is_empty());
+    assert!(result.value().perstruct_dirty_fields().is_empty());
+}
+
+#[test]
+fn keys_missing_from_map() {
+    use pretty_assertions::assert_eq;
+
+    let map = vec![("fontSize", "12"), ("unknown", "1")]
+        .into_iter()
+        .collect::<std::collections::HashMap<_, _>>();
+    assert_eq!(
+        AliasedSettings::perstruct_keys_missing_from(&map),
+        vec!["color"]
+    );
+    let map = vec![("font_size", "12"), ("colour", "\"red\"")]
+        .into_iter()
+        .collect::<std::collections::HashMap<_, _>>();
+    assert!(AliasedSettings::perstruct_keys_missing_from(&map).is_empty());
+}
+
+#[perstruct]
+struct RenamedSettings {
+    #[perstruct(key = "volume", aliases = "vol, sound_volume")]
+    volume: u8,
+}
+
+#[test]
+fn comma_separated_aliases() {
+    use pretty_assertions::assert_eq;
+
+    let result = RenamedSettings::from_map(
+        &vec![("sound_volume", "3"), ("vol", "4")]
+            .into_iter()
+            .collect(),
+    );
+    assert_eq!(
+        result.shadowed_aliases(),
+        vec![("volume", "sound_volume".to_string())]
+    );
+    let mut settings = result.into_value();
+    assert_eq!(settings.volume(), 4);
+    assert_eq!(RenamedSettings::perstruct_keys(), vec!["volume"]);
+
+    settings.set_volume(5);
+    assert_eq!(
+        settings.perstruct_get_changes().unwrap(),
+        vec![("volume", "5".to_string())]
+    );
+}
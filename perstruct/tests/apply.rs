@@ -0,0 +1,37 @@
+use perstruct::perstruct;
+
+#[perstruct]
+struct LiveSettings {
+    #[perstruct(alias = "level")]
+    volume: u8,
+    muted: bool,
+}
+
+#[test]
+fn apply_single_entries() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = LiveSettings::default();
+    settings.set_muted(true);
+
+    assert_eq!(settings.perstruct_apply("volume", "7"), Ok(true));
+    assert_eq!(settings.volume(), 7);
+    assert_eq!(
+        settings.perstruct_apply("level", String::from("8")),
+        Ok(true)
+    );
+    assert_eq!(settings.volume(), 8);
+    assert_eq!(settings.perstruct_apply("unknown", "1"), Ok(false));
+    assert_eq!(
+        settings.perstruct_apply("volume", "-1"),
+        Err("invalid value: integer `-1`, expected u8 at line 1 column 2".to_string())
+    );
+    assert_eq!(settings.volume(), 8);
+
+    assert_eq!(
+        settings.perstruct_get_changes().unwrap(),
+        vec![("muted", "true".to_string())]
+    );
+    assert_eq!(settings.perstruct_apply("muted", "false"), Ok(true));
+    assert_eq!(settings.perstruct_get_changes().unwrap(), vec![]);
+}
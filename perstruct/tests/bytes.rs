@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+
+use perstruct::{perstruct, PerstructFieldError};
+
+#[perstruct]
+struct CacheSettings {
+    name: String,
+    #[perstruct(default = 64)]
+    capacity: u32,
+    blob: Vec<u8>,
+}
+
+#[test]
+fn changes_as_bytes() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = CacheSettings::default();
+    settings.set_name("thumbnails".to_string());
+    settings.set_blob(vec![0, 255]);
+    let mut changes = settings.perstruct_get_changes_bytes().unwrap();
+    changes.sort();
+    assert_eq!(
+        changes,
+        vec![
+            ("blob", b"[0,255]".to_vec()),
+            ("name", b"\"thumbnails\"".to_vec()),
+        ]
+    );
+}
+
+#[test]
+fn load_from_byte_values() {
+    use pretty_assertions::assert_eq;
+
+    let map: HashMap<&str, Vec<u8>> = HashMap::from([
+        ("name", b"\"thumbnails\"".to_vec()),
+        ("capacity", vec![0xff, 0xfe]),
+        ("blob", b"[1,2]".to_vec()),
+        ("legacy", vec![0xc3]),
+    ]);
+    let result = CacheSettings::from_map_bytes(&map);
+    assert_eq!(
+        result.deserialization_errors(),
+        vec![PerstructFieldError {
+            key: "capacity",
+            message: "invalid utf-8 sequence of 1 bytes from index 0".to_string(),
+            line: 0,
+            column: 0,
+        }]
+    );
+    assert_eq!(result.unknown_fields(), vec!["legacy".to_string()]);
+    let settings = result.into_value();
+    assert_eq!(settings.name(), "thumbnails");
+    assert_eq!(settings.capacity(), 64);
+    assert_eq!(settings.blob(), &vec![1, 2]);
+}
+
+#[perstruct(version = 2, preserve_unknown)]
+struct SyncSettings {
+    #[perstruct(required, alias = "account")]
+    account_id: String,
+    interval: u32,
+}
+
+#[test]
+fn changes_as_bytes_include_version_and_unknown_entries() {
+    use pretty_assertions::assert_eq;
+
+    let map = HashMap::from([("account_id", "\"a-1\""), ("legacy", "true")]);
+    let mut settings = SyncSettings::from_map(&map).into_value();
+    settings.set_interval(30);
+    let changes = settings.perstruct_get_changes_bytes().unwrap();
+    let text = settings.perstruct_get_changes().unwrap();
+    assert_eq!(
+        changes,
+        text.into_iter()
+            .map(|(key, value)| (key, value.into_bytes()))
+            .collect::<Vec<_>>()
+    );
+    assert!(changes.contains(&("legacy", b"true".to_vec())));
+}
+
+#[test]
+fn invalid_bytes_of_required_alias_are_errors() {
+    use pretty_assertions::assert_eq;
+
+    let map: HashMap<&str, Vec<u8>> = HashMap::from([("account", vec![0xff])]);
+    let result = SyncSettings::from_map_bytes(&map);
+    assert_eq!(result.missing_required(), Vec::<&str>::new());
+    assert_eq!(
+        result.deserialization_errors(),
+        vec![PerstructFieldError {
+            key: "account_id",
+            message: "invalid utf-8 sequence of 1 bytes from index 0".to_string(),
+            line: 0,
+            column: 0,
+        }]
+    );
+    assert_eq!(result.unknown_fields(), Vec::<String>::new());
+}
+
+#[test]
+fn invalid_unknown_bytes_are_bounded() {
+    use pretty_assertions::assert_eq;
+
+    let map: HashMap<String, Vec<u8>> = (0..perstruct::MAX_UNKNOWN_FIELDS + 5)
+        .map(|i| (format!("stale_{i}"), vec![0xff]))
+        .collect();
+    let result = CacheSettings::from_map_bytes(&map);
+    assert_eq!(result.unknown_fields().len(), perstruct::MAX_UNKNOWN_FIELDS);
+    assert_eq!(result.unknown_overflow(), 5);
+}
@@ -0,0 +1,58 @@
+use perstruct::perstruct;
+
+#[perstruct(case_insensitive)]
+struct LegacySettings {
+    #[perstruct(key = "fontSize")]
+    font_size: i32,
+    #[perstruct(alias = "colour")]
+    color: String,
+}
+
+#[test]
+fn keys_match_ignoring_case() {
+    use pretty_assertions::assert_eq;
+
+    let result = LegacySettings::from_map(
+        &vec![("FONTSIZE", "12"), ("Colour", "\"red\""), ("Other", "1")]
+            .into_iter()
+            .collect(),
+    );
+    assert_eq!(result.value().font_size(), 12);
+    assert_eq!(result.value().color(), "red");
+    assert_eq!(result.unknown_fields(), vec!["Other".to_string()]);
+    assert_eq!(result.used_aliases(), vec![("color", "Colour".to_string())]);
+    // Loaded from an alias, so it's rewritten under its key
+    assert_eq!(
+        result.value().perstruct_dirty_fields(),
+        &vec!["color"].into_iter().collect()
+    );
+}
+
+#[test]
+fn exact_case_match_wins() {
+    use pretty_assertions::assert_eq;
+
+    let result = LegacySettings::from_map(
+        &vec![("fontsize", "1"), ("fontSize", "2"), ("FONTSIZE", "3")]
+            .into_iter()
+            .collect(),
+    );
+    assert_eq!(result.value().font_size(), 2);
+    let mut shadowed_aliases = result.shadowed_aliases().to_vec();
+    shadowed_aliases.sort();
+    assert_eq!(
+        shadowed_aliases,
+        vec![
+            ("fontSize", "FONTSIZE".to_string()),
+            ("fontSize", "fontsize".to_string())
+        ]
+    );
+
+    let mut settings = result.into_value();
+    settings.perstruct_saved();
+    settings.set_font_size(4);
+    assert_eq!(
+        settings.perstruct_get_changes().unwrap(),
+        vec![("fontSize", "4".to_string())]
+    );
+}
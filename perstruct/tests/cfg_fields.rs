@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+
+use perstruct::{perstruct, DirtyFields, Perstruct};
+
+/// `cfg(any())` never holds, `cfg(test)` always does in tests.
+#[perstruct(key_fn = "mangle", case_insensitive, dirty = "bitset", version = 2)]
+struct MappedSettings {
+    #[cfg(any())]
+    #[perstruct(alias = "old_removed")]
+    removed: u32,
+    #[cfg(test)]
+    #[perstruct(default = 3)]
+    kept: u32,
+    #[cfg(any())]
+    #[perstruct(skip)]
+    removed_cache: Vec<u8>,
+}
+
+fn mangle(key: &'static str) -> &'static str {
+    Box::leak(format!("app:{key}").into_boxed_str())
+}
+
+#[test]
+fn removed_fields_are_not_persisted() {
+    use pretty_assertions::assert_eq;
+
+    assert_eq!(MappedSettings::perstruct_keys(), vec!["app:kept"]);
+    assert_eq!(<MappedSettings as Perstruct>::keys(), &["app:kept"]);
+    let result =
+        MappedSettings::from_map(&HashMap::from([("app:removed", "1"), ("APP:KEPT", "4")]));
+    assert_eq!(result.value().kept(), 4);
+    assert_eq!(result.unknown_fields(), vec!["app:removed".to_string()]);
+}
+
+#[perstruct(single_document = "settings")]
+struct RequiredSettings {
+    #[cfg(any())]
+    #[perstruct(no_default)]
+    removed: String,
+    #[cfg(test)]
+    #[perstruct(no_default)]
+    token: String,
+    #[cfg(any())]
+    #[perstruct(store = "secure")]
+    removed_secret: String,
+}
+
+#[test]
+fn removed_required_fields_are_not_required() {
+    use pretty_assertions::assert_eq;
+
+    let result = RequiredSettings::try_from_map(&HashMap::from([("token", "\"abc\"")])).unwrap();
+    assert_eq!(result.value().token(), "abc");
+    assert_eq!(
+        RequiredSettings::try_from_map(&HashMap::<&str, &str>::new())
+            .err()
+            .unwrap()
+            .missing_fields,
+        vec!["token"]
+    );
+}
+
+#[derive(Perstruct)]
+struct DerivedSettings {
+    #[cfg(any())]
+    removed: u32,
+    kept: u32,
+    _perstruct_dirty_fields: DirtyFields,
+}
+
+#[test]
+fn derive_skips_removed_fields() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = DerivedSettings::default();
+    settings.perstruct_mark_all_dirty();
+    assert_eq!(
+        settings.perstruct_get_changes().unwrap(),
+        vec![("kept", "0".to_string())]
+    );
+}
+
+#[perstruct]
+struct DocumentedSettings {
+    #[cfg(test)]
+    #[cfg(not(any()))]
+    doubly_gated: u32,
+    always: u32,
+}
+
+#[test]
+fn field_handles_expose_the_cfg_predicate() {
+    use pretty_assertions::assert_eq;
+
+    assert_eq!(MappedSettings::KEPT.cfg(), Some("test"));
+    assert_eq!(
+        DocumentedSettings::DOUBLY_GATED.cfg(),
+        Some("all(test, not(any()))")
+    );
+    assert_eq!(DocumentedSettings::ALWAYS.cfg(), None);
+}
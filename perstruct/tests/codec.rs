@@ -0,0 +1,76 @@
+use perstruct::{perstruct, JsonCodec, PerstructCodec};
+
+/// Stores json with a prefix naming the format.
+struct TaggedJson;
+
+impl PerstructCodec for TaggedJson {
+    fn encode<T: serde::Serialize>(value: &T) -> Result<String, String> {
+        Ok(format!("json:{}", JsonCodec::encode(value)?))
+    }
+
+    fn decode<T: serde::de::DeserializeOwned>(value: &str) -> Result<T, String> {
+        let json = value.strip_prefix("json:").ok_or("missing format tag")?;
+        JsonCodec::decode(json)
+    }
+}
+
+#[perstruct(codec = "TaggedJson")]
+struct TaggedSettings {
+    #[perstruct(default = 3)]
+    retries: u32,
+    #[perstruct(serialize_as = "String")]
+    endpoint: Endpoint,
+}
+
+#[derive(Default, Debug, PartialEq)]
+struct Endpoint(String);
+
+impl From<&Endpoint> for String {
+    fn from(endpoint: &Endpoint) -> Self {
+        endpoint.0.clone()
+    }
+}
+
+impl From<String> for Endpoint {
+    fn from(endpoint: String) -> Self {
+        Endpoint(endpoint)
+    }
+}
+
+#[test]
+fn values_go_through_the_codec() {
+    use pretty_assertions::assert_eq;
+
+    let result = TaggedSettings::from_map(
+        &vec![("retries", "json:5"), ("endpoint", "json:\"https://a\"")]
+            .into_iter()
+            .collect(),
+    );
+    assert!(result.deserialization_errors().is_empty());
+    let mut settings = result.into_value();
+    assert_eq!(settings.retries(), 5);
+    assert_eq!(settings.endpoint(), &Endpoint("https://a".to_string()));
+
+    settings.perstruct_saved();
+    settings.set_retries(7);
+    assert_eq!(
+        settings.perstruct_get_changes().unwrap(),
+        vec![("retries", "json:7".to_string())]
+    );
+}
+
+#[test]
+fn codec_errors_are_deserialization_errors() {
+    use pretty_assertions::assert_eq;
+
+    let result = TaggedSettings::from_map(&vec![("retries", "5")].into_iter().collect());
+    assert_eq!(result.value().retries(), 3);
+    assert_eq!(
+        result
+            .deserialization_errors()
+            .iter()
+            .map(|error| (error.key, error.message.as_str()))
+            .collect::<Vec<_>>(),
+        vec![("retries", "missing format tag")]
+    );
+}
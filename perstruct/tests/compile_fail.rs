@@ -0,0 +1,5 @@
+#[test]
+fn conflicting_attributes_are_rejected() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/compile_fail/*.rs");
+}
@@ -0,0 +1,8 @@
+use perstruct::perstruct;
+
+#[perstruct(codec = "perstruct::JsonCodec", single_document = "settings")]
+struct Settings {
+    value: i32,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use perstruct::perstruct;
+
+#[perstruct]
+struct Settings {
+    #[perstruct(default_fn = "mod::f")]
+    port: u16,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use perstruct::perstruct;
+
+#[perstruct]
+struct Settings {
+    #[perstruct(default = 1 +)]
+    retries: u32,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use perstruct::perstruct;
+
+#[perstruct]
+struct Settings {
+    #[perstruct(default = 1, try_default_fn = "one")]
+    value: i32,
+}
+
+fn main() {}
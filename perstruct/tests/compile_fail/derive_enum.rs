@@ -0,0 +1,9 @@
+use perstruct::Perstruct;
+
+#[derive(Perstruct)]
+enum Settings {
+    Light,
+    Dark,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use perstruct::perstruct;
+
+#[perstruct]
+struct Settings {
+    theme: String,
+    #[perstruct(alias = "theme")]
+    color_scheme: String,
+}
+
+fn main() {}
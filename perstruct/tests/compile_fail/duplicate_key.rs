@@ -0,0 +1,10 @@
+use perstruct::perstruct;
+
+#[perstruct(rename_all = "camelCase")]
+struct Settings {
+    font_size: u32,
+    #[perstruct(key = "fontSize")]
+    legacy_font_size: u32,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use perstruct::perstruct;
+
+#[perstruct]
+struct Settings {
+    #[perstruct(dynamic_key)]
+    window_geometry: Vec<(u32, u32)>,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use perstruct::perstruct;
+
+#[perstruct]
+enum Settings {
+    Light,
+    Dark,
+}
+
+fn main() {}
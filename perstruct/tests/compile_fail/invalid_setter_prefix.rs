@@ -0,0 +1,8 @@
+use perstruct::perstruct;
+
+#[perstruct(setter_prefix = "with-")]
+struct Settings {
+    port: u16,
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use perstruct::perstruct;
+
+const KEY: &str = "retries";
+
+#[perstruct]
+struct Settings {
+    #[perstruct(key = KEY)]
+    retries: u32,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use perstruct::perstruct;
+
+#[perstruct]
+struct Settings {
+    #[perstruct(key = "value", key_const = "KEY")]
+    value: i32,
+}
+
+fn main() {}
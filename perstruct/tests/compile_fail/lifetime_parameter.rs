@@ -0,0 +1,8 @@
+use perstruct::perstruct;
+
+#[perstruct]
+struct Settings<'a> {
+    name: &'a str,
+}
+
+fn main() {}
@@ -0,0 +1,6 @@
+use perstruct::perstruct;
+
+#[perstruct]
+struct UiTheme(String);
+
+fn main() {}
@@ -0,0 +1,14 @@
+use std::collections::HashMap;
+
+use perstruct::perstruct;
+
+#[perstruct]
+struct Account {
+    #[perstruct(no_default)]
+    account_id: u64,
+}
+
+fn main() {
+    let map: HashMap<String, String> = HashMap::new();
+    let _ = Account::from_map(&map);
+}
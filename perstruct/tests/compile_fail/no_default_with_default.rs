@@ -0,0 +1,9 @@
+use perstruct::perstruct;
+
+#[perstruct]
+struct Settings {
+    #[perstruct(no_default, default = 1)]
+    value: i32,
+}
+
+fn main() {}
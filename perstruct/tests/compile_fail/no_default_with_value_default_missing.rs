@@ -0,0 +1,9 @@
+use perstruct::perstruct;
+
+#[perstruct]
+struct Settings {
+    #[perstruct(no_default, value_default_missing)]
+    value: i32,
+}
+
+fn main() {}
@@ -0,0 +1,13 @@
+use perstruct::perstruct;
+
+#[perstruct]
+struct Settings {
+    #[perstruct(validate = "valid_port", on_invalid = "reject")]
+    port: u16,
+}
+
+fn valid_port(_port: &u16) -> Result<(), String> {
+    Ok(())
+}
+
+fn main() {}
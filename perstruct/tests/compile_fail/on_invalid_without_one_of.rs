@@ -0,0 +1,9 @@
+use perstruct::perstruct;
+
+#[perstruct]
+struct Settings {
+    #[perstruct(on_invalid = "reject")]
+    theme: String,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use perstruct::perstruct;
+
+#[perstruct(single_document = "settings", on_load_raw = "archive")]
+struct Settings {
+    volume: u8,
+}
+
+fn archive(_entries: &[(String, String)]) {}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use perstruct::perstruct;
+
+#[perstruct]
+struct Settings {
+    #[perstruct(one_of("auto", "light", "dark"))]
+    theme: String,
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+use perstruct::perstruct;
+
+#[perstruct]
+struct Settings {
+    #[perstruct(readonly)]
+    install_id: String,
+}
+
+fn main() {
+    let mut settings = Settings::default();
+    settings.set_install_id("abc".to_string());
+}
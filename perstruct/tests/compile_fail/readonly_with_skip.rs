@@ -0,0 +1,9 @@
+use perstruct::perstruct;
+
+#[perstruct]
+struct Settings {
+    #[perstruct(readonly, skip)]
+    value: i32,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use perstruct::perstruct;
+
+#[perstruct]
+struct Settings {
+    #[perstruct(key = "a", key = "b")]
+    value: i32,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use perstruct::perstruct;
+
+#[perstruct]
+struct Settings {
+    #[perstruct(skip, key = "value")]
+    value: i32,
+}
+
+fn main() {}
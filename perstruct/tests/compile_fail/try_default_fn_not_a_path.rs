@@ -0,0 +1,9 @@
+use perstruct::perstruct;
+
+#[perstruct]
+struct Settings {
+    #[perstruct(try_default_fn = "themes::")]
+    theme: String,
+}
+
+fn main() {}
@@ -0,0 +1,6 @@
+use perstruct::perstruct;
+
+#[perstruct]
+struct Settings(u32, String);
+
+fn main() {}
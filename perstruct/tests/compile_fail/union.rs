@@ -0,0 +1,9 @@
+use perstruct::perstruct;
+
+#[perstruct]
+union Settings {
+    volume: u32,
+    brightness: f32,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use perstruct::perstruct;
+
+#[perstruct(getter_prefix = "get_")]
+struct Release {
+    commit: String,
+    #[perstruct(key = "previous_commit")]
+    set_commit: String,
+}
+
+fn main() {}
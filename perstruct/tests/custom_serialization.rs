@@ -0,0 +1,180 @@
+use perstruct::{perstruct, PerstructFieldError};
+
+#[perstruct]
+struct TimestampSettings {
+    #[perstruct(
+        serialize_with = "timestamp::serialize",
+        deserialize_with = "timestamp::deserialize"
+    )]
+    last_seen: Timestamp,
+    count: i32,
+}
+
+#[perstruct]
+struct NetworkSettings {
+    #[perstruct(with = "cidr")]
+    subnet: Cidr,
+}
+
+#[perstruct]
+struct ServerSettings {
+    #[perstruct(display_fromstr, default_fn = "default_listen")]
+    listen: std::net::SocketAddr,
+}
+
+fn default_listen() -> std::net::SocketAddr {
+    std::net::SocketAddr::from(([127, 0, 0, 1], 8080))
+}
+
+#[derive(PartialEq, Eq, Debug)]
+struct Cidr {
+    address: std::net::Ipv4Addr,
+    prefix_len: u8,
+}
+
+impl Default for Cidr {
+    fn default() -> Self {
+        Cidr {
+            address: std::net::Ipv4Addr::UNSPECIFIED,
+            prefix_len: 0,
+        }
+    }
+}
+
+mod cidr {
+    use super::Cidr;
+
+    pub fn serialize(value: &Cidr) -> Result<String, String> {
+        Ok(format!("{}/{}", value.address, value.prefix_len))
+    }
+
+    pub fn deserialize(value: &str) -> Result<Cidr, String> {
+        let (address, prefix_len) = value.split_once('/').ok_or("missing prefix length")?;
+        Ok(Cidr {
+            address: address
+                .parse()
+                .map_err(|e| format!("invalid address: {e}"))?,
+            prefix_len: prefix_len
+                .parse()
+                .map_err(|e| format!("invalid prefix length: {e}"))?,
+        })
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Default)]
+struct Timestamp(u64);
+
+mod timestamp {
+    use super::Timestamp;
+
+    pub fn serialize(value: &Timestamp) -> Result<String, String> {
+        Ok(value.0.to_string())
+    }
+
+    pub fn deserialize(value: &str) -> Result<Timestamp, String> {
+        value
+            .parse()
+            .map(Timestamp)
+            .map_err(|e| format!("invalid timestamp: {e}"))
+    }
+}
+
+#[test]
+fn custom_serialize_and_deserialize() {
+    use pretty_assertions::assert_eq;
+
+    let result = TimestampSettings::from_map(
+        &vec![("last_seen", "1700000000"), ("count", "3")]
+            .into_iter()
+            .collect(),
+    );
+    assert_eq!(result.deserialization_errors(), vec![]);
+    let mut settings = result.into_value();
+    assert_eq!(settings.last_seen(), &Timestamp(1700000000));
+    assert_eq!(settings.count(), 3);
+
+    settings.set_last_seen(Timestamp(42));
+    assert_eq!(
+        settings.perstruct_get_changes().unwrap(),
+        vec![("last_seen", "42".to_string())]
+    );
+
+    let result =
+        TimestampSettings::from_map(&vec![("last_seen", "\"yesterday\"")].into_iter().collect());
+    assert_eq!(result.value().last_seen(), &Timestamp::default());
+    assert_eq!(
+        result.deserialization_errors(),
+        vec![PerstructFieldError {
+            key: "last_seen",
+            message: "invalid timestamp: invalid digit found in string".to_string(),
+            line: 0,
+            column: 0,
+        }]
+    );
+}
+
+#[test]
+fn with_module() {
+    use pretty_assertions::assert_eq;
+
+    let result = NetworkSettings::from_map(&vec![("subnet", "10.0.0.0/8")].into_iter().collect());
+    assert_eq!(result.deserialization_errors(), vec![]);
+    let mut settings = result.into_value();
+    assert_eq!(
+        settings.subnet(),
+        &Cidr {
+            address: std::net::Ipv4Addr::new(10, 0, 0, 0),
+            prefix_len: 8,
+        }
+    );
+    settings.perstruct_saved();
+    settings.update_subnet(|subnet| subnet.prefix_len = 16);
+    assert_eq!(
+        settings.perstruct_get_changes().unwrap(),
+        vec![("subnet", "10.0.0.0/16".to_string())]
+    );
+
+    let result = NetworkSettings::from_map(&vec![("subnet", "10.0.0.0")].into_iter().collect());
+    assert_eq!(
+        result.deserialization_errors(),
+        vec![PerstructFieldError {
+            key: "subnet",
+            message: "missing prefix length".to_string(),
+            line: 0,
+            column: 0,
+        }]
+    );
+}
+
+#[test]
+fn display_fromstr() {
+    use pretty_assertions::assert_eq;
+
+    let result = ServerSettings::from_map(&vec![("listen", "0.0.0.0:443")].into_iter().collect());
+    assert_eq!(result.deserialization_errors(), vec![]);
+    let mut settings = result.into_value();
+    assert_eq!(
+        settings.listen(),
+        &std::net::SocketAddr::from(([0, 0, 0, 0], 443))
+    );
+    settings.set_listen("[::1]:8443".parse().unwrap());
+    let changes = settings.perstruct_get_changes().unwrap();
+    assert_eq!(changes, vec![("listen", "[::1]:8443".to_string())]);
+
+    let reloaded = ServerSettings::from_map(&changes.into_iter().collect());
+    assert_eq!(reloaded.deserialization_errors(), vec![]);
+    assert_eq!(reloaded.value().listen(), settings.listen());
+
+    let result =
+        ServerSettings::from_map(&vec![("listen", "\"0.0.0.0:443\"")].into_iter().collect());
+    assert_eq!(result.value().listen(), &default_listen());
+    assert_eq!(
+        result.deserialization_errors(),
+        vec![PerstructFieldError {
+            key: "listen",
+            message: "invalid socket address syntax".to_string(),
+            line: 0,
+            column: 0,
+        }]
+    );
+}
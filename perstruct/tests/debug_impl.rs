@@ -0,0 +1,76 @@
+use perstruct::perstruct;
+
+#[perstruct(debug)]
+struct WindowSettings {
+    #[perstruct(key = "window-width", default = 800)]
+    width: u32,
+    #[perstruct(skip)]
+    focused: bool,
+    title: String,
+}
+
+#[test]
+fn debug_shows_declared_fields_only() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = WindowSettings::default();
+    settings.set_width(1024);
+    settings.focused = true;
+    assert_eq!(
+        format!("{settings:?}"),
+        r#"WindowSettings { width: 1024, focused: true, title: "" }"#
+    );
+}
+
+#[perstruct(debug)]
+struct Wrapper<T> {
+    value: T,
+}
+
+#[test]
+fn debug_of_generic_struct() {
+    use pretty_assertions::assert_eq;
+
+    assert_eq!(
+        format!("{:?}", Wrapper::<Vec<u8>>::default()),
+        "Wrapper { value: [] }"
+    );
+}
+
+#[perstruct(debug)]
+struct ApiSettings {
+    endpoint: String,
+    #[perstruct(secret)]
+    token: String,
+}
+
+#[test]
+fn secret_fields_are_redacted() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = ApiSettings::default();
+    settings.set_endpoint("https://example.com".to_string());
+    settings.set_token("hunter2".to_string());
+    assert_eq!(
+        format!("{settings:?}"),
+        r#"ApiSettings { endpoint: "https://example.com", token: "<redacted>" }"#
+    );
+    assert_eq!(ApiSettings::perstruct_redacted_keys(), vec!["token"]);
+    assert!(ApiSettings::TOKEN.is_secret());
+    assert!(!ApiSettings::ENDPOINT.is_secret());
+    assert_eq!(
+        WindowSettings::perstruct_redacted_keys(),
+        Vec::<&str>::new()
+    );
+
+    // Secrets are still persisted
+    let mut changes = settings.perstruct_get_changes().unwrap();
+    changes.sort();
+    assert_eq!(
+        changes,
+        vec![
+            ("endpoint", "\"https://example.com\"".to_string()),
+            ("token", "\"hunter2\"".to_string())
+        ]
+    );
+}
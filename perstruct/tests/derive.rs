@@ -0,0 +1,41 @@
+use std::collections::HashSet;
+
+use perstruct::{DirtyFields, Perstruct};
+
+#[derive(Perstruct)]
+#[perstruct(into_setters)]
+struct DerivedSettings {
+    #[perstruct(key = "ui.theme")]
+    theme: String,
+    #[perstruct(default = 3)]
+    retries: u32,
+    #[perstruct(skip)]
+    cache: Vec<u8>,
+    _perstruct_dirty_fields: DirtyFields,
+}
+
+#[test]
+fn derive_generates_the_same_api() {
+    use pretty_assertions::assert_eq;
+
+    let result = DerivedSettings::from_map(&vec![("ui.theme", "\"dark\"")].into_iter().collect());
+    let mut settings = result.into_value();
+    assert_eq!(settings.theme(), "dark");
+    assert_eq!(settings.retries(), 3);
+    assert!(settings.cache.is_empty());
+    assert_eq!(
+        settings.perstruct_dirty_fields(),
+        &vec!["retries"].into_iter().collect::<HashSet<_>>()
+    );
+
+    settings.perstruct_saved();
+    settings.set_theme("light");
+    assert_eq!(
+        settings.perstruct_get_changes().unwrap(),
+        vec![("ui.theme", "\"light\"".to_string())]
+    );
+    assert_eq!(
+        DerivedSettings::perstruct_keys(),
+        vec!["ui.theme", "retries"]
+    );
+}
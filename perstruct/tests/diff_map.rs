@@ -0,0 +1,63 @@
+use perstruct::{perstruct, PerstructMapDiff};
+
+#[perstruct]
+struct SyncedSettings {
+    #[perstruct(default = 1)]
+    a: i32,
+    #[perstruct(default = 2)]
+    b: i32,
+    #[perstruct(default = 3)]
+    c: i32,
+}
+
+#[test]
+fn diff_against_stored_map() {
+    use pretty_assertions::assert_eq;
+
+    let stored = vec![("a", "1"), ("b", "5"), ("old", "true"), ("older", "null")]
+        .into_iter()
+        .collect();
+    let settings = SyncedSettings::default();
+    assert_eq!(
+        settings.perstruct_diff_map(&stored).unwrap(),
+        PerstructMapDiff {
+            inserts: vec![("c", "3".to_string())],
+            updates: vec![("b", "2".to_string())],
+            deletes: vec!["old".to_string(), "older".to_string()],
+        }
+    );
+}
+
+#[test]
+fn diff_between_instances() {
+    use pretty_assertions::assert_eq;
+
+    let before = SyncedSettings::default();
+    let mut after = SyncedSettings::default();
+    after.set_a(4);
+    after.set_c(6);
+    after.set_c(3);
+    after.perstruct_saved();
+    after.set_b(2);
+    assert_eq!(
+        after.perstruct_diff(&before).unwrap(),
+        vec![("a", "4".to_string())]
+    );
+    assert_eq!(
+        before.perstruct_diff(&after).unwrap(),
+        vec![("a", "1".to_string())]
+    );
+    assert!(after.perstruct_diff(&after).unwrap().is_empty());
+}
+
+#[test]
+fn diff_against_identical_map_is_empty() {
+    let stored = vec![("a", "1"), ("b", "2"), ("c", "3")]
+        .into_iter()
+        .collect();
+    let settings = SyncedSettings::default();
+    assert_eq!(
+        settings.perstruct_diff_map(&stored).unwrap(),
+        PerstructMapDiff::default()
+    );
+}
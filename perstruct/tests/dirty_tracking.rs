@@ -0,0 +1,89 @@
+use std::collections::HashSet;
+
+use perstruct::perstruct;
+
+#[perstruct(dirty = "vec")]
+struct VecTracked {
+    a: i32,
+    #[perstruct(key = "bee")]
+    b: i32,
+    c: String,
+}
+
+#[perstruct(dirty = "bitset", key_fn = "prefixed")]
+struct BitsetTracked {
+    a: i32,
+    #[perstruct(key = "bee")]
+    b: i32,
+    c: String,
+}
+
+fn prefixed(key: &'static str) -> &'static str {
+    Box::leak(format!("x.{key}").into_boxed_str())
+}
+
+#[test]
+fn vec_tracking() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = VecTracked::from_map(&vec![("a", "1")].into_iter().collect()).into_value();
+    assert_eq!(
+        settings.perstruct_dirty_fields(),
+        ["bee", "c"].into_iter().collect::<HashSet<_>>()
+    );
+    settings.perstruct_saved();
+    assert!(settings.perstruct_dirty_fields().is_empty());
+    settings.set_a(2);
+    settings.set_a(3);
+    assert_eq!(
+        settings.perstruct_get_changes().unwrap(),
+        vec![("a", "3".to_string())]
+    );
+}
+
+#[test]
+fn bitset_tracking() {
+    use pretty_assertions::assert_eq;
+
+    assert_eq!(
+        std::mem::size_of::<BitsetTracked>(),
+        std::mem::size_of::<(i32, i32, String, u128, &[&str])>()
+    );
+    let mut settings =
+        BitsetTracked::from_map(&vec![("x.bee", "1")].into_iter().collect()).into_value();
+    assert_eq!(
+        settings.perstruct_dirty_fields(),
+        ["x.a", "x.c"].into_iter().collect::<HashSet<_>>()
+    );
+    settings.perstruct_saved();
+    settings.set_b(5);
+    assert_eq!(
+        settings.perstruct_get_changes().unwrap(),
+        vec![("x.bee", "5".to_string())]
+    );
+    let settings = BitsetTracked::default();
+    assert!(settings.perstruct_dirty_fields().is_empty());
+}
+
+#[test]
+fn clear_dirty_acknowledges_single_fields() {
+    use pretty_assertions::assert_eq;
+
+    let empty = std::collections::HashMap::<&str, &str>::new();
+    let mut settings = VecTracked::from_map(&empty).into_value();
+    assert!(settings.perstruct_clear_dirty("bee"));
+    assert!(!settings.perstruct_clear_dirty("bee"));
+    assert!(!settings.perstruct_clear_dirty("unknown"));
+    assert_eq!(
+        settings.perstruct_dirty_fields(),
+        ["a", "c"].into_iter().collect::<HashSet<_>>()
+    );
+
+    let mut settings = BitsetTracked::from_map(&empty).into_value();
+    assert!(settings.perstruct_clear_dirty("x.a"));
+    assert!(!settings.perstruct_clear_dirty("a"));
+    assert_eq!(
+        settings.perstruct_dirty_fields(),
+        ["x.bee", "x.c"].into_iter().collect::<HashSet<_>>()
+    );
+}
@@ -0,0 +1,44 @@
+use perstruct::{perstruct, DynAccess, DynAccessError};
+
+#[perstruct]
+struct ScriptedSettings {
+    #[perstruct(key = "ui.zoom", default = 100)]
+    zoom: u32,
+    title: String,
+}
+
+#[test]
+fn access_through_trait_object() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings: Box<dyn DynAccess> = Box::new(ScriptedSettings::default());
+    assert_eq!(settings.get_json("ui.zoom"), Some(Ok("100".to_string())));
+    assert_eq!(settings.get_json("zoom"), None);
+
+    settings.set_json("title", "\"Hello\"").unwrap();
+    assert_eq!(
+        settings.get_json("title"),
+        Some(Ok("\"Hello\"".to_string()))
+    );
+    assert_eq!(
+        settings.set_json("missing", "1"),
+        Err(DynAccessError::UnknownKey("missing".to_string()))
+    );
+    let Err(DynAccessError::Deserialize(error)) = settings.set_json("ui.zoom", "\"big\"") else {
+        panic!("expected a deserialization error");
+    };
+    assert_eq!(error.key, "ui.zoom");
+}
+
+#[test]
+fn set_json_marks_dirty_like_setters() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = ScriptedSettings::default();
+    settings.perstruct_set_by_key("ui.zoom", "150").unwrap();
+    assert_eq!(settings.zoom(), 150);
+    assert_eq!(
+        settings.perstruct_get_changes().unwrap(),
+        vec![("ui.zoom", "150".to_string())]
+    );
+}
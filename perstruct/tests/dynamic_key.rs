@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+
+use perstruct::{perstruct, PerstructFieldError};
+
+#[perstruct]
+struct WindowSettings {
+    #[perstruct(dynamic_key)]
+    window_geometry: HashMap<String, (u32, u32)>,
+    #[perstruct(dynamic_key, key = "zoom")]
+    zoom_levels: HashMap<String, f32>,
+    title: String,
+}
+
+#[test]
+fn dynamic_rows_are_loaded_into_the_map() {
+    use pretty_assertions::assert_eq;
+
+    let map = HashMap::from([
+        ("window_geometry.DP-1", "[1920,1080]"),
+        ("window_geometry.HDMI-2", "[1280,720]"),
+        ("window_geometry.broken", "\"wide\""),
+        ("zoom.main", "1.5"),
+        ("title", "\"Editor\""),
+        ("window_geometry", "[1,1]"),
+    ]);
+    let result = WindowSettings::from_map(&map);
+    assert_eq!(
+        result.deserialization_errors(),
+        vec![PerstructFieldError {
+            key: "window_geometry",
+            message: "invalid type: string \"wide\", expected a tuple of size 2 at line 1 column 6"
+                .to_string(),
+            line: 1,
+            column: 6,
+        }]
+    );
+    assert_eq!(result.unknown_fields(), vec!["window_geometry".to_string()]);
+    let settings = result.into_value();
+    assert_eq!(
+        settings.window_geometry(),
+        &HashMap::from([
+            ("DP-1".to_string(), (1920, 1080)),
+            ("HDMI-2".to_string(), (1280, 720)),
+        ])
+    );
+    assert_eq!(settings.window_geometry_for("DP-1"), Some(&(1920, 1080)));
+    assert_eq!(settings.zoom_levels_for("main"), Some(&1.5));
+    assert_eq!(settings.perstruct_get_dynamic_changes().unwrap(), vec![]);
+}
+
+#[test]
+fn changes_write_and_delete_rows() {
+    use pretty_assertions::assert_eq;
+
+    let map = HashMap::from([
+        ("window_geometry.DP-1", "[1920,1080]"),
+        ("window_geometry.HDMI-2", "[1280,720]"),
+    ]);
+    let mut settings = WindowSettings::from_map(&map).into_value();
+    settings.set_window_geometry_for("DP-1", (2560, 1440));
+    settings.set_window_geometry_for("eDP-1", (1280, 800));
+    assert_eq!(
+        settings.remove_window_geometry_for("HDMI-2"),
+        Some((1280, 720))
+    );
+    assert_eq!(
+        settings.perstruct_get_dynamic_changes().unwrap(),
+        vec![
+            (
+                "window_geometry.DP-1".to_string(),
+                Some("[2560,1440]".to_string())
+            ),
+            ("window_geometry.HDMI-2".to_string(), None),
+            (
+                "window_geometry.eDP-1".to_string(),
+                Some("[1280,800]".to_string())
+            ),
+        ]
+    );
+
+    settings.perstruct_saved();
+    assert_eq!(settings.perstruct_get_dynamic_changes().unwrap(), vec![]);
+
+    settings.perstruct_mark_all_dirty();
+    assert_eq!(settings.perstruct_get_dynamic_changes().unwrap().len(), 2);
+}
+
+#[test]
+fn suffixes_with_the_separator_are_escaped() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = WindowSettings::default();
+    settings.set_zoom_levels_for("v1.2%", 2.0);
+    assert_eq!(
+        settings.perstruct_get_dynamic_changes().unwrap(),
+        vec![("zoom.v1%2E2%25".to_string(), Some("2.0".to_string()))]
+    );
+
+    let map = settings.perstruct_to_hashmap().unwrap();
+    let loaded = WindowSettings::from_map(&map).into_value();
+    assert_eq!(loaded.zoom_levels_for("v1.2%"), Some(&2.0));
+
+    // Unescaped separators are no rows of the field
+    let map = HashMap::from([("zoom.a.b", "1.0")]);
+    let result = WindowSettings::from_map(&map);
+    assert_eq!(result.unknown_fields(), vec!["zoom.a.b".to_string()]);
+    assert_eq!(result.value().zoom_levels(), &HashMap::new());
+}
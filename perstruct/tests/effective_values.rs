@@ -0,0 +1,42 @@
+use perstruct::{perstruct, EffectiveValue};
+
+#[perstruct]
+struct Display {
+    #[perstruct(default = 100)]
+    brightness: i32,
+    #[perstruct(default_fn = "default_theme")]
+    theme: String,
+    #[perstruct(skip)]
+    _cache: Vec<u8>,
+}
+
+fn default_theme() -> String {
+    "dark".to_string()
+}
+
+#[test]
+fn effective_values_compare_current_and_default() {
+    use pretty_assertions::assert_eq;
+
+    let mut display = Display::default();
+    display.set_theme("light".to_string());
+    assert_eq!(
+        display.perstruct_effective_values().unwrap(),
+        vec![
+            EffectiveValue {
+                key: "brightness",
+                current: "100".to_string(),
+                default: "100".to_string(),
+                is_dirty: false,
+                differs_from_default: false,
+            },
+            EffectiveValue {
+                key: "theme",
+                current: "\"light\"".to_string(),
+                default: "\"dark\"".to_string(),
+                is_dirty: true,
+                differs_from_default: true,
+            },
+        ]
+    );
+}
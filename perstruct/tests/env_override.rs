@@ -0,0 +1,67 @@
+use perstruct::perstruct;
+
+#[perstruct]
+struct ServerSettings {
+    #[perstruct(env = "PERSTRUCT_TEST_PORT", default = 8080)]
+    port: u16,
+    #[perstruct(env = "PERSTRUCT_TEST_HOST", default = "localhost")]
+    host: String,
+    #[perstruct(env = "PERSTRUCT_TEST_UNSET")]
+    workers: u32,
+}
+
+#[perstruct(value_format = "plain")]
+struct PlainSettings {
+    #[perstruct(env = "PERSTRUCT_TEST_PLAIN_HOST", default = "localhost")]
+    host: String,
+    #[perstruct(env = "PERSTRUCT_TEST_PLAIN_BAD_PORT", default = 8080)]
+    port: u16,
+}
+
+#[test]
+fn environment_variables_beat_the_map() {
+    use pretty_assertions::assert_eq;
+
+    std::env::set_var("PERSTRUCT_TEST_PORT", "9000");
+    std::env::set_var("PERSTRUCT_TEST_HOST", "\"example.com\"");
+    let result = ServerSettings::from_map(
+        &vec![("port", "3000"), ("workers", "2")]
+            .into_iter()
+            .collect(),
+    );
+    assert_eq!(result.value().port(), 9000);
+    assert_eq!(result.value().host(), "example.com");
+    assert_eq!(result.value().workers(), 2);
+    assert_eq!(
+        result.env_overrides(),
+        vec![
+            ("port", "PERSTRUCT_TEST_PORT"),
+            ("host", "PERSTRUCT_TEST_HOST")
+        ]
+    );
+    // Fields overridden from the environment are not dirty
+    assert!(result.value().perstruct_dirty_fields().is_empty());
+}
+
+#[test]
+fn plain_values_and_parse_errors() {
+    use pretty_assertions::assert_eq;
+
+    std::env::set_var("PERSTRUCT_TEST_PLAIN_HOST", "example.com");
+    std::env::set_var("PERSTRUCT_TEST_PLAIN_BAD_PORT", "http");
+    let result = PlainSettings::from_map(&vec![("port", "3000")].into_iter().collect());
+    assert_eq!(result.value().host(), "example.com");
+    assert_eq!(result.value().port(), 3000);
+    assert_eq!(
+        result.env_overrides(),
+        vec![("host", "PERSTRUCT_TEST_PLAIN_HOST")]
+    );
+    assert_eq!(
+        result
+            .deserialization_errors()
+            .iter()
+            .map(|error| error.key)
+            .collect::<Vec<_>>(),
+        vec!["port (env PERSTRUCT_TEST_PLAIN_BAD_PORT)"]
+    );
+}
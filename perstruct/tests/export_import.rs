@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+
+use perstruct::{perstruct, ImportError};
+
+#[perstruct(version = 2, migrate_fn = "migrate_settings")]
+struct AudioSettings {
+    volume: u8,
+    #[perstruct(default = 1)]
+    zoom: u8,
+    device: String,
+}
+
+/// Version 1 stored the volume as `sound_level`.
+fn migrate_settings(found_version: u32, map: &mut HashMap<String, String>) {
+    if found_version < 2 {
+        if let Some(level) = map.remove("sound_level") {
+            map.insert("volume".to_string(), level);
+        }
+    }
+}
+
+#[perstruct]
+struct VideoSettings {
+    volume: u8,
+}
+
+#[test]
+fn export_round_trips() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = AudioSettings::default();
+    settings.set_volume(7);
+    settings.set_device("speakers".to_string());
+    let export = settings.perstruct_export().unwrap();
+    let envelope: serde_json::Value = serde_json::from_str(&export).unwrap();
+    assert_eq!(envelope["schema"], "AudioSettings");
+    assert_eq!(envelope["version"], 2);
+    assert!(envelope["exported_at"].as_u64().unwrap() > 0);
+    assert_eq!(
+        envelope["values"],
+        serde_json::json!({ "volume": 7, "zoom": 1, "device": "speakers" })
+    );
+
+    let mut imported = AudioSettings::default();
+    let report = imported.perstruct_import(&export).unwrap();
+    assert_eq!(report.applied, vec!["volume", "zoom", "device"]);
+    assert_eq!(report.exported_version, Some(2));
+    assert!(report.unknown.is_empty() && report.invalid.is_empty());
+    assert_eq!(imported.volume(), 7);
+    assert_eq!(imported.device(), "speakers");
+    assert!(imported.is_volume_dirty());
+}
+
+#[test]
+fn import_migrates_and_reports_skipped_keys() {
+    use pretty_assertions::assert_eq;
+
+    let export = r#"{
+        "schema": "AudioSettings",
+        "version": 1,
+        "exported_at": 1700000000,
+        "values": { "sound_level": 5, "zoom": "big", "theme": "dark" }
+    }"#;
+    let mut settings = AudioSettings::default();
+    let report = settings.perstruct_import(export).unwrap();
+    assert_eq!(settings.volume(), 5);
+    assert_eq!(settings.zoom(), 1);
+    assert_eq!(report.exported_version, Some(1));
+    assert_eq!(report.exported_at, 1700000000);
+    assert_eq!(report.applied, vec!["volume", "device"]);
+    assert_eq!(report.unknown, vec!["theme".to_string()]);
+    assert_eq!(report.invalid.len(), 1);
+    assert_eq!(report.invalid[0].key, "zoom");
+}
+
+#[test]
+fn import_of_other_struct_fails_without_changes() {
+    use pretty_assertions::assert_eq;
+
+    let mut video = VideoSettings::default();
+    video.set_volume(3);
+    let export = video.perstruct_export().unwrap();
+
+    let mut settings = AudioSettings::default();
+    assert_eq!(
+        settings.perstruct_import(&export),
+        Err(ImportError::SchemaMismatch {
+            expected: "AudioSettings",
+            found: "VideoSettings".to_string(),
+        })
+    );
+    assert_eq!(settings.volume(), 0);
+    assert!(settings.perstruct_dirty_fields().is_empty());
+
+    let newer = r#"{"schema": "AudioSettings", "version": 3, "exported_at": 0, "values": {}}"#;
+    assert_eq!(
+        settings.perstruct_import(newer),
+        Err(ImportError::UnsupportedVersion {
+            supported: 2,
+            found: 3
+        })
+    );
+    assert!(matches!(
+        settings.perstruct_import("[]"),
+        Err(ImportError::Malformed(_))
+    ));
+}
@@ -0,0 +1,37 @@
+use perstruct::{perstruct, FeedOutcome};
+
+#[perstruct]
+struct StreamedSettings {
+    #[perstruct(alias = "level")]
+    volume: u8,
+    muted: bool,
+}
+
+#[test]
+fn feeds_stream_entries() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = StreamedSettings::default();
+    settings.set_volume(3);
+    settings.set_muted(true);
+
+    let stream = [("level", "7"), ("muted", "nope"), ("theme", "\"dark\"")];
+    let outcomes = stream
+        .iter()
+        .map(|(key, value)| settings.perstruct_feed(key, value))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        outcomes,
+        vec![
+            FeedOutcome::Applied,
+            FeedOutcome::DeserError("expected ident at line 1 column 2".to_string()),
+            FeedOutcome::Unknown,
+        ]
+    );
+    assert_eq!(settings.volume(), 7);
+    assert!(settings.muted());
+    assert_eq!(
+        settings.perstruct_get_changes().unwrap(),
+        vec![("muted", "true".to_string())]
+    );
+}
@@ -0,0 +1,34 @@
+use perstruct::{perstruct, Field};
+
+#[perstruct]
+struct EditorSettings {
+    #[perstruct(key = "editor.tab_width", default = 4)]
+    tab_width: u8,
+    font: String,
+}
+
+fn bump<S>(field: Field<S, u8>, settings: &mut S) -> &'static str {
+    let value = *field.get(settings);
+    field.set(settings, value + 1);
+    field.key()
+}
+
+#[test]
+fn field_handles_access_fields_generically() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = EditorSettings::default();
+    assert_eq!(
+        bump(EditorSettings::TAB_WIDTH, &mut settings),
+        "editor.tab_width"
+    );
+    assert_eq!(settings.tab_width(), 5);
+    assert!(settings.is_tab_width_dirty());
+
+    let font = EditorSettings::FONT;
+    let copy = font;
+    copy.set(&mut settings, "mono".to_string());
+    assert_eq!(font.get(&settings), "mono");
+    assert_eq!(font.key(), "font");
+    assert_eq!(settings.perstruct_get_changes().unwrap().len(), 2);
+}
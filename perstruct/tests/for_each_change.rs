@@ -0,0 +1,58 @@
+use perstruct::perstruct;
+
+#[perstruct]
+struct Settings {
+    volume: u8,
+    name: String,
+    #[perstruct(serialize_with = "fail", deserialize_with = "parse")]
+    broken: u8,
+}
+
+fn fail(_value: &u8) -> Result<String, String> {
+    Err("can't serialize".to_string())
+}
+
+fn parse(value: &str) -> Result<u8, String> {
+    value.parse().map_err(|_| "invalid".to_string())
+}
+
+#[test]
+fn streams_the_changes() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = Settings::default();
+    settings.set_volume(3);
+    settings.set_name("a".to_string());
+    let mut written = vec![];
+    settings
+        .perstruct_for_each_change(|key, value| {
+            written.push((key, value));
+            Ok(())
+        })
+        .unwrap();
+    written.sort();
+    let mut changes = settings.perstruct_get_changes().unwrap();
+    changes.sort();
+    assert_eq!(written, changes);
+}
+
+#[test]
+fn stops_at_the_first_error() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = Settings::default();
+    settings.set_volume(3);
+    settings.set_name("a".to_string());
+    let mut calls = 0;
+    let result = settings.perstruct_for_each_change(|_, _| {
+        calls += 1;
+        Err("transaction failed".to_string())
+    });
+    assert_eq!(result, Err("transaction failed".to_string()));
+    assert_eq!(calls, 1);
+
+    settings.perstruct_saved();
+    settings.set_broken(1);
+    let result = settings.perstruct_for_each_change(|_, _| Ok(()));
+    assert_eq!(result, Err("can't serialize".to_string()));
+}
@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+
+use perstruct::{perstruct, Perstruct};
+use serde_derive::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+struct Payload {
+    level: u8,
+}
+
+#[perstruct]
+struct Settings<T: Clone> {
+    #[perstruct(default = 2)]
+    volume: u8,
+    payload: T,
+    history: Vec<T>,
+}
+
+/// Without `Default` on `T`, the bounds are given explicitly.
+#[perstruct(bound = "T: serde::Serialize + serde::de::DeserializeOwned")]
+struct Wrapper<T> {
+    items: Vec<T>,
+}
+
+#[perstruct(key_fn = "prefixed")]
+struct Keyed<T>
+where
+    T: Clone,
+{
+    value: T,
+}
+
+fn prefixed(key: &'static str) -> &'static str {
+    Box::leak(format!("k.{key}").into_boxed_str())
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+struct NoDefault(u8);
+
+#[test]
+fn generic_struct_round_trip() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = Settings::<Payload>::default();
+    settings.set_payload(Payload { level: 3 });
+    assert_eq!(
+        settings.perstruct_get_changes().unwrap(),
+        vec![("payload", r#"{"level":3}"#.to_string())]
+    );
+
+    let map = HashMap::from([
+        ("payload", r#"{"level":4}"#),
+        ("history", r#"[{"level":1}]"#),
+    ]);
+    let settings = Settings::<Payload>::from_map(&map).into_value();
+    assert_eq!(settings.payload(), &Payload { level: 4 });
+    assert_eq!(settings.history(), &vec![Payload { level: 1 }]);
+    assert_eq!(settings.volume(), 2);
+
+    let loaded = Settings::<u32>::loader()
+        .set_payload(7)
+        .apply_map(&HashMap::<String, String>::new())
+        .into_value();
+    assert_eq!(loaded.payload(), &7);
+}
+
+#[test]
+fn generic_struct_implements_the_traits() {
+    use pretty_assertions::assert_eq;
+
+    fn keys<T: Perstruct>() -> &'static [&'static str] {
+        T::keys()
+    }
+
+    assert_eq!(keys::<Settings<String>>(), ["volume", "payload", "history"]);
+    assert_eq!(keys::<Keyed<u8>>(), ["k.value"]);
+    let mut erased: Vec<Box<dyn perstruct::ErasedPerstruct>> = vec![
+        Box::new(Settings::<String>::default()),
+        Box::new(Keyed::<u8>::default()),
+    ];
+    for settings in &mut erased {
+        settings.apply_entry("payload", "\"x\"").unwrap();
+    }
+}
+
+#[test]
+fn custom_bounds() {
+    use pretty_assertions::assert_eq;
+
+    let mut wrapper = Wrapper::<NoDefault>::default();
+    wrapper.set_items(vec![NoDefault(1)]);
+    assert_eq!(
+        wrapper.perstruct_get_changes().unwrap(),
+        vec![("items", "[1]".to_string())]
+    );
+}
@@ -0,0 +1,51 @@
+#![cfg(feature = "intern")]
+
+use perstruct::perstruct;
+
+#[perstruct]
+struct NetworkSettings {
+    timeout: u32,
+    enabled: bool,
+}
+
+#[perstruct(prefix = "ui.")]
+struct UiSettings {
+    #[perstruct(key = "timeout")]
+    ui_timeout: u32,
+    enabled: bool,
+}
+
+#[perstruct(key_fn = "strip_ui")]
+struct LegacySettings {
+    #[perstruct(key = "ui.enabled")]
+    enabled: bool,
+}
+
+fn strip_ui(key: &'static str) -> &'static str {
+    key.trim_start_matches("ui.")
+}
+
+#[test]
+fn equal_keys_are_shared() {
+    use pretty_assertions::assert_eq;
+
+    let network = NetworkSettings::perstruct_keys();
+    let ui = UiSettings::perstruct_keys();
+    let legacy = LegacySettings::perstruct_keys();
+    assert_eq!(network, vec!["timeout", "enabled"]);
+    assert_eq!(ui, vec!["ui.timeout", "ui.enabled"]);
+    assert!(std::ptr::eq(network[1], legacy[0]));
+    assert!(!std::ptr::eq(network[0], ui[0]));
+    assert!(std::ptr::eq(perstruct::intern("timeout"), network[0]));
+}
+
+#[test]
+fn changes_use_the_interned_keys() {
+    let mut settings = NetworkSettings::default();
+    settings.set_enabled(true);
+    let changes = settings.perstruct_get_changes().unwrap();
+    assert!(std::ptr::eq(
+        changes[0].0,
+        LegacySettings::perstruct_keys()[0]
+    ));
+}
@@ -0,0 +1,31 @@
+use std::path::PathBuf;
+
+use perstruct::perstruct;
+
+#[perstruct(into_setters)]
+struct IntoSettings {
+    name: String,
+    path: PathBuf,
+}
+
+#[perstruct]
+struct FieldIntoSettings {
+    #[perstruct(into_setter)]
+    name: String,
+    count: u64,
+}
+
+#[test]
+fn set_string_from_str() {
+    let mut settings = IntoSettings::default();
+    settings.set_name("alice");
+    settings.set_path("/tmp");
+    assert_eq!(settings.name(), "alice");
+    assert_eq!(settings.path(), &PathBuf::from("/tmp"));
+
+    let mut settings = FieldIntoSettings::default();
+    settings.set_name("bob");
+    settings.set_count(3);
+    assert_eq!(settings.name(), "bob");
+    assert_eq!(settings.count(), 3);
+}
@@ -0,0 +1,64 @@
+use perstruct::perstruct;
+
+#[perstruct]
+struct ExportedSettings {
+    #[perstruct(key = "ui.theme")]
+    theme: String,
+    #[perstruct(default = 12)]
+    font_size: u8,
+    #[perstruct(skip)]
+    cache: Vec<u8>,
+}
+
+#[test]
+fn to_json_string() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = ExportedSettings::default();
+    settings.set_theme("dark".to_string());
+    assert_eq!(
+        settings.perstruct_to_json_string().unwrap(),
+        r#"{"font_size":12,"ui.theme":"dark"}"#
+    );
+    assert_eq!(
+        settings.perstruct_to_json_string_pretty().unwrap(),
+        "{\n  \"font_size\": 12,\n  \"ui.theme\": \"dark\"\n}"
+    );
+    assert!(settings.cache.is_empty());
+}
+
+#[test]
+fn from_json_str_round_trip() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = ExportedSettings::default();
+    settings.set_theme("dark".to_string());
+    settings.set_font_size(14);
+    let json = settings.perstruct_to_json_string().unwrap();
+
+    let result = ExportedSettings::from_json_str(&json);
+    assert!(result.deserialization_errors().is_empty());
+    assert!(result.unknown_fields().is_empty());
+    assert!(result.value().perstruct_dirty_fields().is_empty());
+    assert_eq!(result.value().theme(), "dark");
+    assert_eq!(result.value().font_size(), 14);
+    assert_eq!(result.value().perstruct_to_json_string().unwrap(), json);
+}
+
+#[test]
+fn from_json_str_reports_errors() {
+    use pretty_assertions::assert_eq;
+
+    let result = ExportedSettings::from_json_str(r#"{"font_size":"big","legacy":1}"#);
+    assert_eq!(result.unknown_fields(), vec!["legacy".to_string()]);
+    assert_eq!(result.deserialization_errors().len(), 1);
+    assert_eq!(result.deserialization_errors()[0].key, "font_size");
+    assert_eq!(result.value().font_size(), 12);
+
+    for json in ["[1, 2]", "12", "not json"] {
+        let result = ExportedSettings::from_json_str(json);
+        assert_eq!(result.deserialization_errors().len(), 1);
+        assert_eq!(result.deserialization_errors()[0].key, "");
+        assert_eq!(result.value().font_size(), 12);
+    }
+}
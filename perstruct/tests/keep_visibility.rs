@@ -0,0 +1,45 @@
+mod settings {
+    use perstruct::perstruct;
+
+    #[perstruct(keep_visibility)]
+    pub struct Settings {
+        pub(crate) volume: u8,
+        pub name: String,
+        hidden: bool,
+    }
+
+    pub fn built_with_struct_literal() -> Settings {
+        Settings {
+            volume: 4,
+            hidden: true,
+            ..Default::default()
+        }
+    }
+}
+
+use settings::Settings;
+
+#[test]
+fn fields_keep_their_visibility() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = settings::built_with_struct_literal();
+    assert_eq!(settings.volume, 4);
+    settings.name = "direct".to_string();
+    assert!(settings.perstruct_get_changes().unwrap().is_empty());
+    assert!(settings.hidden());
+}
+
+#[test]
+fn direct_writes_can_be_marked_dirty() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = Settings::default();
+    settings.volume = 7;
+    assert!(settings.perstruct_mark_dirty("volume"));
+    assert!(!settings.perstruct_mark_dirty("unknown"));
+    assert_eq!(
+        settings.perstruct_get_changes().unwrap(),
+        vec![("volume", "7".to_string())]
+    );
+}
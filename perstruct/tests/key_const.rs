@@ -0,0 +1,42 @@
+use perstruct::perstruct;
+
+mod keys {
+    pub const TIMEOUT_KEY: &str = "net.timeout";
+}
+
+#[perstruct]
+struct NetworkSettings {
+    #[perstruct(key_const = "crate::keys::TIMEOUT_KEY", default = 30)]
+    timeout: u32,
+    retries: u32,
+}
+
+#[test]
+fn key_const_is_used_everywhere() {
+    use pretty_assertions::assert_eq;
+
+    assert_eq!(
+        NetworkSettings::perstruct_keys(),
+        vec![keys::TIMEOUT_KEY, "retries"]
+    );
+
+    let result = NetworkSettings::from_map(
+        &vec![
+            (keys::TIMEOUT_KEY, "10"),
+            ("retries", "2"),
+            ("timeout", "5"),
+        ]
+        .into_iter()
+        .collect(),
+    );
+    assert_eq!(result.unknown_fields(), vec!["timeout".to_string()]);
+    let mut settings = result.into_value();
+    assert_eq!(settings.timeout(), 10);
+    assert_eq!(settings.retries(), 2);
+
+    settings.set_timeout(60);
+    assert_eq!(
+        settings.perstruct_get_changes().unwrap(),
+        vec![("net.timeout", "60".to_string())]
+    );
+}
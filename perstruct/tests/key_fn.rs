@@ -0,0 +1,49 @@
+use perstruct::perstruct;
+
+#[perstruct(key_fn = "mangle")]
+struct MangledSettings {
+    volume: i32,
+    #[perstruct(key = "theme_name")]
+    theme: String,
+}
+
+fn mangle(key: &'static str) -> &'static str {
+    Box::leak(format!("app:{key}").into_boxed_str())
+}
+
+#[test]
+fn key_fn_is_applied_consistently() {
+    use pretty_assertions::assert_eq;
+
+    assert_eq!(
+        MangledSettings::perstruct_keys(),
+        vec!["app:volume", "app:theme_name"]
+    );
+
+    let result = MangledSettings::from_map(
+        &vec![("app:volume", "7"), ("volume", "8")]
+            .into_iter()
+            .collect(),
+    );
+    assert_eq!(result.unknown_fields(), vec!["volume".to_string()]);
+    let mut settings = result.into_value();
+    assert_eq!(settings.volume(), 7);
+
+    let mut changes = settings.perstruct_get_changes().unwrap();
+    assert_eq!(changes, vec![("app:theme_name", "\"\"".to_string())]);
+
+    settings.set_volume(9);
+    changes = settings.perstruct_get_changes().unwrap();
+    changes.sort();
+    assert_eq!(
+        changes,
+        vec![
+            ("app:theme_name", "\"\"".to_string()),
+            ("app:volume", "9".to_string())
+        ]
+    );
+
+    let reloaded = MangledSettings::from_map(&changes.into_iter().collect());
+    assert!(reloaded.unknown_fields().is_empty());
+    assert_eq!(reloaded.value().volume(), 9);
+}
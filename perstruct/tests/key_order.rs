@@ -0,0 +1,56 @@
+use perstruct::{perstruct, Perstruct};
+
+#[perstruct(dirty = "bitset")]
+struct ServerConfig {
+    #[perstruct(default = 8080)]
+    port: u16,
+    #[perstruct(order = 0)]
+    name: String,
+    #[perstruct(skip)]
+    connections: u32,
+    verbose: bool,
+    #[perstruct(order = 5)]
+    host: String,
+    #[perstruct(order = 1)]
+    timeout: u32,
+}
+
+#[test]
+fn keys_follow_the_order() {
+    use pretty_assertions::assert_eq;
+
+    let expected = ["name", "port", "timeout", "verbose", "host"];
+    assert_eq!(ServerConfig::perstruct_keys(), expected);
+    assert_eq!(<ServerConfig as Perstruct>::keys(), expected);
+}
+
+#[test]
+fn changes_follow_the_order() {
+    use pretty_assertions::assert_eq;
+
+    let mut config = ServerConfig::default();
+    config.perstruct_saved();
+    config.set_host("localhost".to_string());
+    config.set_verbose(true);
+    config.set_port(80);
+    config.set_name("main".to_string());
+    config.connections += 1;
+    assert_eq!(
+        config.perstruct_get_changes().unwrap(),
+        vec![
+            ("name", r#""main""#.to_string()),
+            ("port", "80".to_string()),
+            ("verbose", "true".to_string()),
+            ("host", r#""localhost""#.to_string()),
+        ]
+    );
+    assert_eq!(
+        config
+            .perstruct_effective_values()
+            .unwrap()
+            .into_iter()
+            .map(|value| value.key)
+            .collect::<Vec<_>>(),
+        ["name", "port", "timeout", "verbose", "host"]
+    );
+}
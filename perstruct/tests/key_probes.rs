@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+
+use perstruct::perstruct;
+
+#[perstruct(prefix = "app.")]
+struct Settings {
+    volume: u8,
+    #[perstruct(alias = "colour")]
+    color: String,
+    #[perstruct(key = "name")]
+    user_name: String,
+}
+
+#[perstruct(case_insensitive)]
+struct CaseInsensitiveSettings {
+    volume: u8,
+}
+
+#[test]
+fn present_keys_with_partial_overlap() {
+    use pretty_assertions::assert_eq;
+
+    let map = HashMap::from([
+        ("app.volume", "not even json"),
+        ("app.colour", "\"red\""),
+        ("volume", "3"),
+        ("other", "1"),
+    ]);
+    assert_eq!(
+        Settings::perstruct_present_keys(&map),
+        vec!["app.volume", "app.color"]
+    );
+    assert_eq!(
+        Settings::perstruct_keys_missing_from(&map),
+        vec!["app.name"]
+    );
+}
+
+#[test]
+fn any_key_present() {
+    assert!(Settings::perstruct_any_key_present(["other", "app.name"]));
+    assert!(Settings::perstruct_any_key_present(vec![
+        "app.colour".to_string()
+    ]));
+    assert!(!Settings::perstruct_any_key_present([
+        "volume",
+        "name",
+        "APP.VOLUME"
+    ]));
+    assert!(!Settings::perstruct_any_key_present(Vec::<&str>::new()));
+    assert!(CaseInsensitiveSettings::perstruct_any_key_present([
+        "VOLUME"
+    ]));
+}
@@ -0,0 +1,145 @@
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap};
+
+use perstruct::{perstruct, PerstructFieldError};
+
+#[perstruct]
+struct AppSettings {
+    #[perstruct(lazy, with = "counted")]
+    theme: Theme,
+    #[perstruct(lazy, default = 14)]
+    font_size: u32,
+    volume: u8,
+}
+
+type Theme = BTreeMap<String, String>;
+
+thread_local! {
+    static THEME_PARSES: Cell<usize> = const { Cell::new(0) };
+}
+
+fn theme_parses() -> usize {
+    THEME_PARSES.with(Cell::get)
+}
+
+mod counted {
+    use super::{Theme, THEME_PARSES};
+
+    pub fn serialize(value: &Theme) -> Result<String, String> {
+        serde_json::to_string(value).map_err(|e| e.to_string())
+    }
+
+    pub fn deserialize(value: &str) -> Result<Theme, String> {
+        THEME_PARSES.with(|parses| parses.set(parses.get() + 1));
+        serde_json::from_str(value).map_err(|e| e.to_string())
+    }
+}
+
+fn theme(entries: &[(&str, &str)]) -> Theme {
+    entries
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+#[test]
+fn lazy_fields_are_not_deserialized_when_loading() {
+    use pretty_assertions::assert_eq;
+
+    let map = HashMap::from([
+        ("theme", r#"{"background":"black"}"#),
+        ("font_size", "16"),
+        ("volume", "3"),
+    ]);
+    let parses = theme_parses();
+    let result = AppSettings::from_map(&map);
+    assert_eq!(result.deserialization_errors(), vec![]);
+    let settings = result.into_value();
+    assert_eq!(theme_parses(), parses);
+    assert!(!settings.theme.is_parsed());
+    assert_eq!(settings.volume(), 3);
+    assert_eq!(settings.perstruct_get_changes().unwrap(), vec![]);
+    assert_eq!(theme_parses(), parses);
+}
+
+#[test]
+fn lazy_fields_are_deserialized_once_on_first_access() {
+    use pretty_assertions::assert_eq;
+
+    let map = HashMap::from([("theme", r#"{"background":"black"}"#), ("font_size", "16")]);
+    let settings = AppSettings::from_map(&map).into_value();
+    let parses = theme_parses();
+    assert_eq!(settings.theme(), Ok(&theme(&[("background", "black")])));
+    assert_eq!(settings.theme(), Ok(&theme(&[("background", "black")])));
+    assert_eq!(theme_parses(), parses + 1);
+    assert!(settings.theme.is_parsed());
+    assert_eq!(settings.font_size(), Ok(&16));
+    assert!(!settings.is_theme_dirty());
+}
+
+#[test]
+fn lazy_fields_report_errors_on_access() {
+    use pretty_assertions::assert_eq;
+
+    let map = HashMap::from([("font_size", "\"large\"")]);
+    let result = AppSettings::from_map(&map);
+    assert_eq!(result.deserialization_errors(), vec![]);
+    let settings = result.into_value();
+    assert_eq!(
+        settings.font_size(),
+        Err(&PerstructFieldError {
+            key: "font_size",
+            message: "invalid type: string \"large\", expected u32 at line 1 column 7".to_string(),
+            line: 1,
+            column: 7,
+        })
+    );
+    // Like eagerly loaded fields, the default stands in for the value that can't be deserialized
+    assert_eq!(*settings.font_size, 14);
+}
+
+#[test]
+fn set_after_lazy_load_replaces_the_stored_string() {
+    use pretty_assertions::assert_eq;
+
+    let map = HashMap::from([
+        ("theme", r#"{"background":"black"}"#),
+        ("font_size", "nope"),
+        ("volume", "3"),
+    ]);
+    let mut settings = AppSettings::from_map(&map).into_value();
+    let parses = theme_parses();
+    settings.set_theme(theme(&[("background", "white")]));
+    settings.set_font_size(18);
+    assert_eq!(theme_parses(), parses);
+    assert_eq!(settings.theme(), Ok(&theme(&[("background", "white")])));
+    assert_eq!(settings.font_size(), Ok(&18));
+
+    let mut changes = settings.perstruct_get_changes().unwrap();
+    changes.sort();
+    assert_eq!(
+        changes,
+        vec![
+            ("font_size", "18".to_string()),
+            ("theme", r#"{"background":"white"}"#.to_string()),
+        ]
+    );
+
+    settings.update_theme(|theme| {
+        theme.insert("accent".to_string(), "blue".to_string());
+    });
+    assert_eq!(
+        settings.theme(),
+        Ok(&theme(&[("accent", "blue"), ("background", "white")]))
+    );
+}
+
+#[test]
+fn default_lazy_fields_are_parsed() {
+    use pretty_assertions::assert_eq;
+
+    let settings = AppSettings::default();
+    assert!(settings.theme.is_parsed());
+    assert_eq!(settings.theme(), Ok(&Theme::new()));
+    assert_eq!(settings.font_size(), Ok(&14));
+}
@@ -0,0 +1,29 @@
+use perstruct::perstruct;
+
+#[perstruct]
+struct SeededSettings {
+    a: i32,
+    #[perstruct(default = 2)]
+    b: i32,
+    name: String,
+}
+
+#[test]
+fn pre_seeded_values_win_over_the_map() {
+    use pretty_assertions::assert_eq;
+
+    let result = SeededSettings::loader().set_a(3).apply_map(
+        &vec![("a", "\"not a number\""), ("b", "4")]
+            .into_iter()
+            .collect(),
+    );
+    assert_eq!(result.deserialization_errors(), vec![]);
+    let settings = result.into_value();
+    assert_eq!(settings.a(), 3);
+    assert_eq!(settings.b(), 4);
+    assert_eq!(settings.name(), "");
+    assert_eq!(
+        settings.perstruct_get_changes().unwrap(),
+        vec![("name", "\"\"".to_string())]
+    );
+}
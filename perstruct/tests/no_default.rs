@@ -0,0 +1,100 @@
+use perstruct::perstruct;
+
+#[derive(PartialEq, Eq, Debug, serde_derive::Serialize, serde_derive::Deserialize)]
+struct AccountId(u64);
+
+#[perstruct]
+struct Account {
+    #[perstruct(no_default, alias = "id")]
+    account_id: AccountId,
+    #[perstruct(default = 3)]
+    retries: i32,
+    #[perstruct(skip)]
+    cache: Vec<u8>,
+}
+
+#[test]
+fn try_from_map_loads_required_fields() {
+    use pretty_assertions::assert_eq;
+
+    let result = Account::try_from_map(&vec![("id", "7")].into_iter().collect()).unwrap();
+    let mut account = result.into_value();
+    assert_eq!(account.account_id(), &AccountId(7));
+    assert_eq!(account.retries(), 3);
+    assert!(account.cache.is_empty());
+    // `account_id` was loaded from its alias, so it's rewritten under its key
+    let mut changes = account.perstruct_get_changes().unwrap();
+    changes.sort();
+    assert_eq!(
+        changes,
+        vec![
+            ("account_id", "7".to_string()),
+            ("retries", "3".to_string())
+        ]
+    );
+    account.perstruct_saved();
+    account.set_account_id(AccountId(8));
+    assert_eq!(
+        account.perstruct_get_changes().unwrap(),
+        vec![("account_id", "8".to_string())]
+    );
+}
+
+#[test]
+fn try_from_map_fails_for_missing_or_invalid_required_fields() {
+    use pretty_assertions::assert_eq;
+
+    let error = Account::try_from_map(&vec![("retries", "1")].into_iter().collect())
+        .err()
+        .unwrap();
+    assert_eq!(error.missing_fields, vec!["account_id"]);
+    assert_eq!(
+        error.to_string(),
+        "failed to load settings; missing keys: account_id"
+    );
+
+    let error = Account::try_from_map(&vec![("account_id", "\"x\"")].into_iter().collect())
+        .err()
+        .unwrap();
+    assert!(error.missing_fields.is_empty());
+    assert_eq!(error.deserialization_errors[0].key, "account_id");
+}
+
+#[perstruct]
+struct Session {
+    #[perstruct(no_default)]
+    token: String,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Session::try_from_map(&vec![("token", "\"anonymous\"")].into_iter().collect())
+            .unwrap()
+            .into_value()
+    }
+}
+
+#[test]
+fn from_map_uses_user_default_impl() {
+    use pretty_assertions::assert_eq;
+
+    let session =
+        Session::from_map(&std::collections::HashMap::<String, String>::new()).into_value();
+    assert_eq!(session.token(), "anonymous");
+    assert_eq!(session.perstruct_dirty_fields().len(), 1);
+}
+
+#[perstruct(case_insensitive)]
+struct LegacyAccount {
+    #[perstruct(no_default)]
+    account_id: u64,
+}
+
+#[test]
+fn try_from_map_ignores_the_case_of_required_keys() {
+    use pretty_assertions::assert_eq;
+
+    let result =
+        LegacyAccount::try_from_map(&vec![("ACCOUNT_ID", "7")].into_iter().collect()).unwrap();
+    assert_eq!(result.value().account_id(), 7);
+}
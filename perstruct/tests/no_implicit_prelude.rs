@@ -0,0 +1,133 @@
+#[no_implicit_prelude]
+mod settings {
+    #[::perstruct::perstruct(into_setters, debug, post_set = "on_set", view)]
+    pub struct AppSettings {
+        #[perstruct(key = "ui.theme", default_fn = "default_theme", alias = "theme")]
+        pub theme: ::std::string::String,
+        #[perstruct(default = 3)]
+        pub retries: u8,
+        #[perstruct(dedup, max_len = 2)]
+        pub recent: ::std::vec::Vec<::std::string::String>,
+        pub proxy: ::std::option::Option<::std::string::String>,
+        #[perstruct(skip)]
+        pub sets: u32,
+    }
+
+    fn default_theme() -> ::std::string::String {
+        ::std::borrow::ToOwned::to_owned("light")
+    }
+
+    fn on_set(settings: &mut AppSettings, _key: &'static str) {
+        settings.sets += 1;
+    }
+
+    #[::perstruct::perstruct(dirty = "bitset", rename_all = "kebab-case", serde)]
+    pub struct WindowSettings {
+        #[perstruct(no_default)]
+        pub window_width: u32,
+        #[perstruct(serialize_as = "::std::string::String")]
+        pub title: Title,
+    }
+
+    #[derive(Default, PartialEq, Debug)]
+    pub struct Title(pub ::std::string::String);
+
+    impl ::std::convert::From<&Title> for ::std::string::String {
+        fn from(title: &Title) -> Self {
+            ::std::clone::Clone::clone(&title.0)
+        }
+    }
+
+    impl ::std::convert::From<::std::string::String> for Title {
+        fn from(title: ::std::string::String) -> Self {
+            Title(title)
+        }
+    }
+
+    #[::perstruct::perstruct(preserve_unknown, version = 2, dirty = "vec")]
+    pub struct Wrapper<T> {
+        pub value: T,
+    }
+
+    #[::perstruct::perstruct(observe = "channel")]
+    pub struct Observed {
+        pub volume: u8,
+    }
+
+    #[derive(::perstruct::Perstruct)]
+    pub struct Derived {
+        pub enabled: bool,
+        pub _perstruct_dirty_fields: ::perstruct::DirtyFields,
+    }
+}
+
+mod shadowed {
+    #![allow(dead_code)]
+
+    struct Option;
+    struct Result;
+    struct String;
+    struct Vec;
+    trait Default {}
+
+    #[perstruct::perstruct]
+    pub struct ShadowedSettings {
+        #[perstruct(default = 2)]
+        pub level: u8,
+    }
+}
+
+use settings::{AppSettings, Derived, Observed, ObservedChange, WindowSettings, Wrapper};
+
+#[test]
+fn generated_code_does_not_need_the_prelude() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings =
+        AppSettings::from_map(&vec![("theme", "\"dark\"")].into_iter().collect()).into_value();
+    assert_eq!(settings.theme(), "dark");
+    settings.perstruct_saved();
+    settings.set_retries(5);
+    settings.set_recent(vec!["a".into(), "b".into(), "a".into()]);
+    assert_eq!(settings.sets, 2);
+    assert_eq!(settings.perstruct_view().retries, 5);
+    let mut changes = settings.perstruct_get_changes().unwrap();
+    changes.sort();
+    assert_eq!(
+        changes,
+        vec![
+            ("recent", r#"["a","b"]"#.to_string()),
+            ("retries", "5".to_string())
+        ]
+    );
+    assert_eq!(
+        format!("{settings:?}"),
+        r#"AppSettings { theme: "dark", retries: 5, recent: ["a", "b"], proxy: None, sets: 2 }"#
+    );
+
+    let window = WindowSettings::try_from_map(
+        &vec![("window-width", "800"), ("title", r#""main""#)]
+            .into_iter()
+            .collect(),
+    )
+    .unwrap()
+    .into_value();
+    assert_eq!(window.window_width(), 800);
+    assert_eq!(window.title(), &settings::Title("main".to_string()));
+
+    let wrapper = Wrapper::<u8>::from_map(&vec![("value", "4")].into_iter().collect()).into_value();
+    assert_eq!(wrapper.value(), &4);
+
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let mut observed = Observed::default();
+    observed.perstruct_set_observer(sender);
+    observed.set_volume(7);
+    let ObservedChange::Volume(volume) = receiver.try_recv().unwrap();
+    assert_eq!(volume, 7);
+
+    let derived = Derived::from_map(&vec![("enabled", "true")].into_iter().collect()).into_value();
+    assert!(derived.enabled());
+
+    let shadowed = shadowed::ShadowedSettings::default();
+    assert_eq!(shadowed.level(), 2);
+}
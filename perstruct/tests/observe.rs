@@ -0,0 +1,81 @@
+use std::sync::mpsc;
+
+use perstruct::perstruct;
+
+#[perstruct(observe = "channel")]
+struct PlayerSettings {
+    volume: u8,
+    #[perstruct(default_fn = "default_device")]
+    output_device: String,
+}
+
+fn default_device() -> String {
+    "speakers".to_string()
+}
+
+#[test]
+fn setters_send_typed_changes() {
+    use pretty_assertions::assert_eq;
+
+    let (sender, receiver) = mpsc::channel();
+    let mut settings = PlayerSettings::default();
+    settings.set_volume(3);
+    settings.perstruct_set_observer(sender);
+    settings.set_volume(5);
+    settings.update_output_device(|device| device.push_str("-2"));
+    settings.reset_output_device();
+    settings.perstruct_set_by_key("volume", "7").unwrap();
+
+    let changes = receiver.try_iter().collect::<Vec<_>>();
+    assert_eq!(changes.len(), 4);
+    assert!(matches!(changes[0], PlayerSettingsChange::Volume(5)));
+    assert!(
+        matches!(&changes[1], PlayerSettingsChange::OutputDevice(device) if device == "speakers-2")
+    );
+    assert!(
+        matches!(&changes[2], PlayerSettingsChange::OutputDevice(device) if device == "speakers")
+    );
+    assert!(matches!(changes[3], PlayerSettingsChange::Volume(7)));
+
+    drop(receiver);
+    settings.set_volume(1);
+    assert_eq!(settings.volume(), 1);
+}
+
+#[perstruct(observe = "callbacks")]
+struct MixerSettings {
+    gain: u8,
+    #[perstruct(key = "mixer.muted")]
+    muted: bool,
+}
+
+#[test]
+fn callbacks_get_the_changed_keys() {
+    use std::sync::{Arc, Mutex};
+
+    use pretty_assertions::assert_eq;
+
+    let keys = Arc::new(Mutex::new(vec![]));
+    let map = std::collections::HashMap::from([("gain", "3"), ("mixer.muted", "true")]);
+    let mut settings = MixerSettings::from_map(&map).into_value();
+    let seen = keys.clone();
+    settings.perstruct_on_change(Box::new(move |key| seen.lock().unwrap().push(key)));
+    let count = Arc::new(Mutex::new(0));
+    let calls = count.clone();
+    settings.perstruct_on_change(Box::new(move |_| *calls.lock().unwrap() += 1));
+
+    // Loading is not an edit
+    settings.perstruct_load_into(&map);
+    assert_eq!(*keys.lock().unwrap(), Vec::<&str>::new());
+
+    settings.set_gain(5);
+    settings.update_muted(|muted| *muted = !*muted);
+    settings.reset_gain();
+    settings.perstruct_set_by_key("gain", "9").unwrap();
+    assert_eq!(
+        *keys.lock().unwrap(),
+        vec!["gain", "mixer.muted", "gain", "gain"]
+    );
+    assert_eq!(*count.lock().unwrap(), 4);
+    assert!(settings.is_gain_dirty());
+}
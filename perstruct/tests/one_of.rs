@@ -0,0 +1,106 @@
+use perstruct::{perstruct, DynAccessError, PerstructFieldError};
+
+#[perstruct]
+struct AppearanceSettings {
+    #[perstruct(one_of("auto", "light", "dark"), default = "auto")]
+    theme: String,
+    #[perstruct(one_of("", "compact", "cozy"), on_invalid = "reject")]
+    density: String,
+}
+
+#[perstruct(serde)]
+struct SerdeSettings {
+    #[perstruct(one_of("auto", "light", "dark"), default = "auto")]
+    theme: String,
+}
+
+#[test]
+fn values_that_are_not_allowed_load_the_default() {
+    use pretty_assertions::assert_eq;
+
+    let result = AppearanceSettings::from_map(
+        &vec![("theme", "\"sepia\""), ("density", "\"cozy\"")]
+            .into_iter()
+            .collect(),
+    );
+    assert_eq!(result.value().theme(), "auto");
+    assert_eq!(result.value().density(), "cozy");
+    assert_eq!(
+        result.deserialization_errors(),
+        vec![PerstructFieldError {
+            key: "theme",
+            message: r#""sepia" is not one of "auto", "light", "dark""#.to_string(),
+            line: 0,
+            column: 0,
+        }]
+    );
+    assert!(result.value().is_theme_dirty());
+}
+
+#[test]
+fn setters_apply_the_policy() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = AppearanceSettings::default();
+    settings.set_theme("dark".to_string());
+    settings.set_density("compact".to_string());
+    settings.perstruct_saved();
+
+    // The default policy stores the default
+    settings.set_theme("sepia".to_string());
+    assert_eq!(settings.theme(), "auto");
+    assert!(settings.is_theme_dirty());
+    settings.update_theme(|theme| theme.push('!'));
+    assert_eq!(settings.theme(), "auto");
+
+    // `on_invalid = "reject"` keeps the field unchanged
+    settings.set_density("roomy".to_string());
+    settings.update_density(|density| density.push('!'));
+    assert_eq!(settings.density(), "compact");
+    assert!(!settings.is_density_dirty());
+}
+
+#[test]
+fn try_setters_return_errors() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = AppearanceSettings::default();
+    assert_eq!(settings.try_set_theme("light".to_string()), Ok(()));
+    assert_eq!(
+        settings.try_set_theme("sepia".to_string()),
+        Err(r#""sepia" is not one of "auto", "light", "dark""#.to_string())
+    );
+    assert_eq!(settings.theme(), "light");
+    assert_eq!(
+        settings.perstruct_set_by_key("density", "\"roomy\""),
+        Err(DynAccessError::Deserialize(PerstructFieldError {
+            key: "density",
+            message: r#""roomy" is not one of "", "compact", "cozy""#.to_string(),
+            line: 0,
+            column: 0,
+        }))
+    );
+}
+
+#[test]
+fn field_handles_expose_the_allowed_values() {
+    use pretty_assertions::assert_eq;
+
+    assert_eq!(
+        AppearanceSettings::THEME.allowed_values(),
+        Some(&["auto", "light", "dark"][..])
+    );
+    assert_eq!(
+        AppearanceSettings::DENSITY.allowed_values(),
+        Some(&["", "compact", "cozy"][..])
+    );
+}
+
+#[test]
+fn serde_treats_values_that_are_not_allowed_as_missing() {
+    use pretty_assertions::assert_eq;
+
+    let settings: SerdeSettings = serde_json::from_str(r#"{"theme":"sepia"}"#).unwrap();
+    assert_eq!(settings.theme(), "auto");
+    assert!(settings.is_theme_dirty());
+}
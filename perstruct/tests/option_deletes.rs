@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+
+use perstruct::perstruct;
+
+#[perstruct]
+struct ProxySettings {
+    host: Option<String>,
+    #[perstruct(key = "proxy_port")]
+    port: Option<u16>,
+    enabled: bool,
+}
+
+#[test]
+fn none_options_are_deleted() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = ProxySettings::from_map(&HashMap::from([
+        ("host", "\"proxy.local\""),
+        ("enabled", "true"),
+    ]))
+    .into_value();
+    settings.set_host(None);
+    settings.set_port(Some(8080));
+    settings.set_enabled(false);
+    let mut changes = settings.perstruct_get_changes_with_deletes().unwrap();
+    changes.sort();
+    assert_eq!(
+        changes,
+        vec![
+            ("enabled", Some("false".to_string())),
+            ("host", None),
+            ("proxy_port", Some("8080".to_string())),
+        ]
+    );
+}
+
+#[test]
+fn plain_changes_keep_null() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = ProxySettings::default();
+    settings.set_host(None);
+    assert_eq!(
+        settings.perstruct_get_changes().unwrap(),
+        vec![("host", "null".to_string())]
+    );
+}
+
+#[perstruct]
+struct EditorSettings {
+    #[perstruct(skip_default, default = 4)]
+    tab_width: u8,
+    #[perstruct(skip_default, default_fn = "default_font")]
+    font: String,
+    #[perstruct(skip_default)]
+    ruler: Option<u16>,
+    word_wrap: bool,
+}
+
+fn default_font() -> String {
+    "mono".to_string()
+}
+
+#[test]
+fn skip_default_fields_equal_to_default_are_deleted() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = EditorSettings::default();
+    settings.perstruct_mark_all_dirty();
+    let mut changes = settings.perstruct_get_changes_with_deletes().unwrap();
+    changes.sort();
+    assert_eq!(
+        changes,
+        vec![
+            ("font", None),
+            ("ruler", None),
+            ("tab_width", None),
+            ("word_wrap", Some("false".to_string())),
+        ]
+    );
+
+    settings.set_tab_width(2);
+    settings.set_ruler(Some(80));
+    let mut changes = settings.perstruct_get_changes_with_deletes().unwrap();
+    changes.sort();
+    assert_eq!(
+        changes,
+        vec![
+            ("font", None),
+            ("ruler", Some("80".to_string())),
+            ("tab_width", Some("2".to_string())),
+            ("word_wrap", Some("false".to_string())),
+        ]
+    );
+
+    // Applying the changes to a store and loading it gives the same values back
+    let stored = changes
+        .into_iter()
+        .filter_map(|(key, value)| Some((key, value?)))
+        .collect::<HashMap<_, _>>();
+    let loaded = EditorSettings::from_map(&stored).into_value();
+    assert_eq!(loaded.tab_width(), 2);
+    assert_eq!(loaded.font(), "mono");
+    assert_eq!(loaded.ruler(), Some(80));
+}
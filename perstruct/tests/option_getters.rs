@@ -0,0 +1,44 @@
+use std::path::{Path, PathBuf};
+
+use perstruct::perstruct;
+
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Clone, PartialEq)]
+struct Proxy {
+    host: String,
+    port: u16,
+}
+
+#[perstruct]
+struct NetworkSettings {
+    timeout: Option<i32>,
+    user_agent: Option<String>,
+    proxy: Option<Proxy>,
+    cache_dir: Option<PathBuf>,
+}
+
+#[test]
+fn option_getters_return_borrowed_options() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = NetworkSettings::default();
+    let timeout: Option<i32> = settings.timeout();
+    let user_agent: Option<&str> = settings.user_agent();
+    let proxy: Option<&Proxy> = settings.proxy();
+    let cache_dir: Option<&Path> = settings.cache_dir();
+    assert_eq!(
+        (timeout, user_agent, proxy, cache_dir),
+        (None, None, None, None)
+    );
+
+    settings.set_timeout(Some(30));
+    settings.set_user_agent(Some("perstruct".to_string()));
+    settings.set_proxy(Some(Proxy {
+        host: "proxy.local".to_string(),
+        port: 8080,
+    }));
+    settings.set_cache_dir(Some(PathBuf::from("/tmp/cache")));
+    assert_eq!(settings.timeout(), Some(30));
+    assert_eq!(settings.user_agent(), Some("perstruct"));
+    assert_eq!(settings.proxy().map(|proxy| proxy.port), Some(8080));
+    assert_eq!(settings.cache_dir(), Some(Path::new("/tmp/cache")));
+}
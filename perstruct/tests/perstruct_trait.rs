@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+
+use perstruct::{perstruct, Perstruct};
+
+#[perstruct]
+struct WindowSettings {
+    #[perstruct(default = 800)]
+    width: u32,
+    #[perstruct(default = 600)]
+    height: u32,
+}
+
+#[perstruct(key_fn = "namespaced")]
+struct AudioSettings {
+    volume: u8,
+}
+
+fn namespaced(key: &'static str) -> &'static str {
+    Box::leak(format!("audio.{key}").into_boxed_str())
+}
+
+fn save<T: Perstruct>(settings: &mut T, store: &mut HashMap<String, String>) {
+    for (key, value) in settings.get_changes().unwrap() {
+        store.insert(key.to_string(), value);
+    }
+    settings.saved();
+}
+
+fn load<T: Perstruct>(store: &HashMap<String, String>) -> T {
+    T::from_map(store).into_value()
+}
+
+#[test]
+fn generic_persistence() {
+    use pretty_assertions::assert_eq;
+
+    assert_eq!(WindowSettings::keys(), &["width", "height"]);
+    assert_eq!(AudioSettings::keys(), &["audio.volume"]);
+
+    let mut store = HashMap::new();
+    let mut window: WindowSettings = load(&store);
+    let mut audio: AudioSettings = load(&store);
+    window.set_width(1024);
+    audio.set_volume(7);
+    save(&mut window, &mut store);
+    save(&mut audio, &mut store);
+    assert!(window.perstruct_dirty_fields().is_empty());
+
+    let window: WindowSettings = load(&store);
+    assert_eq!((window.width(), window.height()), (1024, 600));
+    let audio: AudioSettings = load(&store);
+    assert_eq!(audio.volume(), 7);
+    assert_eq!(store.len(), 3);
+}
+
+#[test]
+fn erased_registry_round_trip() {
+    use perstruct::ErasedPerstruct;
+    use pretty_assertions::assert_eq;
+
+    let mut window = WindowSettings::default();
+    window.set_height(900);
+    let mut audio = AudioSettings::default();
+    audio.set_volume(3);
+    let mut registry: Vec<Box<dyn ErasedPerstruct>> = vec![Box::new(window), Box::new(audio)];
+
+    let mut store = HashMap::new();
+    for settings in &mut registry {
+        assert_eq!(settings.dirty_keys().len(), 1);
+        store.extend(settings.serialize_changes().unwrap());
+        settings.mark_saved();
+        assert!(settings.dirty_keys().is_empty());
+    }
+    assert_eq!(store.len(), 2);
+
+    let mut window = WindowSettings::default();
+    let mut audio = AudioSettings::default();
+    let loaded: Vec<&mut dyn ErasedPerstruct> = vec![&mut window, &mut audio];
+    for settings in loaded {
+        for (key, value) in &store {
+            settings.apply_entry(key, value).unwrap();
+        }
+        assert!(settings.dirty_keys().is_empty());
+    }
+    assert_eq!(window.height(), 900);
+    assert_eq!(audio.volume(), 3);
+    assert!(window.apply_entry("height", "tall").is_err());
+}
@@ -0,0 +1,66 @@
+use perstruct::perstruct;
+
+#[perstruct(value_format = "plain")]
+struct EnvSettings {
+    greeting: String,
+    port: u16,
+    ratio: f64,
+    enabled: bool,
+    tags: Vec<String>,
+}
+
+#[test]
+fn plain_values_round_trip() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = EnvSettings::default();
+    settings.set_greeting(r#"say "hello""#.to_string());
+    settings.set_port(8080);
+    settings.set_ratio(0.5);
+    settings.set_enabled(true);
+    settings.set_tags(vec!["a".to_string()]);
+
+    let mut changes = settings.perstruct_get_changes().unwrap();
+    changes.sort();
+    assert_eq!(
+        changes,
+        vec![
+            ("enabled", "true".to_string()),
+            ("greeting", r#"say "hello""#.to_string()),
+            ("port", "8080".to_string()),
+            ("ratio", "0.5".to_string()),
+            ("tags", r#"["a"]"#.to_string()),
+        ]
+    );
+
+    let result = EnvSettings::from_map(&changes.into_iter().collect());
+    assert_eq!(result.deserialization_errors(), vec![]);
+    assert_eq!(result.value().greeting(), r#"say "hello""#);
+    assert_eq!(result.value().port(), 8080);
+    assert_eq!(result.value().ratio(), 0.5);
+    assert!(result.value().enabled());
+    assert_eq!(result.value().tags(), &vec!["a".to_string()]);
+}
+
+#[test]
+fn plain_values_are_parsed_leniently() {
+    use pretty_assertions::assert_eq;
+
+    let result = EnvSettings::from_map(
+        &vec![
+            ("port", " 0080 "),
+            ("greeting", "\"quoted\""),
+            ("enabled", "yes"),
+        ]
+        .into_iter()
+        .collect(),
+    );
+    assert_eq!(result.value().port(), 80);
+    assert_eq!(result.value().greeting(), "\"quoted\"");
+    assert_eq!(result.deserialization_errors().len(), 1);
+    assert_eq!(result.deserialization_errors()[0].key, "enabled");
+    assert_eq!(
+        result.deserialization_errors()[0].message,
+        "provided string was not `true` or `false`"
+    );
+}
@@ -0,0 +1,55 @@
+use perstruct::perstruct;
+
+#[perstruct(post_set = "after_set")]
+struct SearchSettings {
+    #[perstruct(default_fn = "default_pattern")]
+    pattern: String,
+    case_sensitive: bool,
+    #[perstruct(skip)]
+    compiled: String,
+    #[perstruct(skip)]
+    changed: Vec<&'static str>,
+}
+
+fn default_pattern() -> String {
+    "needle".to_string()
+}
+
+fn after_set(settings: &mut SearchSettings, changed_key: &'static str) {
+    settings.changed.push(changed_key);
+    settings.compiled = if settings.case_sensitive {
+        settings.pattern.clone()
+    } else {
+        settings.pattern.to_lowercase()
+    };
+}
+
+#[test]
+fn runs_after_setters_updates_and_resets() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = SearchSettings::default();
+    settings.set_pattern("Hay".to_string());
+    assert_eq!(settings.compiled, "hay");
+    settings.update_case_sensitive(|case_sensitive| *case_sensitive = true);
+    assert_eq!(settings.compiled, "Hay");
+    settings.reset_pattern();
+    assert_eq!(settings.compiled, "needle");
+    assert_eq!(
+        settings.changed,
+        vec!["pattern", "case_sensitive", "pattern"]
+    );
+}
+
+#[test]
+fn runs_after_set_by_key_but_not_when_loading() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = SearchSettings::from_map(&[("pattern", "\"Loaded\"")].into()).into_value();
+    assert_eq!(settings.compiled, "");
+    settings
+        .perstruct_set_by_key("case_sensitive", "true")
+        .unwrap();
+    assert_eq!(settings.compiled, "Loaded");
+    assert_eq!(settings.changed, vec!["case_sensitive"]);
+}
@@ -0,0 +1,56 @@
+use perstruct::perstruct;
+
+#[perstruct]
+struct Library {
+    #[perstruct(precise_updates)]
+    tags: Vec<String>,
+    #[perstruct(precise_updates)]
+    layout: Layout,
+    shelves: Vec<u32>,
+}
+
+/// No `PartialEq`, so updates can't be compared.
+#[derive(Debug, Default, Clone, serde_derive::Serialize, serde_derive::Deserialize)]
+struct Layout {
+    columns: u8,
+}
+
+fn library() -> Library {
+    let mut library = Library::default();
+    library.set_tags(vec!["rust".to_string(), "".to_string()]);
+    library.perstruct_saved();
+    library
+}
+
+#[test]
+fn no_op_update_is_not_dirty() {
+    use pretty_assertions::assert_eq;
+
+    let mut library = library();
+    library.update_tags(|tags| tags.retain(|tag| tag != "go"));
+    assert!(!library.is_tags_dirty());
+    assert_eq!(library.perstruct_get_changes().unwrap(), vec![]);
+
+    // Without `precise_updates` the field is always dirty
+    library.update_shelves(|shelves| shelves.retain(|shelf| *shelf > 0));
+    assert!(library.is_shelves_dirty());
+}
+
+#[test]
+fn mutating_update_is_dirty() {
+    use pretty_assertions::assert_eq;
+
+    let mut library = library();
+    library.update_tags(|tags| tags.retain(|tag| !tag.is_empty()));
+    assert_eq!(
+        library.perstruct_get_changes().unwrap(),
+        vec![("tags", r#"["rust"]"#.to_string())]
+    );
+}
+
+#[test]
+fn types_without_partial_eq_are_always_dirty() {
+    let mut library = library();
+    library.update_layout(|_| {});
+    assert!(library.is_layout_dirty());
+}
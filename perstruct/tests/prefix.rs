@@ -0,0 +1,45 @@
+use perstruct::perstruct;
+
+#[perstruct(prefix = "editor.")]
+struct EditorSettings {
+    #[perstruct(alias = "tabs")]
+    tab_width: u8,
+    #[perstruct(key = "font")]
+    font_family: String,
+}
+
+#[perstruct(prefix = "terminal.")]
+struct TerminalSettings {
+    #[perstruct(key = "font")]
+    font_family: String,
+}
+
+#[test]
+fn prefix_applies_to_all_keys() {
+    use pretty_assertions::assert_eq;
+
+    assert_eq!(
+        EditorSettings::perstruct_keys(),
+        vec!["editor.tab_width", "editor.font"]
+    );
+    let map = vec![
+        ("editor.tabs", "2"),
+        ("editor.font", "\"mono\""),
+        ("terminal.font", "\"fixed\""),
+    ]
+    .into_iter()
+    .collect();
+
+    let editor = EditorSettings::from_map(&map);
+    assert_eq!(editor.value().tab_width(), 2);
+    assert_eq!(editor.value().font_family(), "mono");
+    assert_eq!(editor.unknown_fields(), vec!["terminal.font".to_string()]);
+
+    let mut terminal = TerminalSettings::from_map(&map).into_value();
+    assert_eq!(terminal.font_family(), "fixed");
+    terminal.set_font_family("serif".to_string());
+    assert_eq!(
+        terminal.perstruct_get_changes().unwrap(),
+        vec![("terminal.font", "\"serif\"".to_string())]
+    );
+}
@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+
+use perstruct::{perstruct, Perstruct};
+
+#[perstruct(preserve_unknown)]
+struct Settings {
+    volume: u8,
+}
+
+#[test]
+fn unknown_entries_are_written_back() {
+    use pretty_assertions::assert_eq;
+
+    let map = HashMap::from([
+        ("volume", "3"),
+        ("theme", "\"dark\""),
+        ("accent", "\"blue\""),
+    ]);
+    let result = Settings::from_map(&map);
+    assert_eq!(result.unknown_fields().len(), 2);
+    let mut settings = result.into_value();
+    assert_eq!(settings.perstruct_get_changes().unwrap(), vec![]);
+
+    settings.set_volume(4);
+    assert_eq!(
+        settings.perstruct_get_changes().unwrap(),
+        vec![
+            ("volume", "4".to_string()),
+            ("accent", "\"blue\"".to_string()),
+            ("theme", "\"dark\"".to_string()),
+        ]
+    );
+    assert_eq!(settings.get_changes().unwrap().len(), 3);
+
+    settings.perstruct_saved();
+    assert_eq!(settings.perstruct_get_changes().unwrap(), vec![]);
+}
+
+#[test]
+fn load_into_preserves_unknown_entries() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = Settings::default();
+    settings.perstruct_load_into(&HashMap::from([("theme", "\"dark\"")]));
+    settings.set_volume(1);
+    assert_eq!(
+        settings.perstruct_get_changes().unwrap(),
+        vec![
+            ("volume", "1".to_string()),
+            ("theme", "\"dark\"".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn profile_entries_are_not_preserved() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings =
+        Settings::with_profile_defaults(&HashMap::from([("theme", "\"dark\"")])).into_value();
+    settings.set_volume(1);
+    assert_eq!(
+        settings.perstruct_get_changes().unwrap(),
+        vec![("volume", "1".to_string())]
+    );
+}
+
+#[test]
+fn for_each_change_includes_unknown_entries() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = Settings::from_map(&HashMap::from([("theme", "\"dark\"")])).into_value();
+    settings.set_volume(2);
+    let mut written = vec![];
+    settings
+        .perstruct_for_each_change(|key, value| {
+            written.push((key.to_string(), value));
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(
+        written,
+        vec![
+            ("volume".to_string(), "2".to_string()),
+            ("theme".to_string(), "\"dark\"".to_string()),
+        ]
+    );
+}
@@ -0,0 +1,41 @@
+use perstruct::perstruct;
+
+#[perstruct]
+struct ServerSettings {
+    #[perstruct(default = 8080)]
+    port: u16,
+    #[perstruct(default_fn = "default_host")]
+    host: String,
+    workers: u8,
+}
+
+fn default_host() -> String {
+    "localhost".to_string()
+}
+
+#[test]
+fn profile_values_are_defaults() {
+    use pretty_assertions::assert_eq;
+
+    let prod = vec![("host", "\"0.0.0.0\""), ("workers", "16")]
+        .into_iter()
+        .collect();
+    let result = ServerSettings::with_profile_defaults(&prod);
+    assert!(result.deserialization_errors().is_empty());
+    let mut settings = result.into_value();
+    assert_eq!(settings.host(), "0.0.0.0");
+    assert_eq!(settings.workers(), 16);
+    assert_eq!(settings.port(), 8080);
+    assert!(settings.perstruct_dirty_fields().is_empty());
+
+    settings.perstruct_load_into(&vec![("workers", "4")].into_iter().collect());
+    assert_eq!(settings.workers(), 4);
+    assert_eq!(settings.host(), "0.0.0.0");
+    assert!(settings.perstruct_get_changes().unwrap().is_empty());
+
+    settings.set_port(9000);
+    assert_eq!(
+        settings.perstruct_get_changes().unwrap(),
+        vec![("port", "9000".to_string())]
+    );
+}
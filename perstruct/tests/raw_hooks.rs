@@ -0,0 +1,111 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use perstruct::perstruct;
+
+/// The hook that ran and the entries it was called with.
+type LogEntry = (&'static str, Vec<(String, String)>);
+
+thread_local! {
+    static LOG: RefCell<Vec<LogEntry>> = const { RefCell::new(vec![]) };
+}
+
+fn take_log() -> Vec<LogEntry> {
+    LOG.with(|log| log.take())
+}
+
+fn archive(entries: &[(String, String)]) {
+    LOG.with(|log| log.borrow_mut().push(("load", entries.to_vec())));
+}
+
+fn append_to_log(entries: &[(String, String)]) {
+    LOG.with(|log| log.borrow_mut().push(("save", entries.to_vec())));
+}
+
+fn entries(entries: &[(&str, &str)]) -> Vec<(String, String)> {
+    entries
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+#[perstruct(
+    on_load_raw = "archive",
+    on_save_raw = "append_to_log",
+    version = 2,
+    migrate_fn = "migrate"
+)]
+struct AuditedSettings {
+    volume: u8,
+    #[perstruct(serialize_with = "serialize_name")]
+    name: String,
+}
+
+fn migrate(_found_version: u32, map: &mut HashMap<String, String>) {
+    if let Some(level) = map.remove("level") {
+        map.insert("volume".to_string(), level);
+    }
+}
+
+fn serialize_name(name: &str) -> Result<String, String> {
+    match name {
+        "" => Err("empty name".to_string()),
+        name => Ok(format!("{name:?}")),
+    }
+}
+
+#[perstruct(on_load_raw = "archive")]
+struct RequiredSettings {
+    #[perstruct(no_default)]
+    account: u32,
+}
+
+#[test]
+fn hooks_see_the_raw_entries_in_order() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = AuditedSettings::from_map(&HashMap::from([
+        ("name", "\"main\""),
+        ("level", "3"),
+        ("__version", "1"),
+    ]))
+    .into_value();
+    assert_eq!(settings.volume(), 3);
+    settings.perstruct_saved();
+    settings.set_volume(4);
+    let changes = settings.perstruct_get_changes().unwrap();
+    assert_eq!(
+        take_log(),
+        vec![
+            // Before the migration
+            (
+                "load",
+                entries(&[("__version", "1"), ("level", "3"), ("name", "\"main\"")])
+            ),
+            ("save", entries(&[("volume", "4"), ("__version", "2")])),
+        ]
+    );
+    assert_eq!(
+        changes,
+        vec![("volume", "4".to_string()), ("__version", "2".to_string())]
+    );
+
+    let session = settings.perstruct_begin_save().unwrap();
+    settings.perstruct_complete_save(session).unwrap();
+    assert_eq!(take_log().len(), 1);
+}
+
+#[test]
+fn hooks_dont_run_when_the_operation_fails() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = AuditedSettings::default();
+    take_log();
+    settings.set_name(String::new());
+    assert!(settings.perstruct_get_changes().is_err());
+    assert!(RequiredSettings::try_from_map(&HashMap::<&str, &str>::new()).is_err());
+    assert_eq!(take_log(), vec![]);
+
+    RequiredSettings::try_from_map(&HashMap::from([("account", "7")])).unwrap();
+    assert_eq!(take_log(), vec![("load", entries(&[("account", "7")]))]);
+}
@@ -0,0 +1,41 @@
+use perstruct::perstruct;
+
+#[perstruct]
+struct Token {
+    r#type: String,
+    #[perstruct(default_fn = "defaults::expiry")]
+    r#move: u32,
+}
+
+mod defaults {
+    pub fn expiry() -> u32 {
+        3600
+    }
+}
+
+#[test]
+fn raw_identifier_fields() {
+    use pretty_assertions::assert_eq;
+
+    let mut token = Token::default();
+    assert_eq!(token.r#move(), 3600);
+    token.set_type("bearer".to_string());
+    token.update_move(|expiry| *expiry += 1);
+    assert!(token.is_type_dirty());
+    let mut changes = token.perstruct_get_changes().unwrap();
+    changes.sort();
+    assert_eq!(
+        changes,
+        vec![
+            ("move", "3601".to_string()),
+            ("type", "\"bearer\"".to_string())
+        ]
+    );
+
+    token
+        .perstruct_update()
+        .r#type("basic".to_string())
+        .commit();
+    assert_eq!(Token::TYPE.get(&token), "basic");
+    assert_eq!(Token::MOVE.key(), "move");
+}
@@ -0,0 +1,55 @@
+use std::collections::HashMap;
+
+use perstruct::{perstruct, DynAccessError};
+
+#[perstruct]
+struct InstallSettings {
+    #[perstruct(readonly)]
+    install_id: String,
+    #[perstruct(readonly, default = 3)]
+    schema_revision: u32,
+    theme: String,
+}
+
+#[test]
+fn readonly_fields_are_loaded_and_persisted() {
+    use pretty_assertions::assert_eq;
+
+    assert_eq!(
+        InstallSettings::perstruct_keys(),
+        vec!["install_id", "schema_revision", "theme"]
+    );
+    let settings = InstallSettings::default();
+    assert_eq!(settings.schema_revision(), 3);
+
+    let map = HashMap::from([("install_id", "\"abc\""), ("schema_revision", "4")]);
+    let mut settings = InstallSettings::from_map(&map).into_value();
+    assert_eq!(settings.install_id(), "abc");
+    assert_eq!(settings.schema_revision(), 4);
+    assert!(!settings.is_install_id_dirty());
+
+    settings.mark_install_id_dirty();
+    let mut changes = settings.perstruct_get_changes().unwrap();
+    changes.sort();
+    assert_eq!(
+        changes,
+        vec![
+            ("install_id", "\"abc\"".to_string()),
+            ("theme", "\"\"".to_string())
+        ]
+    );
+}
+
+#[test]
+fn readonly_fields_are_not_set_by_key() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = InstallSettings::default();
+    assert_eq!(
+        settings.perstruct_set_by_key("install_id", "\"xyz\""),
+        Err(DynAccessError::ReadOnly("install_id"))
+    );
+    assert_eq!(settings.install_id(), "");
+    settings.perstruct_set_by_key("theme", "\"dark\"").unwrap();
+    assert_eq!(settings.theme(), "dark");
+}
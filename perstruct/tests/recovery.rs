@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+
+use perstruct::{perstruct, CorruptEntry, RecoveryReport};
+
+#[perstruct(case_insensitive)]
+struct DisplaySettings {
+    #[perstruct(alias = "size")]
+    font_size: u32,
+    font: String,
+    #[perstruct(default = true)]
+    animations: bool,
+}
+
+#[test]
+fn reports_each_group() {
+    use pretty_assertions::assert_eq;
+
+    let garbage = format!("{{\"broken\":\n{}", "x".repeat(60));
+    let map = HashMap::from([
+        ("SIZE", garbage.as_str()),
+        ("animations", "false"),
+        ("fnt", "\"mono\""),
+    ]);
+    let (settings, report) = DisplaySettings::from_map_recovering(&map);
+    assert_eq!(settings.font_size(), 0);
+    assert!(!settings.animations());
+    assert_eq!(
+        report,
+        RecoveryReport {
+            loaded: vec!["animations"],
+            defaulted_missing: vec!["font"],
+            defaulted_corrupt: vec![CorruptEntry {
+                key: "font_size",
+                error: "invalid type: map, expected u32 at line 1 column 0".to_string(),
+                snippet: "{\"broken\": xxxxxxxxxxxxxxxxxxxxxxxxxxxxx…".to_string(),
+            }],
+            unknown: vec!["fnt".to_string()],
+        }
+    );
+    assert!(!report.is_clean());
+    assert_eq!(
+        report.to_user_message(),
+        "Loaded 1 of 3 settings.\n\
+        Missing, reset to default: font\n\
+        Unreadable, reset to default:\n  \
+        font_size: invalid type: map, expected u32 at line 1 column 0 \
+        (stored: {\"broken\": xxxxxxxxxxxxxxxxxxxxxxxxxxxxx…)\n\
+        Unknown, ignored: fnt"
+    );
+}
+
+#[test]
+fn clean_load() {
+    use pretty_assertions::assert_eq;
+
+    let map = HashMap::from([
+        ("font_size", "12"),
+        ("font", "\"mono\""),
+        ("animations", "true"),
+    ]);
+    let (_, report) = DisplaySettings::from_map_recovering(&map);
+    assert!(report.is_clean());
+    assert_eq!(report.to_user_message(), "Loaded 3 of 3 settings.");
+}
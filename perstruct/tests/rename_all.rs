@@ -0,0 +1,52 @@
+use perstruct::perstruct;
+
+#[perstruct(rename_all = "camelCase")]
+struct CamelSettings {
+    font_size: u8,
+    #[perstruct(key = "theme_name")]
+    ui_theme: String,
+    enabled: bool,
+}
+
+#[perstruct(rename_all = "kebab-case", prefix = "app.")]
+struct KebabSettings {
+    max_open_files: u32,
+}
+
+#[perstruct(rename_all = "PascalCase")]
+struct PascalSettings {
+    max_open_files: u32,
+}
+
+#[perstruct(rename_all = "SCREAMING_SNAKE_CASE")]
+struct ScreamingSettings {
+    max_open_files: u32,
+}
+
+#[test]
+fn keys_are_converted() {
+    use pretty_assertions::assert_eq;
+
+    assert_eq!(
+        CamelSettings::perstruct_keys(),
+        vec!["fontSize", "theme_name", "enabled"]
+    );
+    assert_eq!(KebabSettings::perstruct_keys(), vec!["app.max-open-files"]);
+    assert_eq!(PascalSettings::perstruct_keys(), vec!["MaxOpenFiles"]);
+    assert_eq!(ScreamingSettings::perstruct_keys(), vec!["MAX_OPEN_FILES"]);
+}
+
+#[test]
+fn converted_keys_round_trip() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings =
+        CamelSettings::from_map(&vec![("fontSize", "14")].into_iter().collect()).into_value();
+    assert_eq!(settings.font_size(), 14);
+    settings.perstruct_saved();
+    settings.set_font_size(16);
+    assert_eq!(
+        settings.perstruct_get_changes().unwrap(),
+        vec![("fontSize", "16".to_string())]
+    );
+}
@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+
+use perstruct::{perstruct, PerstructLoadError};
+
+#[perstruct]
+struct License {
+    #[perstruct(required)]
+    license_key: String,
+    #[perstruct(required, alias = "tenant")]
+    tenant_id: u64,
+    seats: u32,
+}
+
+#[test]
+fn missing_required_keys_are_reported() {
+    use pretty_assertions::assert_eq;
+
+    let map = HashMap::from([("seats", "5")]);
+    let result = License::from_map(&map);
+    assert_eq!(result.missing_required(), vec!["license_key", "tenant_id"]);
+    // The fields still get their default, so the struct can be used for error reporting
+    assert_eq!(result.value().license_key(), "");
+    assert_eq!(result.value().seats(), 5);
+    assert_eq!(
+        result.into_result().err(),
+        Some(PerstructLoadError {
+            missing_fields: vec!["license_key", "tenant_id"],
+            unknown_fields: vec![],
+            unknown_overflow: 0,
+            deserialization_errors: vec![],
+        })
+    );
+}
+
+#[test]
+fn present_required_keys_load() {
+    use pretty_assertions::assert_eq;
+
+    let map = HashMap::from([("license_key", "\"ABC-123\""), ("tenant", "42")]);
+    let result = License::from_map(&map);
+    assert_eq!(result.missing_required(), Vec::<&str>::new());
+    let license = result.into_result().unwrap();
+    assert_eq!(license.license_key(), "ABC-123");
+    assert_eq!(license.tenant_id(), 42);
+}
+
+#[test]
+fn load_into_reports_missing_required_keys() {
+    use pretty_assertions::assert_eq;
+
+    let mut license = License::default();
+    let result = license.perstruct_load_into(&HashMap::from([("license_key", "\"ABC-123\"")]));
+    assert_eq!(result.missing_required(), vec!["tenant_id"]);
+    assert!(result.into_result().is_err());
+}
+
+#[perstruct]
+struct Subscription {
+    #[perstruct(required, no_default)]
+    plan: String,
+    #[perstruct(no_default)]
+    seats: u32,
+}
+
+impl Default for Subscription {
+    fn default() -> Self {
+        Subscription::try_from_map(&HashMap::from([("plan", "\"free\""), ("seats", "1")]))
+            .unwrap()
+            .into_value()
+    }
+}
+
+#[test]
+fn no_default_fields_are_required() {
+    use pretty_assertions::assert_eq;
+
+    assert_eq!(
+        Subscription::try_from_map(&HashMap::from([("seats", "5")])).err(),
+        Some(PerstructLoadError {
+            missing_fields: vec!["plan"],
+            unknown_fields: vec![],
+            unknown_overflow: 0,
+            deserialization_errors: vec![],
+        })
+    );
+    let result = Subscription::from_map(&HashMap::from([("seats", "5")]));
+    assert_eq!(result.missing_required(), vec!["plan"]);
+    let result = Subscription::from_map(&HashMap::from([("plan", "\"pro\"")]));
+    assert_eq!(result.missing_required(), vec!["seats"]);
+}
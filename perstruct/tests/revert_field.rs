@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+
+use perstruct::perstruct;
+
+#[perstruct(track_loaded)]
+struct Settings {
+    #[perstruct(default = 3)]
+    volume: u8,
+    name: String,
+}
+
+#[perstruct(track_loaded, dirty = "bitset")]
+struct BitsetSettings {
+    #[perstruct(default = 3)]
+    volume: u8,
+}
+
+#[test]
+fn revert_restores_the_loaded_value() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings =
+        Settings::from_map(&HashMap::from([("volume", "7"), ("name", "\"a\"")])).into_value();
+    settings.set_volume(9);
+    settings.set_name("b".to_string());
+    assert!(settings.perstruct_revert_field("volume"));
+    assert_eq!(settings.volume(), 7);
+    assert!(!settings.is_volume_dirty());
+    assert!(settings.is_name_dirty());
+}
+
+#[test]
+fn revert_falls_back_to_the_default() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = Settings::default();
+    settings.set_volume(9);
+    assert!(settings.perstruct_revert_field("volume"));
+    assert_eq!(settings.volume(), 3);
+    assert!(settings.perstruct_dirty_fields().is_empty());
+}
+
+#[test]
+fn revert_restores_the_last_saved_value() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = Settings::default();
+    settings.set_name("saved".to_string());
+    settings.perstruct_saved();
+    settings.set_name("unsaved".to_string());
+    assert!(settings.perstruct_revert_field("name"));
+    assert_eq!(settings.name(), "saved");
+}
+
+#[test]
+fn revert_restores_applied_values() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = Settings::default();
+    settings.perstruct_apply("volume", "5").unwrap();
+    settings.set_volume(1);
+    assert!(settings.perstruct_revert_field("volume"));
+    assert_eq!(settings.volume(), 5);
+}
+
+#[test]
+fn revert_unknown_key() {
+    let mut settings = Settings::default();
+    assert!(!settings.perstruct_revert_field("missing"));
+}
+
+#[test]
+fn revert_with_bitset() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = BitsetSettings::from_map(&HashMap::from([("volume", "4")])).into_value();
+    settings.set_volume(8);
+    settings.perstruct_saved();
+    settings.set_volume(1);
+    assert!(settings.perstruct_revert_field("volume"));
+    assert_eq!(settings.volume(), 8);
+    assert!(!settings.is_volume_dirty());
+}
+
+#[test]
+fn revert_restores_a_value_saved_on_its_own() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = Settings::default();
+    settings.set_volume(5);
+    settings.set_name("unsaved".to_string());
+    assert!(settings.perstruct_clear_dirty("volume"));
+    settings.set_volume(6);
+    assert!(settings.perstruct_revert_field("volume"));
+    assert!(settings.perstruct_revert_field("name"));
+    assert_eq!(settings.volume(), 5);
+    assert_eq!(settings.name(), "");
+}
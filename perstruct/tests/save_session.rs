@@ -0,0 +1,79 @@
+use perstruct::{perstruct, SaveSession};
+
+#[perstruct]
+struct ProfileSettings {
+    display_name: String,
+    #[perstruct(default = 12)]
+    font_size: u8,
+    status: String,
+}
+
+#[test]
+fn edits_during_a_save_stay_dirty() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = ProfileSettings::default();
+    settings.perstruct_saved();
+    settings.set_display_name("Ada".to_string());
+    settings.set_font_size(14);
+
+    let session = settings.perstruct_begin_save().unwrap();
+    let mut sent = session.changes().to_vec();
+    sent.sort();
+    assert_eq!(
+        sent,
+        vec![
+            ("display_name".to_string(), r#""Ada""#.to_string()),
+            ("font_size".to_string(), "14".to_string()),
+        ]
+    );
+
+    // Edited while the save is in flight
+    settings.set_font_size(16);
+    settings.set_status("away".to_string());
+
+    settings.perstruct_complete_save(session).unwrap();
+    let mut changes = settings.perstruct_get_changes().unwrap();
+    changes.sort();
+    assert_eq!(
+        changes,
+        vec![
+            ("font_size", "16".to_string()),
+            ("status", r#""away""#.to_string()),
+        ]
+    );
+}
+
+#[test]
+fn edits_back_to_the_saved_value_are_saved() {
+    let mut settings = ProfileSettings::default();
+    settings.perstruct_saved();
+    settings.set_font_size(14);
+    let session = SaveSession::new(settings.perstruct_get_changes().unwrap());
+
+    settings.set_font_size(16);
+    settings.set_font_size(14);
+    settings.perstruct_complete_save(session).unwrap();
+    assert!(!settings.is_font_size_dirty());
+}
+
+#[perstruct(single_document = "profile")]
+struct ProfileDocument {
+    display_name: String,
+    status: String,
+}
+
+#[test]
+fn single_documents_are_saved_as_a_whole() {
+    let mut settings = ProfileDocument::default();
+    let session = settings.perstruct_begin_save().unwrap();
+    settings.perstruct_complete_save(session).unwrap();
+    assert!(settings.perstruct_get_changes().unwrap().is_empty());
+
+    settings.set_display_name("Ada".to_string());
+    let session = settings.perstruct_begin_save().unwrap();
+    settings.set_status("away".to_string());
+    settings.perstruct_complete_save(session).unwrap();
+    assert!(settings.is_display_name_dirty());
+    assert!(settings.is_status_dirty());
+}
@@ -0,0 +1,72 @@
+use perstruct::perstruct;
+
+#[perstruct(serde)]
+struct Settings {
+    #[perstruct(key = "user-name", default_fn = "anonymous")]
+    name: String,
+    #[perstruct(default = 3, alias = "attempts")]
+    retries: u32,
+    #[perstruct(skip)]
+    session: Option<u64>,
+}
+
+fn anonymous() -> String {
+    "anonymous".to_string()
+}
+
+#[test]
+fn serializes_persisted_keys() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = Settings::default();
+    settings.set_retries(5);
+    assert_eq!(
+        serde_json::to_string(&settings).unwrap(),
+        r#"{"user-name":"anonymous","retries":5}"#
+    );
+}
+
+#[test]
+fn round_trips() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = Settings::default();
+    settings.set_name("ferris".to_string());
+    settings.set_retries(1);
+    let json = serde_json::to_string(&settings).unwrap();
+    let restored: Settings = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored.name(), "ferris");
+    assert_eq!(restored.retries(), 1);
+    assert_eq!(restored.session, None);
+    assert_eq!(restored.perstruct_get_changes().unwrap(), vec![]);
+}
+
+#[test]
+fn missing_fields_default_and_dirty() {
+    use pretty_assertions::assert_eq;
+
+    let settings: Settings = serde_json::from_str(r#"{"attempts":7,"other":[1]}"#).unwrap();
+    assert_eq!(settings.name(), "anonymous");
+    assert_eq!(settings.retries(), 7);
+    assert_eq!(
+        settings.perstruct_get_changes().unwrap(),
+        vec![("user-name", "\"anonymous\"".to_string())]
+    );
+}
+
+#[perstruct(serde)]
+struct Required {
+    #[perstruct(no_default)]
+    token: String,
+}
+
+#[test]
+fn missing_no_default_field_errors() {
+    use pretty_assertions::assert_eq;
+
+    let error = serde_json::from_str::<Required>("{}").err().unwrap();
+    assert_eq!(
+        error.to_string(),
+        "missing field `token` at line 1 column 2"
+    );
+}
@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+
+use perstruct::perstruct;
+use serde_derive::{Deserialize, Serialize};
+
+/// Not serializable itself.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Rgb(u8, u8, u8);
+
+#[derive(Serialize, Deserialize)]
+struct HexColor(String);
+
+impl From<&Rgb> for HexColor {
+    fn from(rgb: &Rgb) -> Self {
+        HexColor(format!("#{:02x}{:02x}{:02x}", rgb.0, rgb.1, rgb.2))
+    }
+}
+
+impl TryFrom<HexColor> for Rgb {
+    type Error = String;
+
+    fn try_from(hex: HexColor) -> Result<Self, Self::Error> {
+        let digits = hex.0.strip_prefix('#').ok_or("missing #")?;
+        let channel = |range: std::ops::Range<usize>| {
+            digits
+                .get(range)
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                .ok_or_else(|| format!("invalid color {}", hex.0))
+        };
+        Ok(Rgb(channel(0..2)?, channel(2..4)?, channel(4..6)?))
+    }
+}
+
+#[perstruct(value_format = "plain")]
+struct Settings {
+    #[perstruct(serialize_as = "HexColor")]
+    color: Rgb,
+}
+
+#[test]
+fn stored_as_proxy() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = Settings::default();
+    settings.set_color(Rgb(255, 0, 16));
+    assert_eq!(
+        settings.perstruct_get_changes().unwrap(),
+        vec![("color", "\"#ff0010\"".to_string())]
+    );
+    assert_eq!(
+        settings.perstruct_to_json_string().unwrap(),
+        r##"{"color":"#ff0010"}"##
+    );
+
+    let settings = Settings::from_map(&HashMap::from([("color", "\"#0a0b0c\"")])).into_value();
+    assert_eq!(settings.color(), &Rgb(10, 11, 12));
+}
+
+#[test]
+fn conversion_errors_are_deserialization_errors() {
+    use pretty_assertions::assert_eq;
+
+    let result = Settings::from_map(&HashMap::from([("color", "\"#0a\"")]));
+    assert_eq!(result.deserialization_errors().len(), 1);
+    assert_eq!(result.deserialization_errors()[0].key, "color");
+    assert_eq!(
+        result.deserialization_errors()[0].message,
+        "invalid color #0a"
+    );
+    assert_eq!(result.value().color(), &Rgb::default());
+}
@@ -0,0 +1,56 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use perstruct::perstruct;
+
+#[perstruct]
+struct SharedSettings {
+    #[perstruct(default = 1)]
+    level: u8,
+    name: String,
+}
+
+/// Only takes a shared reference, like code holding a read lock.
+fn query(settings: &SharedSettings) -> Vec<(&'static str, String)> {
+    assert!(settings.is_name_dirty());
+    assert_eq!(settings.perstruct_dirty_fields().len(), 1);
+    assert_eq!(
+        settings.perstruct_get_by_key("name").unwrap().unwrap(),
+        "\"x\""
+    );
+    assert_eq!(
+        settings.perstruct_to_json_string().unwrap(),
+        r#"{"level":1,"name":"x"}"#
+    );
+    let diff = settings
+        .perstruct_diff_map(&HashMap::<String, String>::new())
+        .unwrap();
+    assert_eq!(diff.inserts.len(), 2);
+    assert_eq!(settings.perstruct_effective_values().unwrap().len(), 2);
+    settings.perstruct_get_changes().unwrap()
+}
+
+#[test]
+fn changes_can_be_read_behind_a_read_lock() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = SharedSettings::default();
+    settings.set_name("x".to_string());
+    let lock = RwLock::new(settings);
+
+    let changes = {
+        let first = lock.read().unwrap();
+        let second = lock.read().unwrap();
+        assert_eq!(query(&second), query(&first));
+        query(&first)
+    };
+    assert_eq!(changes, vec![("name", "\"x\"".to_string())]);
+
+    lock.write().unwrap().perstruct_saved();
+    assert!(lock
+        .read()
+        .unwrap()
+        .perstruct_get_changes()
+        .unwrap()
+        .is_empty());
+}
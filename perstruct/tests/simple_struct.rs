@@ -1,6 +1,7 @@
 use std::collections::HashSet;
+use std::time::Duration;
 
-use perstruct::{perstruct, PerstructLoadResult};
+use perstruct::{perstruct, PerstructFieldError};
 
 #[perstruct]
 struct MySettings {
@@ -20,6 +21,30 @@ fn default_foo() -> Foo {
     Foo {}
 }
 
+#[perstruct]
+struct ExprDefaults {
+    #[perstruct(default = Foo {})]
+    foo: Foo,
+    #[perstruct(default = vec![1, 2, 3])]
+    list: Vec<u8>,
+    #[perstruct(default = Level::High)]
+    level: Level,
+    #[perstruct(default = 60 * 60)]
+    timeout_secs: u32,
+    #[perstruct(default = -1)]
+    offset: i32,
+    #[perstruct(default = Duration::from_secs(30))]
+    poll_interval: Duration,
+    #[perstruct(default = "eu".to_uppercase())]
+    region: String,
+}
+
+#[derive(PartialEq, Eq, Debug, serde_derive::Serialize, serde_derive::Deserialize)]
+enum Level {
+    Low,
+    High,
+}
+
 #[test]
 fn some_basic_tests() {
     use pretty_assertions::assert_eq;
@@ -45,11 +70,7 @@ fn some_basic_tests() {
         vec!["b", "foo", "bar", "list"]
     );
 
-    let PerstructLoadResult {
-        value: settings,
-        mut deserialization_errors,
-        unknown_fields,
-    } = MySettings::from_map(
+    let result = MySettings::from_map(
         &vec![
             ("b", "3".to_string()),
             ("foo", "null".to_string()),
@@ -59,23 +80,147 @@ fn some_basic_tests() {
         .into_iter()
         .collect(),
     );
+    let settings = result.value();
     assert_eq!(settings.a(), 3);
     assert_eq!(settings.bar(), 2);
     assert_eq!(settings.foo(), &Foo {});
-    deserialization_errors.sort_by_key(|(k, _)| *k);
+    let mut deserialization_errors = result.deserialization_errors().to_vec();
+    deserialization_errors.sort_by_key(|error| error.key);
     assert_eq!(
         deserialization_errors,
         vec![
-            (
-                "bar",
-                "invalid type: string \"a\", expected i32 at line 1 column 3".to_string()
-            ),
-            (
-                "foo",
-                "invalid type: null, expected struct Foo at line 1 column 4".to_string()
-            ),
+            PerstructFieldError {
+                key: "bar",
+                message: "invalid type: string \"a\", expected i32 at line 1 column 3".to_string(),
+                line: 1,
+                column: 3,
+            },
+            PerstructFieldError {
+                key: "foo",
+                message: "invalid type: null, expected struct Foo at line 1 column 4".to_string(),
+                line: 1,
+                column: 4,
+            },
+        ]
+    );
+
+    assert_eq!(result.unknown_fields(), vec!["whatever".to_string()]);
+}
+
+#[test]
+fn reset_restores_defaults() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = MySettings::from_map(
+        &vec![("b", "3"), ("bar", "5"), ("foo", "{}"), ("list", "[null]")]
+            .into_iter()
+            .collect(),
+    )
+    .into_value();
+    assert_eq!(settings.perstruct_get_changes().unwrap(), vec![]);
+
+    settings.reset_a();
+    settings.reset_bar();
+    assert_eq!(settings.a(), 0);
+    assert_eq!(settings.bar(), 2);
+    let mut changes = settings.perstruct_get_changes().unwrap();
+    changes.sort();
+    assert_eq!(
+        changes,
+        vec![("b", "0".to_string()), ("bar", "2".to_string())]
+    );
+}
+
+#[test]
+fn dirty_state_per_field() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = MySettings::default();
+    assert!(!settings.is_a_dirty());
+    settings.set_a(1);
+    assert!(settings.is_a_dirty());
+    assert!(!settings.is_bar_dirty());
+
+    settings.mark_bar_dirty();
+    assert!(settings.is_bar_dirty());
+    assert_eq!(settings.bar(), 2);
+    settings.perstruct_saved();
+    assert!(!settings.is_a_dirty());
+    assert!(!settings.is_bar_dirty());
+}
+
+#[test]
+fn mark_all_dirty_returns_all_changes() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = MySettings::default();
+    settings.perstruct_mark_all_dirty();
+    let mut changes = settings.perstruct_get_changes().unwrap();
+    changes.sort();
+    assert_eq!(
+        changes,
+        vec![
+            ("b", "0".to_string()),
+            ("bar", "2".to_string()),
+            ("foo", "{}".to_string()),
+            ("list", "[]".to_string()),
         ]
     );
+}
+
+#[test]
+fn touch_all_after_save_returns_all_changes() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = MySettings::default();
+    settings.perstruct_saved();
+    assert_eq!(settings.perstruct_get_changes().unwrap(), vec![]);
+    settings.perstruct_touch_all();
+    let mut changes = settings.perstruct_get_changes().unwrap();
+    changes.sort();
+    let mut all = MySettings::perstruct_keys();
+    all.sort();
+    assert_eq!(
+        changes.into_iter().map(|(key, _)| key).collect::<Vec<_>>(),
+        all
+    );
+}
+
+#[test]
+fn load_into_keeps_existing_values() {
+    use pretty_assertions::assert_eq;
 
-    assert_eq!(unknown_fields, vec!["whatever".to_string()]);
+    let mut settings = MySettings::default();
+    settings.set_a(5);
+    settings.set_bar(6);
+    let result = settings.perstruct_load_into(
+        &vec![("bar", "7"), ("foo", "invalid"), ("whatever", "1")]
+            .into_iter()
+            .collect(),
+    );
+    assert_eq!(result.unknown_fields(), vec!["whatever".to_string()]);
+    assert_eq!(result.deserialization_errors().len(), 1);
+    assert_eq!(settings.a(), 5);
+    assert_eq!(settings.bar(), 7);
+    assert_eq!(
+        settings.perstruct_dirty_fields(),
+        &vec!["b"].into_iter().collect::<HashSet<_>>()
+    );
+}
+
+#[test]
+fn default_expressions() {
+    use pretty_assertions::assert_eq;
+
+    let result = ExprDefaults::from_map(&vec![("level", "\"Low\"")].into_iter().collect());
+    assert_eq!(result.deserialization_errors(), vec![]);
+    let settings = result.into_value();
+    assert_eq!(settings.foo(), &Foo {});
+    assert_eq!(settings.list(), &vec![1, 2, 3]);
+    assert_eq!(settings.level(), &Level::Low);
+    assert_eq!(settings.timeout_secs(), 3600);
+    assert_eq!(settings.offset(), -1);
+    assert_eq!(settings.poll_interval(), &Duration::from_secs(30));
+    assert_eq!(settings.region(), "EU");
+    assert_eq!(ExprDefaults::default().level(), &Level::High);
 }
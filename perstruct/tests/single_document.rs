@@ -0,0 +1,110 @@
+use perstruct::perstruct;
+
+#[perstruct(single_document = "my_settings")]
+struct DocumentSettings {
+    #[perstruct(default = 1)]
+    a: i32,
+    #[perstruct(key = "names")]
+    list: Vec<String>,
+    #[perstruct(serialize_with = "to_hex", deserialize_with = "from_hex")]
+    color: u32,
+}
+
+fn to_hex(value: &u32) -> Result<String, String> {
+    Ok(format!("#{value:06x}"))
+}
+
+fn from_hex(value: &str) -> Result<u32, String> {
+    let hex = value.strip_prefix('#').ok_or("missing #")?;
+    u32::from_str_radix(hex, 16).map_err(|e| e.to_string())
+}
+
+#[test]
+fn changes_are_a_single_document() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = DocumentSettings::from_document("{}").into_value();
+    assert_eq!(
+        settings.perstruct_get_changes().unwrap(),
+        vec![(
+            "my_settings",
+            r##"{"a":1,"color":"#000000","names":[]}"##.to_string()
+        )]
+    );
+    settings.perstruct_saved();
+    assert_eq!(settings.perstruct_get_changes().unwrap(), vec![]);
+
+    settings.set_color(0xff0000);
+    assert_eq!(
+        settings.perstruct_dirty_fields(),
+        &["color"].into_iter().collect()
+    );
+    let changes = settings.perstruct_get_changes().unwrap();
+    assert_eq!(
+        changes,
+        vec![(
+            "my_settings",
+            r##"{"a":1,"color":"#ff0000","names":[]}"##.to_string()
+        )]
+    );
+
+    let result = DocumentSettings::from_document(&changes[0].1);
+    assert_eq!(result.deserialization_errors(), vec![]);
+    assert_eq!(result.value().color(), 0xff0000);
+    assert_eq!(result.value().perstruct_get_changes().unwrap(), vec![]);
+}
+
+#[test]
+fn from_document_reports_errors() {
+    use pretty_assertions::assert_eq;
+
+    let result = DocumentSettings::from_document(r#"{"a":"x","other":1}"#);
+    assert_eq!(result.value().a(), 1);
+    assert_eq!(result.unknown_fields(), vec!["other".to_string()]);
+    assert_eq!(result.deserialization_errors()[0].key, "a");
+
+    let result = DocumentSettings::from_document("[1, 2]");
+    assert_eq!(result.deserialization_errors().len(), 1);
+    assert_eq!(result.deserialization_errors()[0].key, "my_settings");
+    assert_eq!(
+        result.value().perstruct_dirty_fields().len(),
+        DocumentSettings::perstruct_keys().len()
+    );
+}
+
+#[test]
+fn pending_changes_count_the_document_once() {
+    use perstruct::SaveScope;
+    use pretty_assertions::assert_eq;
+
+    let mut settings = DocumentSettings::from_document("{}").into_value();
+    assert_eq!(settings.perstruct_dirty_fields().len(), 3);
+    assert_eq!(settings.perstruct_pending_for(SaveScope::All), 1);
+    settings.perstruct_saved();
+    assert_eq!(settings.perstruct_pending_for(SaveScope::All), 0);
+}
+
+#[test]
+fn for_each_change_streams_the_document() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = DocumentSettings::from_document("{}").into_value();
+    settings.perstruct_saved();
+    let mut written = vec![];
+    settings
+        .perstruct_for_each_change(|key, value| {
+            written.push((key, value));
+            Ok(())
+        })
+        .unwrap();
+    assert!(written.is_empty());
+
+    settings.set_a(2);
+    settings
+        .perstruct_for_each_change(|key, value| {
+            written.push((key, value));
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(written, settings.perstruct_get_changes().unwrap());
+}
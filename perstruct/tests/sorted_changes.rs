@@ -0,0 +1,48 @@
+use std::collections::BTreeMap;
+
+use perstruct::perstruct;
+
+#[perstruct(version = 3)]
+struct EditorSettings {
+    zoom: u8,
+    #[perstruct(key = "editor.font")]
+    font: String,
+    autosave: bool,
+}
+
+#[test]
+fn changes_are_sorted_by_key() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = EditorSettings::default();
+    settings.perstruct_mark_all_dirty();
+    assert_eq!(
+        settings.perstruct_get_changes_sorted().unwrap(),
+        BTreeMap::from([
+            ("__version", "3".to_string()),
+            ("autosave", "false".to_string()),
+            ("editor.font", "\"\"".to_string()),
+            ("zoom", "0".to_string()),
+        ])
+    );
+    assert_eq!(
+        EditorSettings::perstruct_keys(),
+        vec!["zoom", "editor.font", "autosave"]
+    );
+
+    settings.perstruct_saved();
+    settings.set_zoom(2);
+    settings.set_autosave(true);
+    assert_eq!(
+        settings
+            .perstruct_get_changes_sorted()
+            .unwrap()
+            .into_iter()
+            .collect::<Vec<_>>(),
+        vec![
+            ("__version", "3".to_string()),
+            ("autosave", "true".to_string()),
+            ("zoom", "2".to_string()),
+        ]
+    );
+}
@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+
+use perstruct::{perstruct, DEFAULT_STORE};
+
+#[perstruct]
+struct RoutedSettings {
+    username: String,
+    #[perstruct(store = "secure")]
+    token: String,
+    #[perstruct(store = "secure", key = "pin_code")]
+    pin: u32,
+    volume: u8,
+}
+
+#[test]
+fn stores_and_keys() {
+    use pretty_assertions::assert_eq;
+
+    assert_eq!(RoutedSettings::perstruct_stores(), &["default", "secure"]);
+    assert_eq!(
+        RoutedSettings::perstruct_keys_for_store(DEFAULT_STORE),
+        vec!["username", "volume"]
+    );
+    assert_eq!(
+        RoutedSettings::perstruct_keys_for_store("secure"),
+        vec!["token", "pin_code"]
+    );
+    assert_eq!(
+        RoutedSettings::perstruct_keys_for_store("unknown"),
+        Vec::<&str>::new()
+    );
+}
+
+#[test]
+fn loading_only_accepts_keys_from_the_matching_store() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = RoutedSettings::default();
+    let result = settings.perstruct_load_store(
+        DEFAULT_STORE,
+        &vec![("username", "\"alice\""), ("token", "\"leaked\"")]
+            .into_iter()
+            .collect(),
+    );
+    assert_eq!(result.unknown_fields(), vec!["token".to_string()]);
+    assert_eq!(settings.username(), "alice");
+    assert_eq!(settings.token(), "");
+
+    let result = settings.perstruct_load_store(
+        "secure",
+        &vec![("token", "\"secret\""), ("volume", "3")]
+            .into_iter()
+            .collect(),
+    );
+    assert_eq!(result.unknown_fields(), vec!["volume".to_string()]);
+    assert_eq!(settings.token(), "secret");
+    assert_eq!(settings.volume(), 0);
+}
+
+#[test]
+fn changes_are_isolated_per_store() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = RoutedSettings::default();
+    settings.set_username("bob".to_string());
+    settings.set_pin(1234);
+
+    assert_eq!(
+        settings
+            .perstruct_get_changes_for_store(DEFAULT_STORE)
+            .unwrap(),
+        vec![("username", "\"bob\"".to_string())]
+    );
+    assert_eq!(
+        settings.perstruct_get_changes_for_store("secure").unwrap(),
+        vec![("pin_code", "1234".to_string())]
+    );
+    assert_eq!(
+        settings.perstruct_get_changes_for_store("unknown").unwrap(),
+        vec![]
+    );
+}
+
+#[test]
+fn pending_changes_per_store() {
+    use perstruct::SaveScope;
+    use pretty_assertions::assert_eq;
+
+    let mut settings = RoutedSettings::default();
+    settings.set_token("t".to_string());
+    settings.set_pin(1234);
+    assert_eq!(settings.perstruct_pending_for(SaveScope::All), 2);
+    assert_eq!(
+        settings.perstruct_pending_for(SaveScope::Store(DEFAULT_STORE)),
+        0
+    );
+    assert_eq!(
+        settings.perstruct_pending_for(SaveScope::Store("secure")),
+        2
+    );
+    assert_eq!(
+        settings.perstruct_pending_for(SaveScope::Store("unknown")),
+        0
+    );
+    assert_eq!(
+        settings.perstruct_pending_for(SaveScope::Store("secure")),
+        settings
+            .perstruct_get_changes_for_store("secure")
+            .unwrap()
+            .len()
+    );
+}
+
+#[perstruct(version = 3)]
+struct VersionedSettings {
+    volume: u8,
+    muted: bool,
+}
+
+#[test]
+fn pending_changes_count_the_version() {
+    use perstruct::SaveScope;
+    use pretty_assertions::assert_eq;
+
+    let mut settings =
+        VersionedSettings::from_map(&HashMap::from([("volume", "1"), ("muted", "false")]))
+            .into_value();
+    assert_eq!(settings.perstruct_pending_for(SaveScope::All), 0);
+    settings.set_volume(7);
+    assert_eq!(settings.perstruct_pending_for(SaveScope::All), 2);
+    assert_eq!(
+        settings.perstruct_pending_for(SaveScope::All),
+        settings.perstruct_get_changes().unwrap().len()
+    );
+    // The version isn't routed to a store
+    assert_eq!(
+        settings.perstruct_pending_for(SaveScope::Store(DEFAULT_STORE)),
+        1
+    );
+}
+
+#[perstruct(preserve_unknown)]
+struct PreservingSettings {
+    volume: u8,
+}
+
+#[test]
+fn pending_changes_count_preserved_unknown_entries() {
+    use perstruct::SaveScope;
+    use pretty_assertions::assert_eq;
+
+    let map = HashMap::from([("volume", "1"), ("theme", "\"dark\""), ("font", "12")]);
+    let mut settings = PreservingSettings::from_map(&map).into_value();
+    assert_eq!(settings.perstruct_pending_for(SaveScope::All), 0);
+    settings.set_volume(7);
+    assert_eq!(settings.perstruct_pending_for(SaveScope::All), 3);
+    assert_eq!(
+        settings.perstruct_pending_for(SaveScope::All),
+        settings.perstruct_get_changes().unwrap().len()
+    );
+}
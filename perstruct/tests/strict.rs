@@ -0,0 +1,63 @@
+use perstruct::{perstruct, PerstructLoadError};
+
+#[perstruct]
+struct StrictSettings {
+    a: i32,
+    b: String,
+}
+
+#[test]
+fn strict_load_succeeds_for_known_keys() {
+    let settings =
+        StrictSettings::from_map_strict(&vec![("a", "1"), ("b", "\"x\"")].into_iter().collect())
+            .unwrap();
+    assert_eq!(settings.a(), 1);
+    assert_eq!(settings.b(), "x");
+}
+
+#[test]
+fn strict_load_reports_unknown_keys_and_errors() {
+    use pretty_assertions::assert_eq;
+
+    fn load() -> Result<StrictSettings, Box<dyn std::error::Error>> {
+        Ok(StrictSettings::from_map_strict(
+            &vec![("a", "\"one\""), ("legacy", "1")]
+                .into_iter()
+                .collect(),
+        )?)
+    }
+
+    let error = load().err().unwrap();
+    assert_eq!(
+        error.to_string(),
+        "failed to load settings; unknown keys: legacy; \
+         a: invalid type: string \"one\", expected i32 at line 1 column 5"
+    );
+    let error = error.downcast::<PerstructLoadError>().unwrap();
+    assert_eq!(error.unknown_fields, vec!["legacy".to_string()]);
+    assert_eq!(error.deserialization_errors.len(), 1);
+    assert_eq!(error.deserialization_errors[0].key, "a");
+}
+
+#[test]
+fn unknown_keys_are_bounded() {
+    use pretty_assertions::assert_eq;
+
+    let overflow = 300_000 - perstruct::MAX_UNKNOWN_FIELDS;
+    let map = (0..300_000)
+        .map(|index| (format!("row.{index}"), "1".to_string()))
+        .chain([("a".to_string(), "7".to_string())])
+        .collect::<std::collections::HashMap<_, _>>();
+
+    let result = StrictSettings::from_map(&map);
+    assert_eq!(result.value().a(), 7);
+    assert_eq!(result.unknown_fields().len(), perstruct::MAX_UNKNOWN_FIELDS);
+    assert_eq!(result.unknown_overflow(), overflow);
+
+    let error = StrictSettings::from_map_strict(&map).err().unwrap();
+    assert_eq!(error.unknown_fields.len(), perstruct::MAX_UNKNOWN_FIELDS);
+    assert_eq!(error.unknown_overflow, overflow);
+    assert!(error
+        .to_string()
+        .ends_with(&format!(" and {overflow} more")));
+}
@@ -0,0 +1,59 @@
+use std::collections::HashMap;
+
+use perstruct::perstruct;
+
+#[perstruct]
+struct CacheSettings {
+    #[perstruct(key = "cache.size", default = 64)]
+    size: u32,
+    region: String,
+}
+
+#[perstruct(version = 2)]
+struct VersionedSettings {
+    volume: u8,
+}
+
+fn owned(entries: &[(&str, &str)]) -> HashMap<String, String> {
+    entries
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+#[test]
+fn all_fields_to_hashmap() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = CacheSettings::default();
+    settings.perstruct_saved();
+    assert_eq!(
+        settings.perstruct_to_hashmap().unwrap(),
+        owned(&[("cache.size", "64"), ("region", "\"\"")])
+    );
+
+    let map = settings.perstruct_to_hashmap().unwrap();
+    let loaded = CacheSettings::from_map(&map);
+    assert_eq!(loaded.deserialization_errors(), vec![]);
+    assert_eq!(loaded.value().perstruct_dirty_fields().len(), 0);
+
+    assert_eq!(
+        VersionedSettings::default().perstruct_to_hashmap().unwrap(),
+        owned(&[("volume", "0"), ("__version", "2")])
+    );
+}
+
+#[test]
+fn changes_to_hashmap() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = CacheSettings::default();
+    settings.perstruct_saved();
+    assert_eq!(settings.perstruct_changes_to_hashmap().unwrap(), owned(&[]));
+
+    settings.set_region("eu-west".to_string());
+    assert_eq!(
+        settings.perstruct_changes_to_hashmap().unwrap(),
+        owned(&[("region", "\"eu-west\"")])
+    );
+}
@@ -0,0 +1,59 @@
+use perstruct::{perstruct, PerstructFieldError};
+
+#[perstruct]
+struct ThemeSettings {
+    #[perstruct(try_default_fn = "themes::load_default_theme")]
+    theme: String,
+    #[perstruct(try_default_fn = "load_default_palette")]
+    palette: Vec<String>,
+    #[perstruct(default = 12)]
+    font_size: u8,
+}
+
+mod themes {
+    pub fn load_default_theme() -> Result<String, String> {
+        Ok("dark".to_string())
+    }
+}
+
+fn load_default_palette() -> Result<Vec<String>, String> {
+    Err("palette.json not found".to_string())
+}
+
+#[test]
+fn try_default_reports_failing_defaults() {
+    use pretty_assertions::assert_eq;
+
+    assert_eq!(
+        ThemeSettings::try_default().err().unwrap(),
+        vec![("palette", "palette.json not found".to_string())]
+    );
+    let settings = ThemeSettings::default();
+    assert_eq!(settings.theme(), "dark");
+    assert!(settings.palette().is_empty());
+    assert_eq!(settings.font_size(), 12);
+}
+
+#[test]
+fn try_from_map_collects_default_failures() {
+    use pretty_assertions::assert_eq;
+
+    let result =
+        ThemeSettings::try_from_map(&vec![("font_size", "14")].into_iter().collect()).unwrap();
+    assert_eq!(
+        result.deserialization_errors(),
+        vec![PerstructFieldError {
+            key: "palette",
+            message: "palette.json not found".to_string(),
+            line: 0,
+            column: 0,
+        }]
+    );
+    assert_eq!(result.value().theme(), "dark");
+    assert_eq!(result.value().font_size(), 14);
+
+    let result =
+        ThemeSettings::try_from_map(&vec![("palette", "[\"red\"]")].into_iter().collect()).unwrap();
+    assert!(result.deserialization_errors().is_empty());
+    assert_eq!(result.value().palette(), &vec!["red".to_string()]);
+}
@@ -0,0 +1,126 @@
+use perstruct::perstruct;
+
+#[perstruct(into_setters)]
+struct WindowSettings {
+    #[perstruct(default = 800)]
+    width: u32,
+    #[perstruct(default = 600)]
+    height: u32,
+    title: String,
+    #[perstruct(dedup)]
+    recent: Vec<String>,
+}
+
+#[perstruct]
+struct Wrapper<T> {
+    value: T,
+    count: u8,
+}
+
+/// Named like the borrow the updater holds.
+#[perstruct]
+struct Prefs {
+    #[perstruct(default = 1)]
+    settings: i32,
+}
+
+#[perstruct]
+struct Release {
+    commit: String,
+    channel: String,
+}
+
+#[perstruct]
+struct Build {
+    #[perstruct(readonly)]
+    commit: String,
+}
+
+#[test]
+fn commit_sets_all_values() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = WindowSettings::default();
+    settings
+        .perstruct_update()
+        .width(1024u32)
+        .height(768u32)
+        .recent(vec!["a".to_string(), "a".to_string()])
+        .commit();
+    assert_eq!(settings.width(), 1024);
+    assert_eq!(settings.height(), 768);
+    // The values go through the setters
+    assert_eq!(settings.recent(), &vec!["a".to_string()]);
+    let mut changes = settings.perstruct_get_changes().unwrap();
+    changes.sort();
+    assert_eq!(
+        changes,
+        vec![
+            ("height", "768".to_string()),
+            ("recent", r#"["a"]"#.to_string()),
+            ("width", "1024".to_string()),
+        ]
+    );
+}
+
+fn failing_title() -> String {
+    panic!("computing the title failed")
+}
+
+#[test]
+fn nothing_changes_without_commit() {
+    let mut settings = WindowSettings::default();
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        settings
+            .perstruct_update()
+            .width(1024u32)
+            .title(failing_title())
+            .commit();
+    }));
+    assert!(result.is_err());
+    assert_eq!(settings.width(), 800);
+    assert!(settings.perstruct_dirty_fields().is_empty());
+
+    #[allow(unused_must_use)]
+    settings.perstruct_update().height(1u32);
+    assert_eq!(settings.height(), 600);
+    assert!(settings.perstruct_dirty_fields().is_empty());
+}
+
+#[test]
+fn generic_structs() {
+    let mut wrapper = Wrapper::<String>::default();
+    wrapper
+        .perstruct_update()
+        .value("x".to_string())
+        .count(2)
+        .commit();
+    assert_eq!(wrapper.value(), "x");
+    assert_eq!(wrapper.count(), 2);
+    assert!(wrapper.is_value_dirty());
+}
+
+#[test]
+fn field_named_settings() {
+    let mut prefs = Prefs::default();
+    prefs.perstruct_update().settings(2).commit();
+    assert_eq!(prefs.settings(), 2);
+    assert!(prefs.is_settings_dirty());
+}
+
+#[test]
+fn field_named_commit_uses_its_setter_name() {
+    use pretty_assertions::assert_eq;
+
+    let mut release = Release::default();
+    release
+        .perstruct_update()
+        .set_commit("abc123".to_string())
+        .channel("beta".to_string())
+        .commit();
+    assert_eq!(release.commit(), "abc123");
+    assert_eq!(release.channel(), "beta");
+
+    // Read-only fields have no updater method to clash with
+    assert_eq!(Build::default().commit(), "");
+}
@@ -0,0 +1,295 @@
+use perstruct::{perstruct, PerstructFieldError, ValidationIssue};
+
+#[perstruct(into_setters)]
+struct ServerSettings {
+    #[perstruct(validate = "valid_port", default = 8080)]
+    port: u16,
+    #[perstruct(validate = "valid_workers", default = 4)]
+    workers: u32,
+    #[perstruct(
+        validate = "valid_name",
+        one_of("main", "backup", "x"),
+        default = "main"
+    )]
+    name: String,
+}
+
+#[perstruct(serde)]
+struct SerdeSettings {
+    #[perstruct(validate = "valid_port", default = 8080)]
+    port: u16,
+}
+
+fn valid_port(port: &u16) -> Result<(), String> {
+    match port {
+        1024.. => Ok(()),
+        _ => Err(format!("port {port} is reserved")),
+    }
+}
+
+fn valid_workers(workers: &u32) -> Result<(), String> {
+    match workers {
+        1..=64 => Ok(()),
+        _ => Err(format!("{workers} workers is out of range 1..=64")),
+    }
+}
+
+fn valid_name(name: &String) -> Result<(), String> {
+    match name.len() {
+        2.. => Ok(()),
+        _ => Err(format!("{name:?} is too short")),
+    }
+}
+
+#[test]
+fn invalid_values_load_the_default() {
+    use pretty_assertions::assert_eq;
+
+    let result = ServerSettings::from_map(
+        &vec![("port", "80"), ("workers", "8"), ("name", "\"x\"")]
+            .into_iter()
+            .collect(),
+    );
+    assert_eq!(result.value().port(), 8080);
+    assert_eq!(result.value().workers(), 8);
+    assert_eq!(result.value().name(), "main");
+    let mut errors = result.deserialization_errors().to_vec();
+    errors.sort_by_key(|error| error.key);
+    assert_eq!(
+        errors,
+        vec![
+            PerstructFieldError {
+                key: "name",
+                message: r#""x" is too short"#.to_string(),
+                line: 0,
+                column: 0,
+            },
+            PerstructFieldError {
+                key: "port",
+                message: "port 80 is reserved".to_string(),
+                line: 0,
+                column: 0,
+            },
+        ]
+    );
+    assert!(result.value().is_port_dirty());
+}
+
+#[test]
+fn setters_reject_invalid_values() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = ServerSettings::default();
+    assert_eq!(settings.set_port(9000u16), Ok(()));
+    settings.perstruct_saved();
+
+    // The field keeps its value and nothing is persisted
+    assert_eq!(
+        settings.set_port(22u16),
+        Err("port 22 is reserved".to_string())
+    );
+    assert_eq!(settings.port(), 9000);
+    assert!(!settings.is_port_dirty());
+    assert_eq!(settings.perstruct_get_changes().unwrap(), vec![]);
+
+    assert_eq!(
+        settings.update_workers(|workers| *workers = 100),
+        Err("100 workers is out of range 1..=64".to_string())
+    );
+    assert_eq!(settings.workers(), 4);
+    assert!(!settings.is_workers_dirty());
+    assert_eq!(settings.update_workers(|workers| *workers += 1), Ok(()));
+    assert_eq!(settings.workers(), 5);
+    assert!(settings.is_workers_dirty());
+
+    // The field handle ignores rejected values
+    ServerSettings::PORT.set(&mut settings, 80);
+    assert_eq!(settings.port(), 9000);
+}
+
+#[test]
+fn updater_commits_nothing_when_a_value_is_rejected() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = ServerSettings::default();
+    assert_eq!(
+        settings
+            .perstruct_update()
+            .workers(8u32)
+            .port(80u16)
+            .commit(),
+        Err("port 80 is reserved".to_string())
+    );
+    assert_eq!(settings.workers(), 4);
+    assert_eq!(settings.perstruct_get_changes().unwrap(), vec![]);
+
+    assert_eq!(
+        settings
+            .perstruct_update()
+            .workers(8u32)
+            .port(9000u16)
+            .commit(),
+        Ok(())
+    );
+    assert_eq!(settings.workers(), 8);
+    assert_eq!(settings.port(), 9000);
+}
+
+#[test]
+fn try_setters_return_errors() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = ServerSettings::default();
+    assert_eq!(settings.try_set_port(2000u16), Ok(()));
+    assert_eq!(
+        settings.try_set_port(443u16),
+        Err("port 443 is reserved".to_string())
+    );
+    assert_eq!(settings.port(), 2000);
+    assert_eq!(
+        settings.try_set_name("sepia"),
+        Err(r#""sepia" is not one of "main", "backup", "x""#.to_string())
+    );
+    assert_eq!(
+        settings.try_set_name("x"),
+        Err(r#""x" is too short"#.to_string())
+    );
+    assert_eq!(settings.try_set_name("backup"), Ok(()));
+    assert_eq!(settings.name(), "backup");
+}
+
+#[test]
+fn try_updates_keep_the_field_on_errors() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = ServerSettings::default();
+    assert_eq!(
+        settings.try_update_port(|port| *port -= 8000),
+        Err("port 80 is reserved".to_string())
+    );
+    assert_eq!(settings.port(), 8080);
+    assert!(!settings.is_port_dirty());
+
+    assert_eq!(settings.try_update_port(|port| *port += 1), Ok(()));
+    assert_eq!(settings.port(), 8081);
+    assert!(settings.is_port_dirty());
+}
+
+#[test]
+fn serde_treats_invalid_values_as_missing() {
+    let settings: SerdeSettings = serde_json::from_str(r#"{"port": 80}"#).unwrap();
+    assert_eq!(settings.port(), 8080);
+}
+
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Default)]
+struct Endpoint(String);
+
+fn valid_endpoint(endpoint: &Endpoint) -> Result<(), String> {
+    match endpoint.0.is_empty() {
+        false => Ok(()),
+        true => Err("empty endpoint".to_string()),
+    }
+}
+
+// `update_endpoint` and `try_update_endpoint` need `Clone`, the other methods don't
+#[perstruct]
+struct EndpointSettings {
+    #[perstruct(validate = "valid_endpoint")]
+    endpoint: Endpoint,
+}
+
+#[test]
+fn validate_without_clone() {
+    let mut settings = EndpointSettings::default();
+    assert_eq!(
+        settings.try_set_endpoint(Endpoint(String::new())),
+        Err("empty endpoint".to_string())
+    );
+}
+
+#[perstruct(validate = "check_view_settings")]
+struct ViewSettings {
+    #[perstruct(default = 0.5)]
+    min_zoom: f64,
+    #[perstruct(default = 4.0)]
+    max_zoom: f64,
+    proxy_enabled: bool,
+    proxy_host: Option<String>,
+}
+
+fn check_view_settings(settings: &ViewSettings) -> Vec<ValidationIssue> {
+    let mut issues = vec![];
+    if settings.min_zoom() > settings.max_zoom() {
+        issues.push(ValidationIssue::new(
+            ["min_zoom", "max_zoom"],
+            "the minimum zoom is above the maximum zoom",
+        ));
+    }
+    if settings.proxy_enabled() && settings.proxy_host().is_none() {
+        issues.push(ValidationIssue::new(
+            ["proxy_enabled", "proxy_host"],
+            "the proxy needs a host",
+        ));
+    }
+    issues
+}
+
+#[test]
+fn struct_validation_runs_after_loading() {
+    use pretty_assertions::assert_eq;
+
+    let result = ViewSettings::from_map(
+        &vec![
+            ("min_zoom", "8.0"),
+            ("max_zoom", "2.0"),
+            ("proxy_enabled", "true"),
+            ("proxy_host", "42"),
+        ]
+        .into_iter()
+        .collect(),
+    );
+    assert_eq!(
+        result.deserialization_errors(),
+        vec![PerstructFieldError {
+            key: "proxy_host",
+            message: "invalid type: integer `42`, expected a string at line 1 column 2".to_string(),
+            line: 1,
+            column: 2,
+        }]
+    );
+    assert_eq!(
+        result.validation_issues(),
+        vec![
+            ValidationIssue::new(
+                ["min_zoom", "max_zoom"],
+                "the minimum zoom is above the maximum zoom"
+            ),
+            ValidationIssue::new(["proxy_enabled", "proxy_host"], "the proxy needs a host"),
+        ]
+    );
+    assert_eq!(
+        result.validation_issues()[1].to_string(),
+        "proxy_enabled, proxy_host: the proxy needs a host"
+    );
+
+    // Nothing is corrected
+    let mut settings = result.into_value();
+    assert_eq!(settings.min_zoom(), 8.0);
+    settings.set_min_zoom(1.0);
+    settings.set_proxy_host(Some("proxy.example.com".to_string()));
+    assert_eq!(settings.perstruct_validate(), vec![]);
+
+    let result = settings.perstruct_load_into(
+        &vec![("proxy_enabled", "true"), ("proxy_host", "null")]
+            .into_iter()
+            .collect(),
+    );
+    assert_eq!(
+        result.validation_issues(),
+        vec![ValidationIssue::new(
+            ["proxy_enabled", "proxy_host"],
+            "the proxy needs a host"
+        )]
+    );
+    assert_eq!(ServerSettings::default().perstruct_validate(), vec![]);
+}
@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+
+use perstruct::perstruct;
+use serde_derive::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Window {
+    width: u32,
+    height: u32,
+    maximized: bool,
+}
+
+impl Default for Window {
+    fn default() -> Self {
+        Window {
+            width: 800,
+            height: 600,
+            maximized: false,
+        }
+    }
+}
+
+#[perstruct]
+struct Settings {
+    #[perstruct(value_default_missing)]
+    window: Window,
+    #[perstruct(value_default_missing, default_fn = "large_window")]
+    popup: Window,
+    strict_window: Window,
+}
+
+fn large_window() -> Window {
+    Window {
+        width: 1920,
+        height: 1080,
+        maximized: true,
+    }
+}
+
+#[test]
+fn missing_members_get_their_default() {
+    use pretty_assertions::assert_eq;
+
+    let map = HashMap::from([
+        ("window", r#"{"width":1024,"height":768}"#),
+        ("popup", r#"{"width":300}"#),
+        ("strict_window", r#"{"width":1024,"height":768}"#),
+    ]);
+    let result = Settings::from_map(&map);
+    let settings = result.value();
+    assert_eq!(
+        settings.window(),
+        &Window {
+            width: 1024,
+            height: 768,
+            maximized: false,
+        }
+    );
+    assert_eq!(
+        settings.popup(),
+        &Window {
+            width: 300,
+            height: 1080,
+            maximized: true,
+        }
+    );
+    assert_eq!(result.deserialization_errors().len(), 1);
+    assert_eq!(result.deserialization_errors()[0].key, "strict_window");
+}
+
+#[test]
+fn completed_values_are_dirty() {
+    use pretty_assertions::assert_eq;
+
+    let map = HashMap::from([
+        ("window", r#"{"width":1024,"height":768}"#),
+        ("popup", r#"{"width":300,"height":200,"maximized":false}"#),
+        (
+            "strict_window",
+            r#"{"width":1,"height":2,"maximized":true}"#,
+        ),
+    ]);
+    let settings = Settings::from_map(&map).into_value();
+    assert_eq!(
+        settings.perstruct_get_changes().unwrap(),
+        vec![(
+            "window",
+            r#"{"width":1024,"height":768,"maximized":false}"#.to_string()
+        )]
+    );
+}
+
+#[test]
+fn apply_fills_in_missing_members() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = Settings::default();
+    settings
+        .perstruct_apply("window", r#"{"maximized":true}"#)
+        .unwrap();
+    assert_eq!(
+        settings.window(),
+        &Window {
+            width: 800,
+            height: 600,
+            maximized: true,
+        }
+    );
+}
@@ -0,0 +1,52 @@
+use perstruct::perstruct;
+
+#[perstruct]
+struct RecentFiles {
+    #[perstruct(dedup, max_len = 3)]
+    recent_files: Vec<String>,
+    #[perstruct(max_len = 2, truncate = "front")]
+    history: Vec<u32>,
+}
+
+#[test]
+fn normalization_on_load_marks_dirty() {
+    use pretty_assertions::assert_eq;
+
+    let result = RecentFiles::from_map(
+        &vec![
+            ("recent_files", r#"["a", "b", "a", "c", "b", "d"]"#),
+            ("history", "[1, 2]"),
+        ]
+        .into_iter()
+        .collect(),
+    );
+    let settings = result.into_value();
+    assert_eq!(settings.recent_files(), &vec!["a", "b", "c"]);
+    assert_eq!(settings.history(), &vec![1, 2]);
+    assert_eq!(
+        settings.perstruct_get_changes().unwrap(),
+        vec![("recent_files", r#"["a","b","c"]"#.to_string())]
+    );
+}
+
+#[test]
+fn normalization_after_update() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = RecentFiles::default();
+    settings.update_history(|history| history.extend([1, 2, 3]));
+    assert_eq!(settings.history(), &vec![2, 3]);
+    settings.update_recent_files(|files| {
+        files.push("a".to_string());
+        files.push("a".to_string());
+    });
+    assert_eq!(settings.recent_files(), &vec!["a"]);
+    settings.set_recent_files(vec![
+        "x".into(),
+        "y".into(),
+        "x".into(),
+        "z".into(),
+        "w".into(),
+    ]);
+    assert_eq!(settings.recent_files(), &vec!["x", "y", "z"]);
+}
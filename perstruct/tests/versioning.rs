@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+
+use perstruct::perstruct;
+
+#[perstruct(version = 2, migrate_fn = "migrate_settings")]
+struct Settings {
+    volume: u8,
+    #[perstruct(default = 1)]
+    zoom: u8,
+}
+
+/// Version 1 stored the volume as `sound_level`, version 0 had no zoom.
+fn migrate_settings(found_version: u32, map: &mut HashMap<String, String>) {
+    if found_version < 1 {
+        map.insert("zoom".to_string(), "2".to_string());
+    }
+    if let Some(level) = map.remove("sound_level") {
+        map.insert("volume".to_string(), level);
+    }
+}
+
+#[perstruct(version = 3)]
+struct UnmigratedSettings {
+    volume: u8,
+}
+
+#[test]
+fn renamed_key_is_migrated() {
+    use pretty_assertions::assert_eq;
+
+    let map = HashMap::from([("__version", "1"), ("sound_level", "7"), ("zoom", "4")]);
+    let result = Settings::from_map(&map);
+    assert_eq!(result.stored_version(), Some(1));
+    assert!(result.unknown_fields().is_empty());
+    let settings = result.into_value();
+    assert_eq!(settings.volume(), 7);
+    assert_eq!(settings.zoom(), 4);
+
+    // Migrated values are written back with the current version
+    let mut changes = settings.perstruct_get_changes().unwrap();
+    changes.sort();
+    assert_eq!(
+        changes,
+        vec![
+            ("__version", "2".to_string()),
+            ("volume", "7".to_string()),
+            ("zoom", "4".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn missing_version_is_version_0() {
+    use pretty_assertions::assert_eq;
+
+    let result = Settings::from_map(&HashMap::from([("sound_level", "3")]));
+    assert_eq!(result.stored_version(), Some(0));
+    assert_eq!(result.value().volume(), 3);
+    assert_eq!(result.value().zoom(), 2);
+}
+
+#[test]
+fn current_version_is_not_migrated() {
+    use pretty_assertions::assert_eq;
+
+    let map = HashMap::from([("__version", "2"), ("volume", "5"), ("zoom", "1")]);
+    let result = Settings::from_map(&map);
+    assert_eq!(result.stored_version(), Some(2));
+    let mut settings = result.into_value();
+    assert_eq!(settings.perstruct_get_changes().unwrap(), vec![]);
+
+    settings.set_volume(6);
+    assert_eq!(
+        settings.perstruct_get_changes().unwrap(),
+        vec![("volume", "6".to_string()), ("__version", "2".to_string())]
+    );
+}
+
+#[test]
+fn version_without_migration() {
+    use pretty_assertions::assert_eq;
+
+    let result = UnmigratedSettings::from_map(&HashMap::from([("volume", "5")]));
+    assert_eq!(result.stored_version(), Some(0));
+    let mut settings = result.into_value();
+    assert_eq!(settings.perstruct_get_changes().unwrap(), vec![]);
+    settings.set_volume(1);
+    assert_eq!(
+        settings.perstruct_get_changes().unwrap(),
+        vec![("volume", "1".to_string()), ("__version", "3".to_string())]
+    );
+    assert_eq!(
+        settings.perstruct_to_json_string().unwrap(),
+        r#"{"__version":3,"volume":1}"#
+    );
+}
+
+#[test]
+fn load_into_migrates() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = Settings::default();
+    settings.perstruct_saved();
+    let result = settings.perstruct_load_into(&HashMap::from([("sound_level", "9")]));
+    assert_eq!(result.stored_version(), Some(0));
+    assert_eq!(settings.volume(), 9);
+    assert!(settings.is_zoom_dirty());
+}
@@ -0,0 +1,38 @@
+use perstruct::perstruct;
+
+#[perstruct(view)]
+struct RenderSettings {
+    #[perstruct(default = 1.0)]
+    scale: f32,
+    #[perstruct(default = true)]
+    vsync: bool,
+    #[perstruct(default = "Inter")]
+    font: String,
+    accent: Option<String>,
+    #[perstruct(skip)]
+    frames: u64,
+}
+
+#[test]
+fn view_copies_primitives_and_borrows_the_rest() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = RenderSettings::default();
+    settings.set_scale(2.0);
+    settings.set_accent(Some("teal".to_string()));
+    settings.frames = 3;
+
+    let view = settings.perstruct_view();
+    let RenderSettingsView {
+        scale,
+        vsync,
+        font,
+        accent,
+        ..
+    } = view;
+    assert_eq!(scale, 2.0);
+    assert!(vsync);
+    assert_eq!(font, "Inter");
+    assert_eq!(accent.as_deref(), Some("teal"));
+    assert!(std::ptr::eq(view.font, settings.font()));
+}
@@ -0,0 +1,91 @@
+use std::io::Write;
+
+use perstruct::{perstruct, ChangeSink};
+
+#[perstruct(value_format = "plain")]
+struct Settings {
+    name: String,
+    samples: Vec<u32>,
+}
+
+/// Writes `key=value` lines into a single buffer, like a backend's write batch.
+#[derive(Default)]
+struct Batch {
+    buffer: Vec<u8>,
+}
+
+impl ChangeSink for Batch {
+    fn entry(
+        &mut self,
+        key: &'static str,
+        value_writer: &mut dyn FnMut(&mut dyn Write) -> std::io::Result<()>,
+    ) -> std::io::Result<()> {
+        write!(self.buffer, "{key}=")?;
+        value_writer(&mut self.buffer)?;
+        writeln!(self.buffer)
+    }
+}
+
+#[test]
+fn changes_are_written_into_the_sink() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = Settings::default();
+    settings.set_name("plain".to_string());
+    settings.perstruct_saved();
+    settings.set_samples(vec![1, 2, 3]);
+    let mut batch = Batch::default();
+    settings.perstruct_write_changes(&mut batch).unwrap();
+    assert_eq!(
+        String::from_utf8(batch.buffer).unwrap(),
+        "samples=[1,2,3]\n"
+    );
+
+    settings.set_name("a b".to_string());
+    let mut changes = vec![];
+    settings.perstruct_write_changes(&mut changes).unwrap();
+    changes.sort();
+    assert_eq!(changes, {
+        let mut changes = settings.perstruct_get_changes().unwrap();
+        changes.sort();
+        changes
+    });
+    assert!(changes.contains(&("name", "a b".to_string())));
+}
+
+#[test]
+fn large_values_are_written_whole() {
+    use pretty_assertions::assert_eq;
+
+    let mut settings = Settings::default();
+    // A few MB, like the field of the `write_changes` benchmark
+    settings.set_samples((0..500_000).collect());
+    let mut batch = Batch::default();
+    settings.perstruct_write_changes(&mut batch).unwrap();
+    let changes = settings.perstruct_get_changes().unwrap();
+    assert!(batch.buffer.len() > 3_000_000);
+    assert_eq!(
+        String::from_utf8(batch.buffer).unwrap(),
+        format!("samples={}\n", changes[0].1)
+    );
+}
+
+#[test]
+fn sink_errors_stop_writing() {
+    struct Failing;
+
+    impl ChangeSink for Failing {
+        fn entry(
+            &mut self,
+            _key: &'static str,
+            _value_writer: &mut dyn FnMut(&mut dyn Write) -> std::io::Result<()>,
+        ) -> std::io::Result<()> {
+            Err(std::io::Error::other("backend unavailable"))
+        }
+    }
+
+    let mut settings = Settings::default();
+    settings.set_name("x".to_string());
+    let error = settings.perstruct_write_changes(&mut Failing).unwrap_err();
+    assert_eq!(error.to_string(), "backend unavailable");
+}
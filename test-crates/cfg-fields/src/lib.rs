@@ -0,0 +1,19 @@
+//! Settings with a field gated behind the `telemetry` feature.
+
+use perstruct::perstruct;
+
+/// Settings using the gated field in all generated methods.
+#[perstruct(observe = "channel", track_loaded, serde)]
+pub struct GatedSettings {
+    /// Always present.
+    #[perstruct(default = 3)]
+    pub retries: u32,
+    /// Only present with the `telemetry` feature.
+    #[cfg(feature = "telemetry")]
+    #[perstruct(key = "telemetry-endpoint", alias = "endpoint")]
+    pub telemetry_endpoint: String,
+    /// A skipped field only present with the `telemetry` feature.
+    #[cfg(feature = "telemetry")]
+    #[perstruct(skip)]
+    pub telemetry_buffer: Vec<u8>,
+}
@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+
+use perstruct_test_cfg_fields::GatedSettings;
+
+#[test]
+fn keys_follow_the_feature() {
+    #[cfg(feature = "telemetry")]
+    assert_eq!(
+        GatedSettings::perstruct_keys(),
+        vec!["retries", "telemetry-endpoint"]
+    );
+    #[cfg(not(feature = "telemetry"))]
+    assert_eq!(GatedSettings::perstruct_keys(), vec!["retries"]);
+}
+
+#[test]
+fn field_handles_expose_the_cfg_predicate() {
+    assert_eq!(GatedSettings::RETRIES.cfg(), None);
+    #[cfg(feature = "telemetry")]
+    assert_eq!(
+        GatedSettings::TELEMETRY_ENDPOINT.cfg(),
+        Some(r#"feature = "telemetry""#)
+    );
+}
+
+#[test]
+fn loads_gated_field_only_with_the_feature() {
+    let map = HashMap::from([("retries", "5"), ("endpoint", "\"https://example.com\"")]);
+    let result = GatedSettings::from_map(&map);
+    assert_eq!(result.value.retries(), 5);
+    #[cfg(feature = "telemetry")]
+    {
+        assert!(result.unknown_fields.is_empty());
+        assert_eq!(result.value.telemetry_endpoint(), "https://example.com");
+    }
+    #[cfg(not(feature = "telemetry"))]
+    assert_eq!(result.unknown_fields, vec!["endpoint".to_string()]);
+}
+
+#[test]
+fn changes_follow_the_feature() {
+    let mut settings = GatedSettings::default();
+    settings.set_retries(1);
+    #[cfg(feature = "telemetry")]
+    settings.set_telemetry_endpoint("https://example.com".to_string());
+    let mut changes = settings.perstruct_get_changes().unwrap();
+    changes.sort();
+    #[cfg(feature = "telemetry")]
+    assert_eq!(
+        changes,
+        vec![
+            ("retries", "1".to_string()),
+            ("telemetry-endpoint", "\"https://example.com\"".to_string())
+        ]
+    );
+    #[cfg(not(feature = "telemetry"))]
+    assert_eq!(changes, vec![("retries", "1".to_string())]);
+}
@@ -0,0 +1,6 @@
+//! Re-exports used as a facade by the tests of this crate.
+
+pub mod facade {
+    pub use ::json as serde_json;
+    pub use ::perstruct;
+}
@@ -0,0 +1,28 @@
+use perstruct::perstruct;
+use perstruct_test_renamed_serde_json::facade;
+
+#[perstruct(
+    crate = "perstruct_test_renamed_serde_json::facade::perstruct",
+    serde_json = "perstruct_test_renamed_serde_json::facade::serde_json"
+)]
+struct FacadeSettings {
+    #[perstruct(default = 3)]
+    retries: u8,
+    name: String,
+}
+
+#[test]
+fn generated_code_uses_overridden_paths() {
+    let result: facade::perstruct::PerstructLoadResult<FacadeSettings> =
+        FacadeSettings::from_map(&vec![("name", "\"facade\"")].into_iter().collect());
+    let mut settings = result.value;
+    assert_eq!(settings.name(), "facade");
+    assert_eq!(settings.retries(), 3);
+
+    settings.perstruct_saved();
+    settings.set_retries(5);
+    assert_eq!(
+        settings.perstruct_get_changes().unwrap(),
+        vec![("retries", "5".to_string())]
+    );
+}
@@ -0,0 +1,27 @@
+//! Settings used to check that the code generated by `perstruct` passes strict lints.
+#![deny(missing_docs, clippy::pedantic, unused_qualifications)]
+
+use perstruct::perstruct;
+
+/// Settings using most of the field attributes.
+#[perstruct(serde)]
+pub struct StrictSettings {
+    /// A plain field.
+    pub name: String,
+    /// A field with a renamed key and a default.
+    #[perstruct(key = "retry_count", default = 3)]
+    pub retries: u32,
+    /// A field with a default function.
+    #[perstruct(default_fn = "default_tags", dedup, max_len = 4, alias = "labels")]
+    pub tags: Vec<String>,
+    /// A field routed to another store.
+    #[perstruct(store = "secure")]
+    pub token: Option<String>,
+    /// A skipped field.
+    #[perstruct(skip)]
+    pub cache: Vec<u8>,
+}
+
+fn default_tags() -> Vec<String> {
+    vec!["default".to_string()]
+}
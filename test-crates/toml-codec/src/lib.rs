@@ -0,0 +1,35 @@
+//! Settings stored as toml values.
+
+use perstruct::{perstruct, PerstructCodec};
+
+/// Codec storing values as toml values, such as `"text"` or `[1, 2]`.
+pub struct TomlCodec;
+
+impl PerstructCodec for TomlCodec {
+    fn encode<T: serde::Serialize>(value: &T) -> Result<String, String> {
+        let mut encoded = String::new();
+        value
+            .serialize(toml::ser::ValueSerializer::new(&mut encoded))
+            .map_err(|e| e.to_string())?;
+        Ok(encoded)
+    }
+
+    fn decode<T: serde::de::DeserializeOwned>(value: &str) -> Result<T, String> {
+        let deserializer = toml::de::ValueDeserializer::parse(value).map_err(|e| e.to_string())?;
+        T::deserialize(deserializer).map_err(|e| e.to_string())
+    }
+}
+
+/// Settings stored with `TomlCodec`.
+#[perstruct(codec = "TomlCodec")]
+pub struct EditorSettings {
+    /// Name of the color theme.
+    #[perstruct(default_fn = "default_theme")]
+    pub theme: String,
+    /// Columns of the rulers.
+    pub rulers: Vec<u32>,
+}
+
+fn default_theme() -> String {
+    "light".to_string()
+}
@@ -0,0 +1,20 @@
+use perstruct_test_toml_codec::EditorSettings;
+
+#[test]
+fn values_are_stored_as_toml() {
+    let result = EditorSettings::from_map(
+        &vec![("theme", "'dark'"), ("rulers", "[80, 100]")]
+            .into_iter()
+            .collect(),
+    );
+    assert!(result.deserialization_errors.is_empty());
+    let mut settings = result.value;
+    assert_eq!(settings.theme(), "dark");
+    assert_eq!(settings.rulers(), &vec![80, 100]);
+
+    settings.set_rulers(vec![120]);
+    assert_eq!(
+        settings.perstruct_get_changes().unwrap(),
+        vec![("rulers", "[120]".to_string())]
+    );
+}
@@ -0,0 +1,21 @@
+//! Settings declared in a crate without a `serde_json` dependency.
+
+use perstruct::perstruct;
+
+/// Settings stored as separate keys.
+#[perstruct(preserve_unknown, version = 1)]
+pub struct EditorSettings {
+    /// Width of a tab in spaces.
+    #[perstruct(default = 4)]
+    pub tab_width: u8,
+    /// Recently opened files, newest first.
+    #[perstruct(dedup, max_len = 3)]
+    pub recent_files: Vec<String>,
+}
+
+/// Settings stored as a single document.
+#[perstruct(single_document = "window")]
+pub struct WindowSettings {
+    /// Whether the window is maximized.
+    pub maximized: bool,
+}
@@ -0,0 +1,31 @@
+use perstruct_test_without_serde_json::{EditorSettings, WindowSettings};
+
+#[test]
+fn generated_code_uses_reexported_serde_json() {
+    let mut settings = EditorSettings::from_map(
+        &vec![("tab_width", "2"), ("theme", "\"dark\"")]
+            .into_iter()
+            .collect(),
+    )
+    .value;
+    assert_eq!(settings.tab_width(), 2);
+
+    settings.perstruct_saved();
+    settings.set_recent_files(vec!["a.rs".to_string(), "a.rs".to_string()]);
+    assert_eq!(
+        settings.perstruct_get_changes().unwrap(),
+        vec![
+            ("recent_files", r#"["a.rs"]"#.to_string()),
+            ("__version", "1".to_string()),
+            ("theme", r#""dark""#.to_string())
+        ]
+    );
+
+    let mut window = WindowSettings::from_document(r#"{"maximized":true}"#).value;
+    assert!(window.maximized());
+    window.set_maximized(false);
+    assert_eq!(
+        window.perstruct_get_changes().unwrap(),
+        vec![("window", r#"{"maximized":false}"#.to_string())]
+    );
+}